@@ -17,6 +17,7 @@ async fn main() {
             Ok(endpoint) => Some(endpoint),
             Err(_) => None,
         },
+        ..Default::default()
     };
     let mut bot = Wechaty::new(PuppetService::new(options).await.unwrap());
 
@@ -31,7 +32,7 @@ async fn main() {
     .on_login(
         async move |payload: LoginPayload<PuppetService>, ctx: WechatyContext<PuppetService>| {
             println!("User {} has logged in", payload.contact);
-            println!("Contact list: {:?}", ctx.contact_find_all(None).await);
+            println!("Contacts and rooms loaded: {:?}", ctx.warmup().await);
         },
     )
     .on_logout(async move |payload: LogoutPayload<PuppetService>, _ctx| {
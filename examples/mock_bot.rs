@@ -0,0 +1,59 @@
+//! A ding-dong bot running against `PuppetMock`, which needs no gateway or network:
+//! `cargo run --example mock_bot`.
+use futures::StreamExt;
+use wechaty::prelude::*;
+use wechaty_puppet::{fixtures, ContactGender, ContactPayload, EventMessagePayload, Puppet, PuppetEvent};
+use wechaty_puppet_mock::PuppetMock;
+
+/// Seed a contact and a message from them, fire the message event, and return the texts the bot replied with.
+///
+/// The bot takes messages from its message feed, so it replies as soon as the message arrives instead of after a
+/// fixed delay.
+async fn run() -> Vec<String> {
+    let mock = PuppetMock::default();
+    mock.add_contact(ContactPayload {
+        gender: ContactGender::Female,
+        ..fixtures::contact("mock-alice", "Alice")
+    });
+    mock.add_message(wechaty_puppet::MessagePayload {
+        to_id: "mock-bot".to_owned(),
+        ..fixtures::message("mock-ding", "mock-alice", "ding")
+    });
+
+    let puppet = Puppet::new(mock.clone());
+    let bot = Wechaty::new(puppet.clone());
+    let mut feed = Box::pin(bot.message_feed());
+
+    puppet
+        .self_addr()
+        .do_send(PuppetEvent::Message(EventMessagePayload {
+            message_id: "mock-ding".to_owned(),
+        }))
+        .unwrap();
+    if let Some(mut message) = feed.next().await {
+        println!("Got message: {}", message);
+        if message.text().as_deref() == Some("ding") {
+            match message.reply_text("dong".to_owned()).await {
+                Ok(_) => println!("REPLY: dong"),
+                Err(e) => println!("Failed to send message, reason: {}", e),
+            }
+        }
+    }
+    mock.sent_texts("mock-alice")
+}
+
+#[wechaty_rt::main]
+async fn main() {
+    env_logger::init();
+    println!("Replies sent to Alice: {:?}", run().await);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[wechaty_rt::test]
+    async fn bot_replies_dong_to_ding() {
+        assert_eq!(run().await, vec!["dong"]);
+    }
+}
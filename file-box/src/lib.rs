@@ -1,16 +1,239 @@
 use std::fmt;
 
-// TODO: FileBox Implementation
-pub struct FileBox {}
+/// Known file types, as `(extension, mime type)`.
+const MIME_TYPES: [(&str, &str); 14] = [
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("amr", "audio/amr"),
+    ("silk", "audio/silk"),
+    ("mp4", "video/mp4"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("txt", "text/plain"),
+    ("json", "application/json"),
+];
+
+/// Number of base64 characters decoded to sniff the content, which is 12 bytes.
+const SNIFF_LEN: usize = 16;
+
+/// Where the content of a file box comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileBoxSource {
+    Base64(String),
+    Url(String),
+    QrCode(String),
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct FileBox {
+    name: String,
+    source: FileBoxSource,
+}
+
+impl FileBox {
+    pub fn from_base64(base64: String, name: String) -> Self {
+        Self {
+            name,
+            source: FileBoxSource::Base64(base64),
+        }
+    }
+
+    pub fn from_url(url: String, name: String) -> Self {
+        Self {
+            name,
+            source: FileBoxSource::Url(url),
+        }
+    }
+
+    pub fn from_qr_code(qr_code: String) -> Self {
+        Self {
+            name: "qrcode.png".to_owned(),
+            source: FileBoxSource::QrCode(qr_code),
+        }
+    }
+
+    /// Set the name, an empty name keeps the current name or derives `file.<extension>` from the content.
+    pub fn with_name(mut self, name: String) -> Self {
+        if !name.is_empty() {
+            self.name = name;
+        } else if self.name.is_empty() {
+            self.name = match self.extension() {
+                Some(extension) => format!("file.{}", extension),
+                None => "file".to_owned(),
+            };
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &FileBoxSource {
+        &self.source
+    }
+
+    /// Guess the mime type, first from the extension of the name, then from the magic bytes of base64 content.
+    pub fn mime_type(&self) -> Option<&'static str> {
+        let extension = extension_of(&self.name);
+        match extension.as_deref().and_then(mime_type_of) {
+            Some(mime_type) => Some(mime_type),
+            None => match &self.source {
+                FileBoxSource::Base64(base64) => sniff(base64),
+                FileBoxSource::QrCode(_) => Some("image/png"),
+                _ => None,
+            },
+        }
+    }
+
+    /// Get the extension without the leading dot, guessed from the mime type if the name has no extension.
+    ///
+    /// Useful to save a received image or voice to disk with the right suffix.
+    pub fn extension(&self) -> Option<String> {
+        match extension_of(&self.name) {
+            Some(extension) => Some(extension),
+            None => self.mime_type().and_then(|mime_type| {
+                MIME_TYPES
+                    .iter()
+                    .find(|(_, known)| *known == mime_type)
+                    .map(|(extension, _)| extension.to_string())
+            }),
+        }
+    }
+}
+
+fn extension_of(name: &str) -> Option<String> {
+    match name.rfind('.') {
+        Some(index) if index + 1 < name.len() => Some(name[index + 1..].to_lowercase()),
+        _ => None,
+    }
+}
+
+fn mime_type_of(extension: &str) -> Option<&'static str> {
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime_type)| *mime_type)
+}
+
+/// Guess the mime type from the magic bytes at the beginning of base64 content.
+fn sniff(base64: &str) -> Option<&'static str> {
+    // Invalid base64 may hold multibyte characters, which the prefix must not cut through.
+    let prefix = base64.get(..base64.len().min(SNIFF_LEN) / 4 * 4)?;
+    let bytes = match base64::decode(prefix) {
+        Ok(bytes) => bytes,
+        Err(_) => return None,
+    };
+    if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+        Some("audio/wav")
+    } else if bytes.starts_with(b"ID3") || bytes.starts_with(&[0xff, 0xfb]) || bytes.starts_with(&[0xff, 0xf3]) {
+        Some("audio/mpeg")
+    } else if bytes.starts_with(b"#!AMR") {
+        Some("audio/amr")
+    } else if bytes.starts_with(b"#!SILK") || bytes.starts_with(b"\x02#!SILK") {
+        Some("audio/silk")
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        Some("video/mp4")
+    } else if bytes.starts_with(b"%PDF") {
+        Some("application/pdf")
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        Some("application/zip")
+    } else {
+        None
+    }
+}
 
 impl fmt::Display for FileBox {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, "")
+        write!(fmt, "{}", self.name)
     }
 }
 
 impl From<String> for FileBox {
     fn from(_: String) -> Self {
-        Self {}
+        Self {
+            name: String::new(),
+            source: FileBoxSource::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_guess_mime_type_from_name() {
+        let file_box = FileBox::from_url("https://example.com/a".to_owned(), "photo.JPG".to_owned());
+        assert_eq!(file_box.mime_type(), Some("image/jpeg"));
+        assert_eq!(file_box.extension(), Some("jpg".to_owned()));
+
+        let file_box = FileBox::from_url("https://example.com/b".to_owned(), "song.mp3".to_owned());
+        assert_eq!(file_box.mime_type(), Some("audio/mpeg"));
+
+        let file_box = FileBox::from_url("https://example.com/c".to_owned(), "movie.mp4".to_owned());
+        assert_eq!(file_box.mime_type(), Some("video/mp4"));
+    }
+
+    #[test]
+    fn can_sniff_mime_type_from_base64() {
+        let jpg = base64::encode([0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01]);
+        let file_box = FileBox::from_base64(jpg, "image".to_owned());
+        assert_eq!(file_box.mime_type(), Some("image/jpeg"));
+        assert_eq!(file_box.extension(), Some("jpg".to_owned()));
+
+        let mp3 = base64::encode(b"ID3\x03\x00\x00\x00\x00\x00\x00\x00\x00");
+        let file_box = FileBox::from_base64(mp3, String::new());
+        assert_eq!(file_box.mime_type(), Some("audio/mpeg"));
+        assert_eq!(file_box.extension(), Some("mp3".to_owned()));
+
+        let mp4 = base64::encode(b"\x00\x00\x00\x20ftypisom\x00\x00");
+        let file_box = FileBox::from_base64(mp4, String::new());
+        assert_eq!(file_box.mime_type(), Some("video/mp4"));
+        assert_eq!(file_box.extension(), Some("mp4".to_owned()));
+    }
+
+    #[test]
+    fn unknown_type_has_no_mime_type() {
+        let file_box = FileBox::from_base64(base64::encode(b"hello, world"), "data.unknown".to_owned());
+        assert_eq!(file_box.mime_type(), None);
+        assert_eq!(file_box.extension(), Some("unknown".to_owned()));
+
+        let file_box = FileBox::from_base64(base64::encode(b"hello, world"), String::new());
+        assert_eq!(file_box.mime_type(), None);
+        assert_eq!(file_box.extension(), None);
+    }
+
+    #[test]
+    fn non_ascii_base64_is_not_sniffed() {
+        let file_box = FileBox::from_base64(format!("a{}", "é".repeat(8)), String::new());
+        assert_eq!(file_box.mime_type(), None);
+    }
+
+    #[test]
+    fn can_derive_name_from_content() {
+        let jpg = base64::encode([0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01]);
+        let file_box = FileBox::from_base64(jpg.clone(), String::new());
+        assert_eq!(file_box.with_name("photo.jpg".to_owned()).name(), "photo.jpg");
+        let file_box = FileBox::from_base64(jpg, String::new());
+        assert_eq!(file_box.with_name(String::new()).name(), "file.jpg");
+        let file_box = FileBox::from_url("https://example.com/a".to_owned(), "a.pdf".to_owned());
+        assert_eq!(file_box.with_name(String::new()).name(), "a.pdf");
+        let file_box = FileBox::from_url("https://example.com/b".to_owned(), String::new());
+        assert_eq!(file_box.with_name(String::new()).name(), "file");
     }
 }
@@ -1,8 +1,185 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
 use async_trait::async_trait;
 use wechaty_puppet::*;
 
-#[derive(Debug)]
-pub struct PuppetMock {}
+/// State of a mock puppet, which is shared by the clones of the puppet but not by other puppets, like the ids.
+#[derive(Debug, Default)]
+struct MockState {
+    /// Description and corporation remark of each contact.
+    contact_remarks: BTreeMap<String, (Option<String>, Option<String>)>,
+    /// Contacts added with `PuppetMock::add_contact`.
+    contacts: BTreeMap<String, ContactPayload>,
+    /// Messages added with `PuppetMock::add_message`.
+    messages: BTreeMap<String, MessagePayload>,
+    contact_aliases: BTreeMap<String, String>,
+    /// Favorites added with `favorite_add`.
+    favorites: BTreeMap<String, FavoritePayload>,
+    room_members: BTreeMap<String, Vec<String>>,
+    room_announces: BTreeMap<String, String>,
+    /// Topics set with `room_topic_set`.
+    room_topics: BTreeMap<String, String>,
+    /// Texts sent to each conversation.
+    sent_texts: BTreeMap<String, Vec<String>>,
+    blocked_contacts: BTreeSet<String>,
+    muted_rooms: BTreeSet<String>,
+    /// Messages forwarded in each chat history message.
+    chat_histories: BTreeMap<String, Vec<MessagePayload>>,
+    /// Url cards shared in each url message.
+    url_links: BTreeMap<String, UrlLinkPayload>,
+    /// Number of calls to each counted method, see `PuppetMock::call_count`.
+    call_counts: BTreeMap<&'static str, usize>,
+}
+
+/// Mints ids for the messages and rooms created by a mock puppet, numbered from one past the seed.
+///
+/// The seed is an offset: the default seed of 0 mints `msg-1`, `msg-2` and so on, while a seed of 42 mints `msg-43`.
+///
+/// The counter is shared by the clones of a puppet but not by other puppets, so ids do not depend on other tests.
+#[derive(Debug, Clone, Default)]
+struct IdGenerator {
+    next: Arc<AtomicU64>,
+}
+
+impl IdGenerator {
+    fn new(seed: u64) -> Self {
+        Self {
+            next: Arc::new(AtomicU64::new(seed)),
+        }
+    }
+
+    fn next_id(&self, prefix: &str) -> String {
+        format!("{}-{}", prefix, self.next.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PuppetMock {
+    ids: IdGenerator,
+    state: Arc<Mutex<MockState>>,
+    latency: Duration,
+    unsupported: BTreeSet<&'static str>,
+    /// Capabilities reported instead of the features the mock implements, see `PuppetMock::with_capabilities`.
+    capabilities: Option<PuppetCapabilities>,
+}
+
+impl PuppetMock {
+    /// Create a mock puppet with no contacts, rooms or messages, the same as `PuppetMock::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a mock puppet whose minted ids are numbered from `seed + 1`, counting messages and rooms together.
+    ///
+    /// The seed only offsets the ids: `PuppetMock::with_seed(42)` sends `msg-43` and then creates `room-44`, while
+    /// `PuppetMock::default()`, the same as `PuppetMock::with_seed(0)`, sends `msg-1`, `msg-2` and so on. Give mocks
+    /// sharing a test different seeds to keep their ids apart.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            ids: IdGenerator::new(seed),
+            ..Default::default()
+        }
+    }
+
+    /// Make loading each contact payload and setting a room topic take this long, like a real gateway, e.g. to test
+    /// cancellation.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Make a method return `PuppetError::Unsupported`, like a gateway lacking it, e.g. to test fallbacks.
+    ///
+    /// Only `contact_exists`, `room_exists`, `room_topic_set` and `message_send_text` can be made unsupported.
+    pub fn with_unsupported(mut self, method: &'static str) -> Self {
+        self.unsupported.insert(method);
+        self
+    }
+
+    /// Report other capabilities than the features the mock implements, e.g. to test how bots degrade.
+    pub fn with_capabilities(mut self, capabilities: PuppetCapabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    fn check_supported(&self, method: &'static str) -> Result<(), PuppetError> {
+        if self.unsupported.contains(method) {
+            Err(PuppetError::Unsupported(method.to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add a contact with its payload, instead of the default one named after its id.
+    pub fn add_contact(&self, payload: ContactPayload) {
+        self.state().contacts.insert(payload.id.clone(), payload);
+    }
+
+    /// Add a message with its payload, e.g. before emitting a message event for it.
+    pub fn add_message(&self, payload: MessagePayload) {
+        self.state().messages.insert(payload.id.clone(), payload);
+    }
+
+    /// Add a chat history message with the messages forwarded in it.
+    pub fn add_chat_history(&self, payload: MessagePayload, message_list: Vec<MessagePayload>) {
+        self.state().chat_histories.insert(payload.id.clone(), message_list);
+        self.add_message(payload);
+    }
+
+    /// Add a url message with the url card shared in it.
+    pub fn add_url_link(&self, payload: MessagePayload, url_link: UrlLinkPayload) {
+        self.state().url_links.insert(payload.id.clone(), url_link);
+        self.add_message(payload);
+    }
+
+    /// Change the members of a mock room behind the scenes, without emitting any event.
+    pub fn set_room_members(&self, room_id: String, member_id_list: Vec<String>) {
+        self.state().room_members.insert(room_id, member_id_list);
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Number of times a method has been called on this mock, e.g. to tell a cache hit from a refetch.
+    ///
+    /// Only calls to `contact_avatar`, `contact_list`, `contact_raw_payload`, `contact_self_qr_code`,
+    /// `room_member_payload_list`, `room_member_raw_payload` and `room_raw_payload` are counted.
+    pub fn call_count(&self, method: &str) -> usize {
+        self.state().call_counts.get(method).copied().unwrap_or_default()
+    }
+
+    fn count_call(&self, method: &'static str) {
+        *self.state().call_counts.entry(method).or_default() += 1;
+    }
+
+    fn room_members(&self, room_id: &str) -> Vec<String> {
+        self.state().room_members.get(room_id).cloned().unwrap_or_default()
+    }
+
+    /// Texts sent to a mock conversation so far, in the order they were sent.
+    pub fn sent_texts(&self, conversation_id: &str) -> Vec<String> {
+        self.state()
+            .sent_texts
+            .get(conversation_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Payload of a mock room member, named after its id.
+fn mock_room_member(member_id: String) -> RoomMemberPayload {
+    RoomMemberPayload {
+        id: member_id.clone(),
+        room_alias: String::new(),
+        inviter_id: String::new(),
+        avatar: String::new(),
+        name: member_id,
+    }
+}
 
 #[allow(dead_code)]
 #[allow(unused_variables)]
@@ -13,7 +190,8 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn contact_self_qr_code(&self) -> Result<String, PuppetError> {
-        unimplemented!()
+        self.count_call("contact_self_qr_code");
+        Ok("qr-self".to_owned())
     }
 
     async fn contact_self_signature_set(&self, signature: String) -> Result<(), PuppetError> {
@@ -21,11 +199,17 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn tag_contact_add(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        if contact_id.is_empty() {
+            return Err(PuppetError::InvalidArgument("Contact id is empty".to_owned()));
+        }
+        Ok(())
     }
 
     async fn tag_contact_remove(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        if contact_id.is_empty() {
+            return Err(PuppetError::InvalidArgument("Contact id is empty".to_owned()));
+        }
+        Ok(())
     }
 
     async fn tag_contact_delete(&self, tag_id: String) -> Result<(), PuppetError> {
@@ -41,15 +225,28 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn contact_alias(&self, contact_id: String) -> Result<String, PuppetError> {
-        unimplemented!()
+        Ok(self
+            .state()
+            .contact_aliases
+            .get(&contact_id)
+            .cloned()
+            .unwrap_or_default())
     }
 
+    /// An empty alias clears the alias.
     async fn contact_alias_set(&self, contact_id: String, alias: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        if alias.is_empty() {
+            self.state().contact_aliases.remove(&contact_id);
+        } else {
+            self.state().contact_aliases.insert(contact_id, alias);
+        }
+        Ok(())
     }
 
+    /// Mock avatars are empty images named after the contact.
     async fn contact_avatar(&self, contact_id: String) -> Result<FileBox, PuppetError> {
-        unimplemented!()
+        self.count_call("contact_avatar");
+        Ok(FileBox::from_base64(String::new(), format!("{}.jpg", contact_id)))
     }
 
     async fn contact_avatar_set(&self, contact_id: String, file: FileBox) -> Result<(), PuppetError> {
@@ -65,7 +262,8 @@ impl PuppetImpl for PuppetMock {
         contact_id: String,
         corporation_remark: Option<String>,
     ) -> Result<(), PuppetError> {
-        unimplemented!()
+        self.state().contact_remarks.entry(contact_id).or_default().1 = corporation_remark;
+        Ok(())
     }
 
     async fn contact_description_set(
@@ -73,23 +271,99 @@ impl PuppetImpl for PuppetMock {
         contact_id: String,
         description: Option<String>,
     ) -> Result<(), PuppetError> {
-        unimplemented!()
+        self.state().contact_remarks.entry(contact_id).or_default().0 = description;
+        Ok(())
     }
 
+    /// Mock contacts are the added contacts, the members of the mock rooms and the contacts with remarks.
     async fn contact_list(&self) -> Result<Vec<String>, PuppetError> {
-        unimplemented!()
-    }
-
+        self.count_call("contact_list");
+        let mut contact_id_list: Vec<String> = self.state().contact_remarks.keys().cloned().collect();
+        contact_id_list.extend(self.state().contacts.keys().cloned());
+        contact_id_list.extend(self.state().room_members.values().flatten().cloned());
+        contact_id_list.sort();
+        contact_id_list.dedup();
+        Ok(contact_id_list)
+    }
+
+    /// Mock contacts exist if they have been added, have a remark or are in a room, although payloads are made up
+    /// for any id.
+    async fn contact_exists(&self, contact_id: String) -> Result<bool, PuppetError> {
+        self.check_supported("contact_exists")?;
+        Ok(self.contact_list().await?.contains(&contact_id))
+    }
+
+    /// Mock contacts are named after their ids, with the alias, description and corporation remark last set.
+    ///
+    /// Added contacts keep their payload, except for the alias if one has been set. Contacts whose id starts with
+    /// `missing-` are not found.
     async fn contact_raw_payload(&self, contact_id: String) -> Result<ContactPayload, PuppetError> {
-        unimplemented!()
+        self.count_call("contact_raw_payload");
+        if !self.latency.is_zero() {
+            actix::clock::sleep(self.latency).await;
+        }
+        if contact_id.starts_with("missing-") {
+            return Err(PuppetError::NotFound(format!("contact {}", contact_id)));
+        }
+        let state = self.state();
+        let alias = state.contact_aliases.get(&contact_id).cloned();
+        if let Some(mut payload) = state.contacts.get(&contact_id).cloned() {
+            if let Some(alias) = alias {
+                payload.alias = alias;
+            }
+            return Ok(payload);
+        }
+        let (description, corporation) = state.contact_remarks.get(&contact_id).cloned().unwrap_or_default();
+        let alias = alias.unwrap_or_default();
+        Ok(ContactPayload {
+            id: contact_id.clone(),
+            gender: ContactGender::Unknown,
+            contact_type: ContactType::Individual,
+            name: contact_id,
+            avatar: String::new(),
+            address: String::new(),
+            alias,
+            city: String::new(),
+            friend: true,
+            province: String::new(),
+            signature: String::new(),
+            star: false,
+            weixin: String::new(),
+            corporation: corporation.unwrap_or_default(),
+            title: String::new(),
+            description: description.unwrap_or_default(),
+            coworker: false,
+            phone: vec![],
+        })
+    }
+
+    async fn contact_block(&self, contact_id: String) -> Result<(), PuppetError> {
+        self.state().blocked_contacts.insert(contact_id);
+        Ok(())
+    }
+
+    async fn contact_unblock(&self, contact_id: String) -> Result<(), PuppetError> {
+        self.state().blocked_contacts.remove(&contact_id);
+        Ok(())
+    }
+
+    async fn contact_is_blocked(&self, contact_id: String) -> Result<bool, PuppetError> {
+        Ok(self.state().blocked_contacts.contains(&contact_id))
     }
 
     async fn message_contact(&self, message_id: String) -> Result<String, PuppetError> {
         unimplemented!()
     }
 
+    /// Files of mock file messages are empty.
     async fn message_file(&self, message_id: String) -> Result<FileBox, PuppetError> {
-        unimplemented!()
+        match message_id.strip_prefix("file-") {
+            Some(filename) => Ok(FileBox::from_base64(String::new(), filename.to_owned())),
+            None => Err(PuppetError::InvalidArgument(format!(
+                "Message {} has no file",
+                message_id
+            ))),
+        }
     }
 
     async fn message_image(&self, message_id: String, image_type: ImageType) -> Result<FileBox, PuppetError> {
@@ -101,7 +375,17 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn message_url(&self, message_id: String) -> Result<UrlLinkPayload, PuppetError> {
-        unimplemented!()
+        match self.state().url_links.get(&message_id) {
+            Some(url_link) => Ok(url_link.clone()),
+            None => Err(PuppetError::NotFound(format!("url link {}", message_id))),
+        }
+    }
+
+    async fn message_chat_history(&self, message_id: String) -> Result<Vec<MessagePayload>, PuppetError> {
+        match self.state().chat_histories.get(&message_id) {
+            Some(message_list) => Ok(message_list.clone()),
+            None => Err(PuppetError::NotFound(format!("chat history {}", message_id))),
+        }
     }
 
     async fn message_send_contact(
@@ -112,8 +396,9 @@ impl PuppetImpl for PuppetMock {
         unimplemented!()
     }
 
+    /// Mock file messages are identified by the file name, see `message_raw_payload`.
     async fn message_send_file(&self, conversation_id: String, file: FileBox) -> Result<Option<String>, PuppetError> {
-        unimplemented!()
+        Ok(Some(format!("file-{}", file.name())))
     }
 
     async fn message_send_mini_program(
@@ -124,13 +409,16 @@ impl PuppetImpl for PuppetMock {
         unimplemented!()
     }
 
+    /// Mock texts are recorded, see `PuppetMock::sent_texts`.
     async fn message_send_text(
         &self,
         conversation_id: String,
         text: String,
         mention_id_list: Vec<String>,
     ) -> Result<Option<String>, PuppetError> {
-        unimplemented!()
+        self.check_supported("message_send_text")?;
+        self.state().sent_texts.entry(conversation_id).or_default().push(text);
+        Ok(Some(self.ids.next_id("msg")))
     }
 
     async fn message_send_url(
@@ -141,8 +429,88 @@ impl PuppetImpl for PuppetMock {
         unimplemented!()
     }
 
+    /// Mock messages are empty text messages, except for file messages sent by `message_send_file`, transfer
+    /// messages of the amount in their id, such as `transfer-0.10`, and the messages added with
+    /// `PuppetMock::add_message`.
     async fn message_raw_payload(&self, message_id: String) -> Result<MessagePayload, PuppetError> {
-        unimplemented!()
+        if let Some(payload) = self.state().messages.get(&message_id) {
+            return Ok(payload.clone());
+        }
+        let (filename, text, message_type) = match message_id.strip_prefix("file-") {
+            Some(filename) => (filename.to_owned(), String::new(), MessageType::Attachment),
+            None => match message_id.strip_prefix("transfer-") {
+                Some(amount) => (
+                    String::new(),
+                    format!(
+                        "<msg><appmsg><type>2000</type><wcpayinfo><paysubtype>1</paysubtype>\
+                         <feedesc><![CDATA[￥{}]]></feedesc></wcpayinfo></appmsg></msg>",
+                        amount
+                    ),
+                    MessageType::Transfer,
+                ),
+                None => (String::new(), String::new(), MessageType::Text),
+            },
+        };
+        Ok(MessagePayload {
+            id: message_id,
+            filename,
+            text,
+            timestamp: 0,
+            message_type,
+            from_id: String::new(),
+            mention_id_list: vec![],
+            room_id: String::new(),
+            to_id: String::new(),
+        })
+    }
+
+    async fn message_send_quote(
+        &self,
+        conversation_id: String,
+        text: String,
+        quoted_message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        Ok(Some(format!("quote-{}", quoted_message_id)))
+    }
+
+    /// Only mock text messages are forwarded natively, others have to be re-sent.
+    async fn message_forward_native(
+        &self,
+        conversation_id: String,
+        message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        if message_id.starts_with("file-") || message_id.starts_with("transfer-") {
+            Err(PuppetError::Unsupported("message_forward_native".to_owned()))
+        } else {
+            Ok(Some(format!("forward-{}", message_id)))
+        }
+    }
+
+    async fn favorite_list(&self) -> Result<Vec<String>, PuppetError> {
+        Ok(self.state().favorites.keys().cloned().collect())
+    }
+
+    async fn favorite_payload(&self, favorite_id: String) -> Result<FavoritePayload, PuppetError> {
+        match self.state().favorites.get(&favorite_id) {
+            Some(payload) => Ok(payload.clone()),
+            None => Err(PuppetError::NotFound(format!("favorite {}", favorite_id))),
+        }
+    }
+
+    async fn favorite_add(&self, message_id: String) -> Result<String, PuppetError> {
+        let message = self.message_raw_payload(message_id.clone()).await?;
+        let favorite_id = format!("favorite-{}", message_id);
+        self.state().favorites.insert(
+            favorite_id.clone(),
+            FavoritePayload {
+                id: favorite_id.clone(),
+                message_id,
+                message_type: message.message_type,
+                text: message.text,
+                timestamp: message.timestamp,
+            },
+        );
+        Ok(favorite_id)
     }
 
     async fn friendship_accept(&self, friendship_id: String) -> Result<(), PuppetError> {
@@ -153,20 +521,33 @@ impl PuppetImpl for PuppetMock {
         unimplemented!()
     }
 
+    /// Mock contacts have no phone numbers.
     async fn friendship_search_phone(&self, phone: String) -> Result<Option<String>, PuppetError> {
-        unimplemented!()
+        Ok(None)
     }
 
+    /// Mock contacts can be found by their ids.
     async fn friendship_search_weixin(&self, weixin: String) -> Result<Option<String>, PuppetError> {
-        unimplemented!()
+        Ok(Some(weixin))
     }
 
     async fn friendship_raw_payload(&self, friendship_id: String) -> Result<FriendshipPayload, PuppetError> {
         unimplemented!()
     }
 
+    /// Mock room invitations can always be accepted.
     async fn room_invitation_accept(&self, room_invitation_id: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        Ok(())
+    }
+
+    /// Mock room invitations can always be rejected.
+    async fn room_invitation_reject(&self, room_invitation_id: String) -> Result<(), PuppetError> {
+        Ok(())
+    }
+
+    /// Joining by url gives the room named after the last segment of the url.
+    async fn room_join_by_url(&self, url: String) -> Result<String, PuppetError> {
+        Ok(format!("room-{}", url.rsplit('/').next().unwrap_or_default()))
     }
 
     async fn room_invitation_raw_payload(
@@ -184,8 +565,11 @@ impl PuppetImpl for PuppetMock {
         unimplemented!()
     }
 
+    /// Created mock rooms get minted ids and have the given contacts as members.
     async fn room_create(&self, contact_id_list: Vec<String>, topic: Option<String>) -> Result<String, PuppetError> {
-        unimplemented!()
+        let room_id = self.ids.next_id("room");
+        self.set_room_members(room_id.clone(), contact_id_list);
+        Ok(room_id)
     }
 
     async fn room_del(&self, room_id: String, contact_id: String) -> Result<(), PuppetError> {
@@ -193,7 +577,7 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn room_qr_code(&self, room_id: String) -> Result<String, PuppetError> {
-        unimplemented!()
+        Ok(format!("qr-{}", room_id))
     }
 
     async fn room_quit(&self, room_id: String) -> Result<(), PuppetError> {
@@ -201,31 +585,71 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn room_topic(&self, room_id: String) -> Result<String, PuppetError> {
-        unimplemented!()
+        Ok(self.state().room_topics.get(&room_id).cloned().unwrap_or_default())
     }
 
     async fn room_topic_set(&self, room_id: String, topic: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        self.check_supported("room_topic_set")?;
+        if !self.latency.is_zero() {
+            actix::clock::sleep(self.latency).await;
+        }
+        self.state().room_topics.insert(room_id, topic);
+        Ok(())
     }
 
+    /// Mock rooms are the ones with members set with `PuppetMock::set_room_members`.
     async fn room_list(&self) -> Result<Vec<String>, PuppetError> {
-        unimplemented!()
+        Ok(self.state().room_members.keys().cloned().collect())
     }
 
+    async fn room_exists(&self, room_id: String) -> Result<bool, PuppetError> {
+        self.check_supported("room_exists")?;
+        Ok(self.state().room_members.contains_key(&room_id))
+    }
+
+    /// Mock rooms have the topic set with `room_topic_set`, and the members set with `PuppetMock::set_room_members`.
+    ///
+    /// Rooms whose id starts with `missing-` are not found.
     async fn room_raw_payload(&self, room_id: String) -> Result<RoomPayload, PuppetError> {
-        unimplemented!()
+        self.count_call("room_raw_payload");
+        if room_id.starts_with("missing-") {
+            return Err(PuppetError::NotFound(format!("room {}", room_id)));
+        }
+        Ok(RoomPayload {
+            member_id_list: self.room_members(&room_id),
+            topic: self.state().room_topics.get(&room_id).cloned().unwrap_or_default(),
+            id: room_id,
+            avatar: String::new(),
+            owner_id: String::new(),
+            admin_id_list: vec![],
+        })
     }
 
     async fn room_announce(&self, room_id: String) -> Result<String, PuppetError> {
-        unimplemented!()
+        Ok(self.state().room_announces.get(&room_id).cloned().unwrap_or_default())
     }
 
     async fn room_announce_set(&self, room_id: String, text: String) -> Result<(), PuppetError> {
-        unimplemented!()
+        self.state().room_announces.insert(room_id, text);
+        Ok(())
+    }
+
+    async fn room_mute(&self, room_id: String) -> Result<(), PuppetError> {
+        self.state().muted_rooms.insert(room_id);
+        Ok(())
+    }
+
+    async fn room_unmute(&self, room_id: String) -> Result<(), PuppetError> {
+        self.state().muted_rooms.remove(&room_id);
+        Ok(())
+    }
+
+    async fn room_is_muted(&self, room_id: String) -> Result<bool, PuppetError> {
+        Ok(self.state().muted_rooms.contains(&room_id))
     }
 
     async fn room_member_list(&self, room_id: String) -> Result<Vec<String>, PuppetError> {
-        unimplemented!()
+        Ok(self.room_members(&room_id))
     }
 
     async fn room_member_raw_payload(
@@ -233,15 +657,26 @@ impl PuppetImpl for PuppetMock {
         room_id: String,
         contact_id: String,
     ) -> Result<RoomMemberPayload, PuppetError> {
-        unimplemented!()
+        self.count_call("room_member_raw_payload");
+        Ok(mock_room_member(contact_id))
+    }
+
+    /// Mock room members are named after their ids.
+    async fn room_member_payload_list(
+        &self,
+        room_id: String,
+        member_id_list: Vec<String>,
+    ) -> Result<Vec<RoomMemberPayload>, PuppetError> {
+        self.count_call("room_member_payload_list");
+        Ok(member_id_list.into_iter().map(mock_room_member).collect())
     }
 
     async fn start(&self) -> Result<(), PuppetError> {
-        unimplemented!()
+        Ok(())
     }
 
     async fn stop(&self) -> Result<(), PuppetError> {
-        unimplemented!()
+        Ok(())
     }
 
     async fn ding(&self, data: String) -> Result<(), PuppetError> {
@@ -249,10 +684,334 @@ impl PuppetImpl for PuppetMock {
     }
 
     async fn version(&self) -> Result<String, PuppetError> {
-        unimplemented!()
+        Ok(env!("CARGO_PKG_VERSION").to_owned())
     }
 
     async fn logout(&self) -> Result<(), PuppetError> {
         unimplemented!()
     }
+
+    /// Tags can be added and removed, files sent, room invitations answered and room announcements set, the
+    /// other features are left unimplemented by the mock.
+    fn capabilities(&self) -> PuppetCapabilities {
+        self.capabilities.clone().unwrap_or(PuppetCapabilities {
+            supports_contact_self: false,
+            supports_tag: true,
+            supports_friendship: false,
+            supports_room_invitation: true,
+            supports_room_announce: true,
+            supports_file: true,
+            supports_mini_program: false,
+            supports_url_link: false,
+            supports_moment: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn seeded_mocks_mint_deterministic_ids() {
+        for mock in [PuppetMock::default(), PuppetMock::with_seed(0)] {
+            let puppet = Puppet::new(mock);
+            let mut ids = vec![];
+            for text in ["hello", "world"] {
+                ids.push(
+                    puppet
+                        .message_send_text("seeded-alice".to_owned(), text.to_owned(), vec![])
+                        .await
+                        .unwrap(),
+                );
+            }
+            assert_eq!(ids, vec![Some("msg-1".to_owned()), Some("msg-2".to_owned())]);
+        }
+
+        // The seed offsets the ids, which messages and rooms count together.
+        let puppet = Puppet::new(PuppetMock::with_seed(42));
+        assert_eq!(
+            puppet
+                .message_send_text("seeded-bob".to_owned(), "hello".to_owned(), vec![])
+                .await
+                .unwrap(),
+            Some("msg-43".to_owned())
+        );
+        let room_id = puppet
+            .room_create(vec!["seeded-bob".to_owned(), "seeded-carol".to_owned()], None)
+            .await
+            .unwrap();
+        assert_eq!(room_id, "room-44");
+        assert_eq!(
+            puppet.room_member_list(room_id).await.unwrap(),
+            vec!["seeded-bob".to_owned(), "seeded-carol".to_owned()]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn can_search_concurrently_on_a_shared_puppet() {
+        fn assert_send<F: Send>(future: F) -> F {
+            future
+        }
+
+        let mock = PuppetMock::default();
+        mock.set_room_members(
+            "concurrent-room".to_owned(),
+            vec!["concurrent-alice".to_owned(), "concurrent-bob".to_owned()],
+        );
+        let puppet = Puppet::new(mock);
+        let shared = &puppet;
+        let (contacts, rooms) = futures::join!(
+            assert_send(shared.contact_search(
+                ContactQueryFilter {
+                    id: Some("concurrent-alice".to_owned()),
+                    ..Default::default()
+                },
+                None
+            )),
+            assert_send(shared.room_search(RoomQueryFilter {
+                id: Some("concurrent-room".to_owned()),
+                ..Default::default()
+            })),
+        );
+        assert_eq!(contacts.unwrap(), vec!["concurrent-alice".to_owned()]);
+        assert_eq!(rooms.unwrap(), vec!["concurrent-room".to_owned()]);
+    }
+
+    #[actix_rt::test]
+    async fn text_messages_are_forwarded_natively() {
+        let puppet = Puppet::new(PuppetMock::default());
+        assert_eq!(
+            puppet
+                .message_forward_with_path("alice".to_owned(), "hello".to_owned())
+                .await
+                .unwrap(),
+            (Some("forward-hello".to_owned()), ForwardPath::Native)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn file_messages_are_forwarded_by_resending() {
+        let puppet = Puppet::new(PuppetMock::default());
+        assert_eq!(
+            puppet
+                .message_forward_with_path("alice".to_owned(), "file-report.pdf".to_owned())
+                .await
+                .unwrap(),
+            (Some("file-report.pdf".to_owned()), ForwardPath::Reconstructed)
+        );
+        assert_eq!(
+            puppet
+                .message_forward("alice".to_owned(), "file-report.pdf".to_owned())
+                .await
+                .unwrap(),
+            Some("file-report.pdf".to_owned())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn emoticons_are_not_forwarded_by_resending() {
+        let mock = PuppetMock::default();
+        let puppet = Puppet::new(mock.clone());
+        // The file of the emoticon can be fetched, but re-sending it would not arrive as a sticker.
+        let mut payload = puppet.message_payload("file-sticker.gif".to_owned()).await.unwrap();
+        payload.message_type = MessageType::Emoticon;
+        mock.add_message(payload);
+        puppet
+            .dirty_payload(PayloadType::Message, "file-sticker.gif".to_owned())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            puppet
+                .message_forward_with_path("alice".to_owned(), "file-sticker.gif".to_owned())
+                .await,
+            Err(PuppetError::Unsupported(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn mock_reports_what_it_implements() {
+        let mock = PuppetMock::default();
+        let capabilities = mock.capabilities();
+        assert!(capabilities.supports(PuppetFeature::Tag));
+        mock.tag_contact_add("tag".to_owned(), "alice".to_owned())
+            .await
+            .unwrap();
+        mock.tag_contact_remove("tag".to_owned(), "alice".to_owned())
+            .await
+            .unwrap();
+        assert!(capabilities.supports(PuppetFeature::File));
+        let file = FileBox::from_base64(String::new(), "a.pdf".to_owned());
+        assert!(mock.message_send_file("alice".to_owned(), file).await.is_ok());
+        assert!(capabilities.supports(PuppetFeature::RoomAnnounce));
+        mock.room_announce_set("room".to_owned(), "hello".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(mock.room_announce("room".to_owned()).await.unwrap(), "hello");
+        assert!(capabilities.supports(PuppetFeature::RoomInvitation));
+        mock.room_invitation_accept("invitation".to_owned()).await.unwrap();
+
+        assert!(!capabilities.supports(PuppetFeature::ContactSelf));
+        assert!(!capabilities.supports(PuppetFeature::Friendship));
+        assert!(!capabilities.supports(PuppetFeature::MiniProgram));
+        assert!(!capabilities.supports(PuppetFeature::UrlLink));
+        assert!(!capabilities.supports(PuppetFeature::Moment));
+    }
+
+    #[actix_rt::test]
+    async fn dry_run_does_not_call_puppet() {
+        let mock = PuppetMock::default();
+        let puppet = Puppet::new(mock.clone()).with_dry_run(true);
+        let message_id = puppet
+            .message_send_text("room".to_owned(), "hello".to_owned(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(message_id, Some("dry-run-message-1".to_owned()));
+        puppet
+            .room_topic_set("room".to_owned(), "topic".to_owned())
+            .await
+            .unwrap();
+        puppet
+            .contact_alias_set("contact".to_owned(), "alias".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            puppet.favorite_add("message".to_owned()).await.unwrap(),
+            "dry-run-favorite-2"
+        );
+        assert_eq!(
+            puppet
+                .room_join_by_url("https://example.com/invitation".to_owned())
+                .await
+                .unwrap(),
+            "dry-run-room-3"
+        );
+
+        assert!(mock.sent_texts("room").is_empty());
+        assert_eq!(mock.contact_alias("contact".to_owned()).await.unwrap(), "");
+        assert!(mock.favorite_list().await.unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn unknown_favorites_are_not_found() {
+        let mock = PuppetMock::default();
+        let favorite_id = mock.favorite_add("message".to_owned()).await.unwrap();
+        assert_eq!(mock.favorite_payload(favorite_id).await.unwrap().id, "favorite-message");
+        assert!(matches!(
+            mock.favorite_payload("favorite-unknown".to_owned()).await,
+            Err(PuppetError::NotFound(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn friendship_search_tells_empty_query_from_not_found() {
+        let puppet = Puppet::new(PuppetMock::default());
+        assert!(matches!(
+            puppet.friendship_search(FriendshipSearchQueryFilter::default()).await,
+            Err(PuppetError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            puppet
+                .friendship_search(FriendshipSearchQueryFilter {
+                    phone: Some(String::new()),
+                    weixin: None,
+                })
+                .await,
+            Err(PuppetError::InvalidConfig(_))
+        ));
+
+        let found = puppet
+            .friendship_search(FriendshipSearchQueryFilter {
+                phone: None,
+                weixin: Some("alice".to_owned()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(found, Some("alice".to_owned()));
+
+        let not_found = puppet
+            .friendship_search(FriendshipSearchQueryFilter {
+                phone: Some("10086".to_owned()),
+                weixin: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(not_found, None);
+    }
+
+    #[actix_rt::test]
+    async fn room_members_are_loaded_in_bulk() {
+        let mock = PuppetMock::default();
+        let puppet = Puppet::new(mock.clone());
+        let member_id_list = vec!["alice".to_owned(), "bob".to_owned()];
+        let member_list = puppet
+            .room_member_payload_list("room".to_owned(), member_id_list.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            member_list.iter().map(|member| member.name.clone()).collect::<Vec<_>>(),
+            member_id_list
+        );
+        assert_eq!(mock.call_count("room_member_payload_list"), 1);
+        assert_eq!(mock.call_count("room_member_raw_payload"), 0);
+        assert!(puppet.is_payload_cached(PayloadType::RoomMember, "room".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn partially_cached_room_members_keep_their_order() {
+        let mock = PuppetMock::default();
+        let puppet = Puppet::new(mock.clone());
+        puppet
+            .room_member_payload_list("room".to_owned(), vec!["bob".to_owned()])
+            .await
+            .unwrap();
+
+        let member_id_list = vec!["alice".to_owned(), "bob".to_owned(), "carol".to_owned()];
+        let member_list = puppet
+            .room_member_payload_list("room".to_owned(), member_id_list.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            member_list.iter().map(|member| member.id.clone()).collect::<Vec<_>>(),
+            member_id_list
+        );
+        assert_eq!(mock.call_count("room_member_payload_list"), 2);
+    }
+
+    #[actix_rt::test]
+    async fn can_snapshot_cached_payloads() {
+        let puppet = Puppet::new(PuppetMock::default());
+        assert!(puppet.cached_messages().is_empty());
+
+        for id in ["first", "second"] {
+            puppet.message_payload(id.to_owned()).await.unwrap();
+        }
+        puppet.contact_payload("alice".to_owned()).await.unwrap();
+        puppet.room_payload("room".to_owned()).await.unwrap();
+
+        let message_id_list = puppet
+            .cached_messages()
+            .into_iter()
+            .map(|payload| payload.id)
+            .collect::<Vec<_>>();
+        assert_eq!(message_id_list, vec!["second".to_owned(), "first".to_owned()]);
+        assert_eq!(puppet.cached_contacts()[0].name, "alice");
+        assert_eq!(puppet.cached_rooms()[0].id, "room");
+    }
+
+    #[actix_rt::test]
+    async fn dirtying_a_room_evicts_only_the_room_payload() {
+        let puppet = Puppet::new(PuppetMock::default());
+        // Dirtying a room used to evict the contact with the same id instead.
+        puppet.contact_payload("shared".to_owned()).await.unwrap();
+        puppet.room_payload("shared".to_owned()).await.unwrap();
+
+        puppet
+            .dirty_payload(PayloadType::Room, "shared".to_owned())
+            .await
+            .unwrap();
+        assert!(!puppet.is_payload_cached(PayloadType::Room, "shared".to_owned()));
+        assert!(puppet.is_payload_cached(PayloadType::Contact, "shared".to_owned()));
+    }
 }
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tonic::transport::Channel;
+use wechaty_grpc::puppet_client::PuppetClient;
+
+/// The connection to the gateway.
+///
+/// A single channel is owned and shared by all the clients handed out, so that every RPC reuses the same
+/// underlying connection instead of dialing again.
+#[derive(Clone, Debug)]
+pub(crate) struct Connection {
+    channel: Channel,
+    connected: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Connection {
+    pub(crate) fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            connected: Arc::new(AtomicBool::new(false)),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub(crate) fn client(&self) -> PuppetClient<Channel> {
+        PuppetClient::new(self.channel.clone())
+    }
+
+    /// Check whether the event stream is currently established.
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::SeqCst);
+    }
+
+    /// Check whether the puppet has been stopped, in which case the event stream is not reestablished.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_stopped(&self, stopped: bool) {
+        self.stopped.store(stopped, Ordering::SeqCst);
+    }
+}
+
@@ -0,0 +1,453 @@
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, Value};
+use wechaty_grpc::puppet::EventResponse;
+use wechaty_puppet::*;
+
+/// The reasons why an event response from the gateway cannot be turned into a puppet event.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DecodeError {
+    /// The event type is unspecified, such responses carry no event and are ignored.
+    Unspecified,
+    UnknownType(i32),
+    InvalidJson(String),
+    InvalidPayload(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Unspecified => write!(fmt, "Unspecified event type"),
+            DecodeError::UnknownType(event_type) => write!(fmt, "Invalid event type: {}", event_type),
+            DecodeError::InvalidJson(reason) => write!(fmt, "Invalid event payload: {}", reason),
+            DecodeError::InvalidPayload(reason) => write!(fmt, "{}", reason),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EventPayload {
+    pub data: Option<Value>,
+    pub contact_id: Option<String>,
+    pub message_id: Option<String>,
+    pub room_invitation_id: Option<String>,
+    pub friendship_id: Option<String>,
+    pub qrcode: Option<String>,
+    pub status: Option<ScanStatus>,
+    pub timestamp: Option<u64>,
+    pub changer_id: Option<String>,
+    pub new_topic: Option<String>,
+    pub old_topic: Option<String>,
+    pub room_id: Option<String>,
+    pub removee_id_list: Option<Vec<String>>,
+    pub remover_id: Option<String>,
+    pub invitee_id_list: Option<Vec<String>>,
+    pub inviter_id: Option<String>,
+    pub payload_type: Option<PayloadType>,
+    pub payload_id: Option<String>,
+}
+
+/// Get the string data of an event, failing with `missing` when there is no data and with `not_string` when the
+/// data is not a string.
+fn string_data(data: Option<Value>, missing: &'static str, not_string: &'static str) -> Result<String, DecodeError> {
+    match data {
+        Some(Value::String(data)) => Ok(data),
+        Some(_) => Err(DecodeError::InvalidPayload(not_string)),
+        None => Err(DecodeError::InvalidPayload(missing)),
+    }
+}
+
+/// Translate an event response from the gateway to a puppet event, without emitting it.
+pub(crate) fn decode_event(response: &EventResponse) -> Result<PuppetEvent, DecodeError> {
+    if response.r#type == 0 {
+        return Err(DecodeError::Unspecified);
+    }
+    let payload: EventPayload = match from_str(&response.payload) {
+        Ok(payload) => payload,
+        Err(e) => return Err(DecodeError::InvalidJson(e.to_string())),
+    };
+
+    match response.r#type {
+        1 => {
+            // Heartbeat
+            let data = match payload.data {
+                Some(Value::String(data)) => data,
+                Some(object @ Value::Object(_)) => object.to_string(),
+                Some(_) => {
+                    return Err(DecodeError::InvalidPayload(
+                        "Heartbeat payload should have string or object data",
+                    ))
+                }
+                None => return Err(DecodeError::InvalidPayload("Heartbeat payload should have data")),
+            };
+            Ok(PuppetEvent::Heartbeat(EventHeartbeatPayload { data }))
+        }
+        2 => {
+            // Message
+            match payload.message_id {
+                Some(message_id) => Ok(PuppetEvent::Message(EventMessagePayload { message_id })),
+                None => Err(DecodeError::InvalidPayload("Message payload should have message id")),
+            }
+        }
+        3 => {
+            // Dong
+            let data = string_data(
+                payload.data,
+                "Dong payload should have data",
+                "Dong payload should have string data",
+            )?;
+            Ok(PuppetEvent::Dong(EventDongPayload { data }))
+        }
+        16 => {
+            // Error
+            let data = string_data(
+                payload.data,
+                "Error payload should have data",
+                "Error payload should have string data",
+            )?;
+            Ok(PuppetEvent::Error(EventErrorPayload { data }))
+        }
+        17 => {
+            // Friendship
+            match payload.friendship_id {
+                Some(friendship_id) => Ok(PuppetEvent::Friendship(EventFriendshipPayload { friendship_id })),
+                None => Err(DecodeError::InvalidPayload(
+                    "Friendship payload should have friendship id",
+                )),
+            }
+        }
+        18 => {
+            // Room invite
+            match payload.room_invitation_id {
+                Some(room_invitation_id) => Ok(PuppetEvent::RoomInvite(EventRoomInvitePayload { room_invitation_id })),
+                None => Err(DecodeError::InvalidPayload(
+                    "Room invite payload should have room invitation id",
+                )),
+            }
+        }
+        19 => {
+            // Room join
+            match (
+                payload.room_id,
+                payload.inviter_id,
+                payload.invitee_id_list,
+                payload.timestamp,
+            ) {
+                (Some(room_id), Some(inviter_id), Some(invitee_id_list), Some(timestamp)) => {
+                    Ok(PuppetEvent::RoomJoin(EventRoomJoinPayload {
+                        room_id,
+                        inviter_id,
+                        invitee_id_list,
+                        timestamp,
+                    }))
+                }
+                _ => Err(DecodeError::InvalidPayload(
+                    "Room join payload should have room id, inviter id, invitee id list and timestamp",
+                )),
+            }
+        }
+        20 => {
+            // Room leave
+            match (
+                payload.room_id,
+                payload.remover_id,
+                payload.removee_id_list,
+                payload.timestamp,
+            ) {
+                (Some(room_id), Some(remover_id), Some(removee_id_list), Some(timestamp)) => {
+                    Ok(PuppetEvent::RoomLeave(EventRoomLeavePayload {
+                        room_id,
+                        remover_id,
+                        removee_id_list,
+                        timestamp,
+                    }))
+                }
+                _ => Err(DecodeError::InvalidPayload(
+                    "Room leave payload should have room id, remover id, removee id list and timestamp",
+                )),
+            }
+        }
+        21 => {
+            // Room topic
+            match (
+                payload.room_id,
+                payload.changer_id,
+                payload.old_topic,
+                payload.new_topic,
+                payload.timestamp,
+            ) {
+                (Some(room_id), Some(changer_id), Some(old_topic), Some(new_topic), Some(timestamp)) => {
+                    Ok(PuppetEvent::RoomTopic(EventRoomTopicPayload {
+                        room_id,
+                        changer_id,
+                        old_topic,
+                        new_topic,
+                        timestamp,
+                    }))
+                }
+                _ => Err(DecodeError::InvalidPayload(
+                    "Room topic payload should have room id, changer id, old topic, new topic and timestamp",
+                )),
+            }
+        }
+        22 => {
+            // Scan
+            match payload.status {
+                Some(status) => Ok(PuppetEvent::Scan(EventScanPayload {
+                    status,
+                    qrcode: payload.qrcode,
+                    data: payload.data.and_then(|value| value.as_str().map(|s| s.to_string())),
+                })),
+                None => Err(DecodeError::InvalidPayload("Scan payload should have scan status")),
+            }
+        }
+        23 => {
+            // Ready
+            let data = string_data(
+                payload.data,
+                "Ready payload should have data",
+                "Ready payload should have string data",
+            )?;
+            Ok(PuppetEvent::Ready(EventReadyPayload { data }))
+        }
+        24 => {
+            // Reset
+            let data = string_data(
+                payload.data,
+                "Reset payload should have data",
+                "Reset payload should have string data",
+            )?;
+            Ok(PuppetEvent::Reset(EventResetPayload { data }))
+        }
+        25 => {
+            // Log in
+            match payload.contact_id {
+                Some(contact_id) => Ok(PuppetEvent::Login(EventLoginPayload { contact_id })),
+                None => Err(DecodeError::InvalidPayload("Login payload should have contact id")),
+            }
+        }
+        26 => {
+            // Log out
+            let contact_id = match payload.contact_id {
+                Some(contact_id) => contact_id,
+                None => {
+                    return Err(DecodeError::InvalidPayload(
+                        "Logout payload should have contact id and data",
+                    ))
+                }
+            };
+            let data = string_data(
+                payload.data,
+                "Logout payload should have contact id and data",
+                "Logout payload should have string data",
+            )?;
+            Ok(PuppetEvent::Logout(EventLogoutPayload { contact_id, data }))
+        }
+        27 => {
+            // Dirty
+            match (payload.payload_type, payload.payload_id) {
+                (Some(payload_type), Some(payload_id)) => Ok(PuppetEvent::Dirty(EventDirtyPayload {
+                    payload_type,
+                    payload_id,
+                })),
+                _ => Err(DecodeError::InvalidPayload(
+                    "Dirty payload should have payload type and payload id",
+                )),
+            }
+        }
+        event_type => Err(DecodeError::UnknownType(event_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(event_type: i32, payload: &str) -> EventResponse {
+        EventResponse {
+            r#type: event_type,
+            payload: payload.to_owned(),
+        }
+    }
+
+    #[test]
+    fn can_decode_every_event_type() {
+        let cases = [
+            (1, r#"{"data":"beat"}"#, EventName::Heartbeat),
+            (1, r#"{"data":{"uptime":1}}"#, EventName::Heartbeat),
+            (2, r#"{"messageId":"message"}"#, EventName::Message),
+            (3, r#"{"data":"dong"}"#, EventName::Dong),
+            (16, r#"{"data":"error"}"#, EventName::Error),
+            (17, r#"{"friendshipId":"friendship"}"#, EventName::Friendship),
+            (18, r#"{"roomInvitationId":"invitation"}"#, EventName::RoomInvite),
+            (
+                19,
+                r#"{"roomId":"room","inviterId":"alice","inviteeIdList":["bob"],"timestamp":1}"#,
+                EventName::RoomJoin,
+            ),
+            (
+                20,
+                r#"{"roomId":"room","removerId":"alice","removeeIdList":["bob"],"timestamp":1}"#,
+                EventName::RoomLeave,
+            ),
+            (
+                21,
+                r#"{"roomId":"room","changerId":"alice","oldTopic":"old","newTopic":"new","timestamp":1}"#,
+                EventName::RoomTopic,
+            ),
+            (22, r#"{"status":2,"qrcode":"qrcode"}"#, EventName::Scan),
+            (23, r#"{"data":"ready"}"#, EventName::Ready),
+            (24, r#"{"data":"reset"}"#, EventName::Reset),
+            (25, r#"{"contactId":"self"}"#, EventName::Login),
+            (26, r#"{"contactId":"self","data":"bye"}"#, EventName::Logout),
+            (27, r#"{"payloadType":2,"payloadId":"alice"}"#, EventName::Dirty),
+        ];
+        for (event_type, payload, event_name) in cases {
+            match decode_event(&response(event_type, payload)) {
+                Ok(event) => assert_eq!(event.event_name(), event_name, "type {}", event_type),
+                Err(e) => panic!("type {} failed to decode: {}", event_type, e),
+            }
+        }
+    }
+
+    #[test]
+    fn can_decode_event_fields() {
+        match decode_event(&response(1, r#"{"data":{"uptime":1}}"#)) {
+            Ok(PuppetEvent::Heartbeat(payload)) => assert_eq!(payload.data, r#"{"uptime":1}"#),
+            other => panic!("unexpected {:?}", other),
+        }
+        match decode_event(&response(
+            19,
+            r#"{"roomId":"room","inviterId":"alice","inviteeIdList":["bob","carol"],"timestamp":7}"#,
+        )) {
+            Ok(PuppetEvent::RoomJoin(payload)) => {
+                assert_eq!(payload.room_id, "room");
+                assert_eq!(payload.inviter_id, "alice");
+                assert_eq!(payload.invitee_id_list, vec!["bob", "carol"]);
+                assert_eq!(payload.timestamp, 7);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match decode_event(&response(22, r#"{"status":3,"data":"scanned"}"#)) {
+            Ok(PuppetEvent::Scan(payload)) => {
+                assert_eq!(payload.status, ScanStatus::Scanned);
+                assert_eq!(payload.qrcode, None);
+                assert_eq!(payload.data, Some("scanned".to_owned()));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match decode_event(&response(27, r#"{"payloadType":2,"payloadId":"alice"}"#)) {
+            Ok(PuppetEvent::Dirty(payload)) => {
+                assert_eq!(payload.payload_type, PayloadType::Contact);
+                assert_eq!(payload.payload_id, "alice");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cannot_decode_invalid_responses() {
+        let cases = [
+            (0, "{}", DecodeError::Unspecified),
+            (99, "{}", DecodeError::UnknownType(99)),
+            (
+                1,
+                "{}",
+                DecodeError::InvalidPayload("Heartbeat payload should have data"),
+            ),
+            (
+                1,
+                r#"{"data":1}"#,
+                DecodeError::InvalidPayload("Heartbeat payload should have string or object data"),
+            ),
+            (
+                2,
+                "{}",
+                DecodeError::InvalidPayload("Message payload should have message id"),
+            ),
+            (
+                3,
+                r#"{"data":1}"#,
+                DecodeError::InvalidPayload("Dong payload should have string data"),
+            ),
+            (16, "{}", DecodeError::InvalidPayload("Error payload should have data")),
+            (
+                17,
+                "{}",
+                DecodeError::InvalidPayload("Friendship payload should have friendship id"),
+            ),
+            (
+                18,
+                "{}",
+                DecodeError::InvalidPayload("Room invite payload should have room invitation id"),
+            ),
+            (
+                19,
+                r#"{"roomId":"room","inviterId":"alice","timestamp":1}"#,
+                DecodeError::InvalidPayload(
+                    "Room join payload should have room id, inviter id, invitee id list and timestamp",
+                ),
+            ),
+            (
+                20,
+                r#"{"roomId":"room"}"#,
+                DecodeError::InvalidPayload(
+                    "Room leave payload should have room id, remover id, removee id list and timestamp",
+                ),
+            ),
+            (
+                21,
+                r#"{"roomId":"room","newTopic":"new"}"#,
+                DecodeError::InvalidPayload(
+                    "Room topic payload should have room id, changer id, old topic, new topic and timestamp",
+                ),
+            ),
+            (
+                22,
+                "{}",
+                DecodeError::InvalidPayload("Scan payload should have scan status"),
+            ),
+            (23, "{}", DecodeError::InvalidPayload("Ready payload should have data")),
+            (
+                24,
+                r#"{"data":[]}"#,
+                DecodeError::InvalidPayload("Reset payload should have string data"),
+            ),
+            (
+                25,
+                "{}",
+                DecodeError::InvalidPayload("Login payload should have contact id"),
+            ),
+            (
+                26,
+                r#"{"data":"bye"}"#,
+                DecodeError::InvalidPayload("Logout payload should have contact id and data"),
+            ),
+            (
+                27,
+                r#"{"payloadId":"alice"}"#,
+                DecodeError::InvalidPayload("Dirty payload should have payload type and payload id"),
+            ),
+        ];
+        for (event_type, payload, error) in cases {
+            assert_eq!(
+                decode_event(&response(event_type, payload)).unwrap_err(),
+                error,
+                "type {}",
+                event_type
+            );
+        }
+    }
+
+    #[test]
+    fn cannot_decode_malformed_json() {
+        assert!(matches!(
+            decode_event(&response(2, "not json")),
+            Err(DecodeError::InvalidJson(_))
+        ));
+    }
+}
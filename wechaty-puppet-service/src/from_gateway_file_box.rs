@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use wechaty_puppet::{FileBox, PuppetError};
+
+/// Box types of the file boxes serialized by the gateway, the others cannot be sent over the wire.
+const BOX_TYPE_BASE64: i32 = 1;
+const BOX_TYPE_URL: i32 = 2;
+const BOX_TYPE_QR_CODE: i32 = 3;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GatewayFileBox {
+    box_type: i32,
+    #[serde(default)]
+    name: String,
+    base64: Option<String>,
+    remote_url: Option<String>,
+    qr_code: Option<String>,
+}
+
+pub trait TryFromGateway: Sized {
+    fn try_from_gateway(filebox: Option<String>) -> Result<Self, PuppetError>;
+}
+
+impl TryFromGateway for FileBox {
+    /// Parse the file box field of a gateway response, which is a file box serialized as json.
+    fn try_from_gateway(filebox: Option<String>) -> Result<Self, PuppetError> {
+        let json = match filebox {
+            Some(json) if !json.trim().is_empty() => json,
+            _ => return Err(PuppetError::Deserialize("filebox: missing".to_owned())),
+        };
+        let payload: GatewayFileBox = match serde_json::from_str(&json) {
+            Ok(payload) => payload,
+            Err(e) => return Err(PuppetError::Deserialize(format!("filebox: {}", e))),
+        };
+        match payload {
+            GatewayFileBox {
+                box_type: BOX_TYPE_BASE64,
+                base64: Some(base64),
+                name,
+                ..
+            } => Ok(FileBox::from_base64(base64, name)),
+            GatewayFileBox {
+                box_type: BOX_TYPE_URL,
+                remote_url: Some(url),
+                name,
+                ..
+            } => Ok(FileBox::from_url(url, name)),
+            GatewayFileBox {
+                box_type: BOX_TYPE_QR_CODE,
+                qr_code: Some(qr_code),
+                ..
+            } => Ok(FileBox::from_qr_code(qr_code)),
+            GatewayFileBox { box_type, .. } => Err(PuppetError::Deserialize(format!(
+                "filebox: unsupported box type {} or missing content",
+                box_type
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::FileBoxSource;
+
+    use super::*;
+
+    #[test]
+    fn can_parse_valid_file_boxes() {
+        let file = FileBox::try_from_gateway(Some(
+            r#"{"boxType": 1, "name": "hello.txt", "base64": "aGVsbG8="}"#.to_owned(),
+        ))
+        .unwrap();
+        assert_eq!(file.name(), "hello.txt");
+        assert_eq!(file.source(), &FileBoxSource::Base64("aGVsbG8=".to_owned()));
+
+        let file = FileBox::try_from_gateway(Some(
+            r#"{"boxType": 2, "name": "avatar.jpg", "remoteUrl": "https://example.com/avatar.jpg", "headers": {}}"#
+                .to_owned(),
+        ))
+        .unwrap();
+        assert_eq!(file.name(), "avatar.jpg");
+        assert_eq!(
+            file.source(),
+            &FileBoxSource::Url("https://example.com/avatar.jpg".to_owned())
+        );
+
+        let file = FileBox::try_from_gateway(Some(r#"{"boxType": 3, "qrCode": "qr"}"#.to_owned())).unwrap();
+        assert_eq!(file.source(), &FileBoxSource::QrCode("qr".to_owned()));
+    }
+
+    #[test]
+    fn invalid_file_boxes_are_deserialize_errors() {
+        for json in [
+            "not json",
+            r#"{"name": "hello.txt"}"#,
+            r#"{"boxType": 1, "name": "hello.txt"}"#,
+            r#"{"boxType": 5, "name": "local.txt"}"#,
+        ] {
+            assert!(
+                matches!(
+                    FileBox::try_from_gateway(Some(json.to_owned())),
+                    Err(PuppetError::Deserialize(_))
+                ),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn missing_file_boxes_are_deserialize_errors() {
+        assert!(matches!(
+            FileBox::try_from_gateway(None),
+            Err(PuppetError::Deserialize(_))
+        ));
+        assert!(matches!(
+            FileBox::try_from_gateway(Some(String::new())),
+            Err(PuppetError::Deserialize(_))
+        ));
+    }
+}
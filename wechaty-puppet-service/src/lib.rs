@@ -1,3 +1,6 @@
+mod connection;
+mod event_decode;
+mod from_gateway_file_box;
 mod from_payload_response;
 mod puppet_service;
 mod service_endpoint;
@@ -1,33 +1,198 @@
-use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, Recipient, StreamHandler};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, Recipient, SpawnHandle,
+    StreamHandler, WrapFuture,
+};
 use async_trait::async_trait;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use num_traits::cast::ToPrimitive;
-use serde::{Deserialize, Serialize};
-use serde_json::{from_str, to_string};
-use tonic::{transport::Channel, Status, Streaming};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{from_str, to_string, to_value, Value};
+use tokio::sync::oneshot;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status, Streaming};
 use wechaty_grpc::puppet::*;
 use wechaty_grpc::puppet_client::PuppetClient;
+use wechaty_puppet::ImageType;
 use wechaty_puppet::*;
-use wechaty_puppet::{ImageType, PayloadType};
 
+use crate::connection::Connection;
+use crate::event_decode::{decode_event, DecodeError};
+use crate::from_gateway_file_box::TryFromGateway;
 use crate::from_payload_response::FromPayloadResponse;
 use crate::service_endpoint::discover;
 
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// Floor for the version a gateway reports in the `Version` RPC when `PuppetOptions::min_version` is not given.
+///
+/// It is not derived from `wechaty-grpc` or any other dependency: it is picked by hand and only leads to a warning,
+/// so update it here when relying on a newer gateway API.
+const RECOMMENDED_VERSION: &str = "0.22.0";
+
+/// Client identifier used when `PuppetOptions::user_agent` is not given.
+fn default_user_agent() -> String {
+    format!("rust-wechaty/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Build a gRPC endpoint that sends the user agent with every request.
+fn grpc_endpoint(endpoint: String, user_agent: String) -> Result<Endpoint, PuppetError> {
+    let endpoint = match Endpoint::from_shared(endpoint) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return Err(PuppetError::InvalidArgument(format!("Invalid endpoint: {}", e))),
+    };
+    match endpoint.user_agent(user_agent) {
+        Ok(endpoint) => Ok(endpoint),
+        Err(e) => Err(PuppetError::InvalidArgument(format!("Invalid user agent: {}", e))),
+    }
+}
+
+/// Phrases of the gateway statuses telling that the account of the bot is banned, other statuses mentioning a ban or
+/// a block, such as a blocked contact, are not about the bot.
+const ACCOUNT_BANNED_PHRASES: [&str; 3] = ["account is banned", "account has been banned", "account is blocked"];
+
+/// Convert a gRPC status returned by the gateway to a puppet error.
+///
+/// Rate limits and account bans are told apart from other failures so that bots can back off instead of
+/// retrying immediately, and missing ids so that they are not retried at all, everything else is regarded as a
+/// network failure.
+fn map_status_error(status: Status, reason: String) -> PuppetError {
+    let message = status.message().to_lowercase();
+    if status.code() == Code::ResourceExhausted || message.contains("rate limit") || message.contains("too frequent") {
+        PuppetError::RateLimited(format!("{}, status: {}", reason, status.message()))
+    } else if status.code() == Code::NotFound {
+        PuppetError::NotFound(format!("{}, status: {}", reason, status.message()))
+    } else if ACCOUNT_BANNED_PHRASES.iter().any(|phrase| message.contains(phrase)) {
+        PuppetError::AccountBanned(format!("{}, status: {}", reason, status.message()))
+    } else {
+        PuppetError::Network(reason)
+    }
+}
+
+/// Parse a json field of a gateway response, malformed json is reported instead of panicking.
+fn parse_json<Payload: DeserializeOwned>(field: &str, json: &str) -> Result<Payload, PuppetError> {
+    from_str(json).map_err(|e| PuppetError::Deserialize(format!("{}: {}", field, e)))
+}
+
+/// Serialize a payload into a json field of a gateway request, with field names in the casing the gateway expects.
+fn to_json<Payload: Serialize>(field: &str, payload: &Payload, casing: JsonCasing) -> Result<String, PuppetError> {
+    let value =
+        to_value(payload).map_err(|e| PuppetError::InvalidArgument(format!("Failed to serialize {}: {}", field, e)))?;
+    let value = match casing {
+        JsonCasing::SnakeCase => value,
+        JsonCasing::CamelCase => camel_case_keys(value),
+    };
+    to_string(&value).map_err(|e| PuppetError::InvalidArgument(format!("Failed to serialize {}: {}", field, e)))
+}
+
+/// Rename the keys of json objects from snake_case to camelCase, recursively.
+fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let mut camel_case = String::with_capacity(key.len());
+                    let mut upper = false;
+                    for c in key.chars() {
+                        if c == '_' {
+                            upper = true;
+                        } else if upper {
+                            camel_case.push(c.to_ascii_uppercase());
+                            upper = false;
+                        } else {
+                            camel_case.push(c);
+                        }
+                    }
+                    (camel_case, camel_case_keys(value))
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(camel_case_keys).collect()),
+        value => value,
+    }
+}
+
+/// Parse a version like `v1.2.3-beta.1` into `(1, 2, 3)`, missing components are regarded as 0.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next().unwrap_or_default();
+    let mut components = [0; 3];
+    for (i, component) in version.split('.').enumerate() {
+        if i >= 3 {
+            break;
+        }
+        match component.parse() {
+            Ok(component) => components[i] = component,
+            Err(_) => return None,
+        }
+    }
+    Some((components[0], components[1], components[2]))
+}
+
+/// Get the version of the gateway behind a connection.
+async fn gateway_version(connection: &Connection) -> Result<String, PuppetError> {
+    match connection.client().version(VersionRequest {}).await {
+        Ok(response) => Ok(response.into_inner().version),
+        Err(status) => Err(map_status_error(status, "Failed to get puppet version".to_owned())),
+    }
+}
+
+/// Check the version reported by a puppet against `min_version`, or `RECOMMENDED_VERSION` if not given.
+///
+/// An outdated version is an error only if `min_version` is given, otherwise only a warning is logged.
+fn check_version(version: Result<String, PuppetError>, min_version: Option<String>) -> Result<(), PuppetError> {
+    let strict = min_version.is_some();
+    let min_version = min_version.unwrap_or_else(|| RECOMMENDED_VERSION.to_owned());
+    let fail = |reason: String| {
+        if strict {
+            Err(PuppetError::IncompatibleVersion(reason))
+        } else {
+            warn!("{}", reason);
+            Ok(())
+        }
+    };
+    let version = match version {
+        Ok(version) => version,
+        Err(e) => return fail(format!("Failed to get gateway version, reason: {}", e)),
+    };
+    info!("Gateway version: {}", version);
+    match (parse_version(&version), parse_version(&min_version)) {
+        (Some(current), Some(min)) if current >= min => Ok(()),
+        (Some(_), Some(_)) => fail(format!("Gateway version {} is older than {}", version, min_version)),
+        _ => fail(format!(
+            "Cannot compare gateway version {} with {}",
+            version, min_version
+        )),
+    }
+}
+
 #[derive(Clone)]
 pub struct PuppetService {
-    client_: PuppetClient<Channel>,
+    connection_: Connection,
     addr: Addr<PuppetServiceInner>,
+    json_casing_: JsonCasing,
 }
 
 impl PuppetService {
     /// Create puppet instance from puppet options.
     ///
-    /// First use endpoint, if endpoint is not given, try token instead.
+    /// First use endpoint, if endpoint is not given, try token instead, which needs the `discovery` feature.
     pub async fn new(options: PuppetOptions) -> Result<Puppet<Self>, PuppetError> {
-        let endpoint = if let Some(endpoint) = options.endpoint {
+        options.validate()?;
+        let dry_run = options.dry_run;
+        let stream_error_policy = options.stream_error_policy;
+        let min_version = options.min_version;
+        let user_agent = options.user_agent.unwrap_or_else(default_user_agent);
+        let heartbeat_timeout = options.heartbeat_timeout;
+        let reconnect_jitter = options.reconnect_jitter;
+        let json_casing = options.json_casing;
+        let endpoint = if let Some(endpoint) = options.endpoint.filter(|endpoint| !endpoint.trim().is_empty()) {
             endpoint
         } else if let Some(token) = options.token {
-            match discover(token).await {
+            match discover(token, &user_agent).await {
                 Ok(endpoint) => endpoint,
                 Err(e) => return Err(e),
             }
@@ -35,21 +200,32 @@ impl PuppetService {
             return Err(PuppetError::InvalidToken);
         };
 
-        match PuppetClient::connect(endpoint.clone()).await {
-            Ok(mut client) => {
+        match grpc_endpoint(endpoint.clone(), user_agent)?.connect().await {
+            Ok(channel) => {
                 info!("Connected to endpoint {}", endpoint);
-                let response = client.event(EventRequest {}).await;
+                let connection = Connection::new(channel);
+                check_version(gateway_version(&connection).await, min_version)?;
+                let addr = PuppetServiceInner::new(connection.clone(), stream_error_policy)
+                    .with_heartbeat_timeout(heartbeat_timeout)
+                    .with_reconnect_jitter(reconnect_jitter)
+                    .start();
+                let puppet_service = Self {
+                    connection_: connection,
+                    addr: addr.clone(),
+                    json_casing_: json_casing,
+                };
+                let response = puppet_service.client().event(EventRequest {}).await;
                 match response {
                     Ok(response) => {
                         info!("Subscribed to event stream");
-                        let addr = PuppetServiceInner::new().start();
-                        let puppet_service = Self {
-                            client_: client,
-                            addr: addr.clone(),
-                        };
-                        let puppet = Puppet::new(puppet_service);
+                        let puppet = Puppet::new(puppet_service).with_dry_run(dry_run);
                         let callback_addr = puppet.self_addr();
                         addr.do_send(PuppetServiceInternalMessage::SetupCallback(callback_addr));
+                        if let Some(fatal_error_sender) = puppet.fatal_error_sender() {
+                            addr.do_send(PuppetServiceInternalMessage::RegisterFatalErrorSender(
+                                fatal_error_sender,
+                            ));
+                        }
                         addr.do_send(PuppetServiceInternalMessage::SetupStream(response.into_inner()));
                         Ok(puppet)
                     }
@@ -66,8 +242,13 @@ impl PuppetService {
         }
     }
 
+    /// Check whether the event stream from the gateway is currently established.
+    pub fn is_connected(&self) -> bool {
+        self.connection_.is_connected()
+    }
+
     fn client(&self) -> PuppetClient<Channel> {
-        self.client_.clone()
+        self.connection_.client()
     }
 }
 
@@ -76,16 +257,52 @@ impl PuppetService {
 enum PuppetServiceInternalMessage {
     SetupCallback(Recipient<PuppetEvent>),
     SetupStream(Streaming<EventResponse>),
+    RegisterFatalErrorSender(oneshot::Sender<PuppetError>),
+    SetHeartbeatTimeout(Option<Duration>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct PuppetServiceInner {
+    connection: Connection,
     callback_addr: Option<Recipient<PuppetEvent>>,
+    connection_state: ConnectionState,
+    reconnect_delay: Duration,
+    reconnect_jitter: f64,
+    stream_handle: Option<SpawnHandle>,
+    stream_error_policy: Option<StreamErrorPolicy>,
+    stream_errors: VecDeque<Instant>,
+    fatal_error_sender: Option<oneshot::Sender<PuppetError>>,
+    heartbeat_timeout: Option<Duration>,
+    heartbeat_handle: Option<SpawnHandle>,
+    last_event_at: Instant,
 }
 
 impl PuppetServiceInner {
-    fn new() -> Self {
-        Self { callback_addr: None }
+    fn new(connection: Connection, stream_error_policy: Option<StreamErrorPolicy>) -> Self {
+        Self {
+            connection,
+            callback_addr: None,
+            connection_state: ConnectionState::Connecting,
+            reconnect_delay: MIN_RECONNECT_DELAY,
+            reconnect_jitter: 0.0,
+            stream_handle: None,
+            stream_error_policy,
+            stream_errors: VecDeque::new(),
+            fatal_error_sender: None,
+            heartbeat_timeout: None,
+            heartbeat_handle: None,
+            last_event_at: Instant::now(),
+        }
+    }
+
+    fn with_heartbeat_timeout(mut self, heartbeat_timeout: Option<Duration>) -> Self {
+        self.heartbeat_timeout = heartbeat_timeout;
+        self
+    }
+
+    fn with_reconnect_jitter(mut self, reconnect_jitter: Option<f64>) -> Self {
+        self.reconnect_jitter = reconnect_jitter.unwrap_or_default();
+        self
     }
 
     fn emit(&self, msg: PuppetEvent) {
@@ -93,13 +310,156 @@ impl PuppetServiceInner {
             error!("Internal error: {}", e)
         }
     }
+
+    /// Update the connection state, a connection change event is emitted only if the state actually changes.
+    fn set_connection_state(&mut self, state: ConnectionState) {
+        if self.connection_state != state {
+            info!(
+                "Connection state changed from {:?} to {:?}",
+                self.connection_state, state
+            );
+            self.connection_state = state;
+            self.connection.set_connected(state == ConnectionState::Connected);
+            self.emit(PuppetEvent::ConnectionChange(EventConnectionChangePayload { state }));
+        }
+    }
+
+    /// Wait for the current reconnect delay, then try to re-subscribe to the event stream.
+    fn schedule_reconnect(&mut self, ctx: &mut Context<Self>) {
+        if self.connection.is_stopped() {
+            info!("Puppet stopped, not reconnecting");
+            self.set_connection_state(ConnectionState::Disconnected);
+            return;
+        }
+        self.set_connection_state(ConnectionState::Reconnecting);
+        let delay = self.next_reconnect_delay();
+        info!("Reconnecting in {:?}", delay);
+        ctx.run_later(delay, |this, ctx| this.reconnect(ctx));
+    }
+
+    /// Take a random fraction, up to the jitter, off the current reconnect delay, then back off for the next attempt.
+    ///
+    /// The jittered delay is never shorter than half the current delay, so that a large jitter does not turn the
+    /// backoff into a busy loop. The delay doubles after each failed attempt, up to `MAX_RECONNECT_DELAY`.
+    fn next_reconnect_delay(&mut self) -> Duration {
+        let delay = self
+            .reconnect_delay
+            .mul_f64(1.0 - self.reconnect_jitter * rand::random::<f64>())
+            .max(self.reconnect_delay / 2);
+        self.reconnect_delay = (self.reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        delay
+    }
+
+    fn reconnect(&mut self, ctx: &mut Context<Self>) {
+        if self.connection.is_stopped() {
+            info!("Puppet stopped, not reconnecting");
+            self.set_connection_state(ConnectionState::Disconnected);
+            return;
+        }
+        self.set_connection_state(ConnectionState::Connecting);
+        let mut client = self.connection.client();
+        ctx.spawn(
+            async move { client.event(EventRequest {}).await }.into_actor(self).map(
+                |response, this, ctx| match response {
+                    Ok(response) => {
+                        info!("Resubscribed to event stream");
+                        this.reconnect_delay = MIN_RECONNECT_DELAY;
+                        this.stream_handle = Some(ctx.add_stream(response.into_inner()));
+                    }
+                    Err(e) => {
+                        error!("Failed to reestablish event stream, reason: {}", e);
+                        this.schedule_reconnect(ctx);
+                    }
+                },
+            ),
+        );
+    }
+
+    /// Check the heartbeat periodically if a heartbeat timeout is set, replacing the previous check if any.
+    fn watch_heartbeat(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.heartbeat_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        if let Some(heartbeat_timeout) = self.heartbeat_timeout {
+            self.heartbeat_handle = Some(ctx.run_interval(heartbeat_timeout / 4, move |this, ctx| {
+                this.check_heartbeat(ctx, heartbeat_timeout)
+            }));
+        }
+    }
+
+    /// Reconnect if the event stream has been silent for longer than the heartbeat timeout, as the connection
+    /// is likely dead even if the stream has not failed.
+    fn check_heartbeat(&mut self, ctx: &mut Context<Self>, heartbeat_timeout: Duration) {
+        if self.connection_state != ConnectionState::Connected || self.last_event_at.elapsed() <= heartbeat_timeout {
+            return;
+        }
+        warn!("No event received within {:?}, reconnecting", heartbeat_timeout);
+        if let Some(handle) = self.stream_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.set_connection_state(ConnectionState::Disconnected);
+        self.schedule_reconnect(ctx);
+    }
+
+    /// Record an event stream error, and apply the stream error policy if there are too many errors within
+    /// the window.
+    fn on_stream_error(&mut self, ctx: &mut Context<Self>) {
+        let policy = match &self.stream_error_policy {
+            Some(policy) => policy.clone(),
+            None => return,
+        };
+        let now = Instant::now();
+        self.stream_errors.push_back(now);
+        while let Some(first) = self.stream_errors.front() {
+            if now.duration_since(*first) > policy.window {
+                self.stream_errors.pop_front();
+            } else {
+                break;
+            }
+        }
+        if self.stream_errors.len() < policy.max_errors {
+            return;
+        }
+
+        self.stream_errors.clear();
+        if let Some(handle) = self.stream_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.set_connection_state(ConnectionState::Disconnected);
+        match policy.action {
+            StreamErrorAction::Reconnect => {
+                warn!(
+                    "{} stream errors within {:?}, reconnecting",
+                    policy.max_errors, policy.window
+                );
+                self.schedule_reconnect(ctx);
+            }
+            StreamErrorAction::Stop => {
+                let reason = format!(
+                    "Event stream failed {} times within {:?}",
+                    policy.max_errors, policy.window
+                );
+                error!("{}, stopping", reason);
+                match self.fatal_error_sender.take() {
+                    Some(sender) => {
+                        if sender.send(PuppetError::Network(reason)).is_err() {
+                            error!("Fatal error is not received by anyone");
+                        }
+                    }
+                    None => error!("Fatal error sender is not set up"),
+                }
+                ctx.stop();
+            }
+        }
+    }
 }
 
 impl Actor for PuppetServiceInner {
     type Context = Context<Self>;
 
-    fn started(&mut self, _ctx: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         info!("Puppet service started");
+        self.watch_heartbeat(ctx);
     }
 
     fn stopped(&mut self, _ctx: &mut Self::Context) {
@@ -116,247 +476,49 @@ impl Handler<PuppetServiceInternalMessage> for PuppetServiceInner {
                 self.callback_addr = Some(callback_addr);
             }
             PuppetServiceInternalMessage::SetupStream(stream) => {
-                ctx.add_stream(stream);
+                self.stream_handle = Some(ctx.add_stream(stream));
+            }
+            PuppetServiceInternalMessage::RegisterFatalErrorSender(sender) => {
+                self.fatal_error_sender = Some(sender);
+            }
+            PuppetServiceInternalMessage::SetHeartbeatTimeout(heartbeat_timeout) => {
+                self.heartbeat_timeout = heartbeat_timeout;
+                self.last_event_at = Instant::now();
+                self.watch_heartbeat(ctx);
             }
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct EventPayload {
-    pub data: Option<serde_json::Value>,
-    pub contact_id: Option<String>,
-    pub message_id: Option<String>,
-    pub room_invitation_id: Option<String>,
-    pub friendship_id: Option<String>,
-    pub qrcode: Option<String>,
-    pub status: Option<ScanStatus>,
-    pub timestamp: Option<u64>,
-    pub changer_id: Option<String>,
-    pub new_topic: Option<String>,
-    pub old_topic: Option<String>,
-    pub room_id: Option<String>,
-    pub removee_id_list: Option<Vec<String>>,
-    pub remover_id: Option<String>,
-    pub invitee_id_list: Option<Vec<String>>,
-    pub inviter_id: Option<String>,
-    pub payload_type: Option<PayloadType>,
-    pub payload_id: Option<String>,
-}
-
 impl StreamHandler<Result<EventResponse, Status>> for PuppetServiceInner {
-    fn handle(&mut self, item: Result<EventResponse, Status>, _ctx: &mut Self::Context) {
+    fn handle(&mut self, item: Result<EventResponse, Status>, ctx: &mut Self::Context) {
         match item {
             Ok(response) => {
                 info!("Receive event response, {:?}", response);
-                let payload: EventPayload = from_str(&response.payload).unwrap();
-
-                match response.r#type {
-                    0 => {
-                        // Unspecified
-                    }
-                    1 => {
-                        // Heartbeat
-                        if payload.data == None {
-                            error!("Heartbeat payload should have data");
-                        } else {
-                            let data = match payload.data.unwrap() {
-                                serde_json::Value::String(data) => data,
-                                object @ serde_json::Value::Object(_) => object.to_string(),
-                                _ => {
-                                    error!("Heartbeat payload should have string or object data");
-                                    return;
-                                }
-                            };
-                            self.emit(PuppetEvent::Heartbeat(EventHeartbeatPayload { data }))
-                        }
-                    }
-                    2 => {
-                        // Message
-                        if payload.message_id == None {
-                            error!("Message payload should have message id");
-                        } else {
-                            self.emit(PuppetEvent::Message(EventMessagePayload {
-                                message_id: payload.message_id.unwrap(),
-                            }));
-                        }
-                    }
-                    3 => {
-                        // Dong
-                        if payload.data == None {
-                            error!("Dong payload should have data");
-                        } else if let serde_json::Value::String(data) = payload.data.unwrap() {
-                            self.emit(PuppetEvent::Dong(EventDongPayload { data }));
-                        } else {
-                            error!("Dong payload should have string data");
-                        }
-                    }
-                    16 => {
-                        // Error
-                        if payload.data == None {
-                            error!("Error payload should have data");
-                        } else if let serde_json::Value::String(data) = payload.data.unwrap() {
-                            self.emit(PuppetEvent::Error(EventErrorPayload { data }));
-                        } else {
-                            error!("Error payload should have string data");
-                        }
-                    }
-                    17 => {
-                        // Friendship
-                        if payload.friendship_id == None {
-                            error!("Friendship payload should have friendship id");
-                        } else {
-                            self.emit(PuppetEvent::Friendship(EventFriendshipPayload {
-                                friendship_id: payload.friendship_id.unwrap(),
-                            }));
-                        }
-                    }
-                    18 => {
-                        // Room invite
-                        if payload.room_invitation_id == None {
-                            error!("Room invite payload should have room invitation id");
-                        } else {
-                            self.emit(PuppetEvent::RoomInvite(EventRoomInvitePayload {
-                                room_invitation_id: payload.room_invitation_id.unwrap(),
-                            }));
-                        }
-                    }
-                    19 => {
-                        // Room join
-                        if payload.room_id == None
-                            || payload.invitee_id_list == None
-                            || payload.inviter_id == None
-                            || payload.timestamp == None
-                        {
-                            error!("Room join payload should have room id, inviter id, invitee id list and timestamp");
-                        } else {
-                            self.emit(PuppetEvent::RoomJoin(EventRoomJoinPayload {
-                                room_id: payload.room_id.unwrap(),
-                                inviter_id: payload.inviter_id.unwrap(),
-                                invitee_id_list: payload.invitee_id_list.unwrap(),
-                                timestamp: payload.timestamp.unwrap(),
-                            }));
-                        }
-                    }
-                    20 => {
-                        // Room leave
-                        if payload.room_id == None
-                            || payload.removee_id_list == None
-                            || payload.remover_id == None
-                            || payload.timestamp == None
-                        {
-                            error!("Room leave payload should have room id, remover id, removee id list and timestamp");
-                        } else {
-                            self.emit(PuppetEvent::RoomLeave(EventRoomLeavePayload {
-                                room_id: payload.room_id.unwrap(),
-                                remover_id: payload.remover_id.unwrap(),
-                                removee_id_list: payload.removee_id_list.unwrap(),
-                                timestamp: payload.timestamp.unwrap(),
-                            }));
-                        }
-                    }
-                    21 => {
-                        // Room topic
-                        if payload.room_id == None
-                            || payload.changer_id == None
-                            || payload.old_topic == None
-                            || payload.new_topic == None
-                            || payload.timestamp == None
-                        {
-                            error!("Room topic payload should have room id, changer id, old topic, new topic and timestamp");
-                        } else {
-                            self.emit(PuppetEvent::RoomTopic(EventRoomTopicPayload {
-                                room_id: payload.room_id.unwrap(),
-                                changer_id: payload.changer_id.unwrap(),
-                                old_topic: payload.old_topic.unwrap(),
-                                new_topic: payload.new_topic.unwrap(),
-                                timestamp: payload.timestamp.unwrap(),
-                            }));
-                        }
-                    }
-                    22 => {
-                        // Scan
-                        if payload.status == None {
-                            error!("Scan payload should have scan status");
-                        } else {
-                            self.emit(PuppetEvent::Scan(EventScanPayload {
-                                status: payload.status.unwrap(),
-                                qrcode: payload.qrcode,
-                                data: payload
-                                    .data
-                                    .map(|value| value.as_str().map(|s| s.to_string()))
-                                    .flatten(),
-                            }));
-                        }
-                    }
-                    23 => {
-                        // Ready
-                        if payload.data == None {
-                            error!("Ready payload should have data");
-                        } else if let serde_json::Value::String(data) = payload.data.unwrap() {
-                            self.emit(PuppetEvent::Ready(EventReadyPayload { data }));
-                        } else {
-                            error!("Ready payload should have string data");
-                        }
-                    }
-                    24 => {
-                        // Reset
-                        if payload.data == None {
-                            error!("Reset payload should have data");
-                        } else if let serde_json::Value::String(data) = payload.data.unwrap() {
-                            self.emit(PuppetEvent::Reset(EventResetPayload { data }));
-                        } else {
-                            error!("Reset payload should have string data");
-                        }
-                    }
-                    25 => {
-                        // Log in
-                        if payload.contact_id == None {
-                            error!("Login payload should have contact id");
-                        } else {
-                            self.emit(PuppetEvent::Login(EventLoginPayload {
-                                contact_id: payload.contact_id.unwrap(),
-                            }));
-                        }
-                    }
-                    26 => {
-                        // Log out
-                        if payload.contact_id == None || payload.data == None {
-                            error!("Logout payload should have contact id and data");
-                        } else if let serde_json::Value::String(data) = payload.data.unwrap() {
-                            self.emit(PuppetEvent::Logout(EventLogoutPayload {
-                                contact_id: payload.contact_id.unwrap(),
-                                data,
-                            }));
-                        } else {
-                            error!("Logout payload should have string data");
-                        }
-                    }
-                    27 => {
-                        // Dirty
-                        if payload.payload_type == None || payload.payload_id == None {
-                            error!("Dirty payload should have payload type and payload id");
-                        } else {
-                            self.emit(PuppetEvent::Dirty(EventDirtyPayload {
-                                payload_type: payload.payload_type.unwrap(),
-                                payload_id: payload.payload_id.unwrap(),
-                            }));
-                        }
-                    }
-                    _ => {
-                        error!("Invalid event type: {}", response.r#type);
-                    }
+                self.stream_errors.clear();
+                self.last_event_at = Instant::now();
+                match decode_event(&response) {
+                    Ok(event) => self.emit(event),
+                    Err(DecodeError::Unspecified) => {}
+                    Err(e) => error!("{}", e),
                 }
             }
             Err(e) => {
                 error!("Network error: {}", e);
+                self.on_stream_error(ctx);
             }
         }
     }
 
-    fn finished(&mut self, _ctx: &mut Self::Context) {
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        self.last_event_at = Instant::now();
+        self.set_connection_state(ConnectionState::Connected);
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
         info!("Stream finished");
+        self.set_connection_state(ConnectionState::Disconnected);
+        self.schedule_reconnect(ctx);
     }
 }
 
@@ -366,7 +528,7 @@ impl PuppetImpl for PuppetService {
         debug!("contact_self_name_set(name = {})", name);
         match self.client().contact_self_name(ContactSelfNameRequest { name }).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to set contact self name".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to set contact self name".to_owned())),
         }
     }
 
@@ -374,7 +536,7 @@ impl PuppetImpl for PuppetService {
         debug!("contact_self_qr_code()");
         match self.client().contact_self_qr_code(ContactSelfQrCodeRequest {}).await {
             Ok(response) => Ok(response.into_inner().qrcode),
-            Err(_) => Err(PuppetError::Network("Failed to get contact self qrcode".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to get contact self qrcode".to_owned())),
         }
     }
 
@@ -386,7 +548,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to set contact self signature".to_owned())),
+            Err(status) => Err(map_status_error(
+                status,
+                "Failed to set contact self signature".to_owned(),
+            )),
         }
     }
 
@@ -401,10 +566,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to add tag {} for contact {}",
-                tag_id, contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to add tag {} for contact {}", tag_id, contact_id),
+            )),
         }
     }
 
@@ -419,10 +584,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to remove tag {} for contact {}",
-                tag_id, contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to remove tag {} for contact {}", tag_id, contact_id),
+            )),
         }
     }
 
@@ -434,7 +599,7 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!("Failed to remove tag {}", tag_id))),
+            Err(status) => Err(map_status_error(status, format!("Failed to remove tag {}", tag_id))),
         }
     }
 
@@ -448,10 +613,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().ids),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get tags for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get tags for contact {}", contact_id),
+            )),
         }
     }
 
@@ -463,7 +628,7 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().ids),
-            Err(_) => Err(PuppetError::Network("Failed to get tags".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to get tags".to_owned())),
         }
     }
 
@@ -478,10 +643,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().alias.unwrap()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get alias of contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get alias of contact {}", contact_id),
+            )),
         }
     }
 
@@ -496,10 +661,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set alias for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set alias for contact {}", contact_id),
+            )),
         }
     }
 
@@ -513,11 +678,11 @@ impl PuppetImpl for PuppetService {
             })
             .await
         {
-            Ok(response) => Ok(FileBox::from(response.into_inner().filebox.unwrap())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get avatar of contact {}",
-                contact_id
-            ))),
+            Ok(response) => FileBox::try_from_gateway(response.into_inner().filebox),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get avatar of contact {}", contact_id),
+            )),
         }
     }
 
@@ -532,10 +697,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set avatar for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set avatar for contact {}", contact_id),
+            )),
         }
     }
 
@@ -553,10 +718,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set phone for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set phone for contact {}", contact_id),
+            )),
         }
     }
 
@@ -578,10 +743,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set corporation remark for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set corporation remark for contact {}", contact_id),
+            )),
         }
     }
 
@@ -603,10 +768,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set description for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set description for contact {}", contact_id),
+            )),
         }
     }
 
@@ -614,7 +779,7 @@ impl PuppetImpl for PuppetService {
         debug!("contact_list()");
         match self.client().contact_list(ContactListRequest {}).await {
             Ok(response) => Ok(response.into_inner().ids),
-            Err(_) => Err(PuppetError::Network("Failed to get contacts".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to get contacts".to_owned())),
         }
     }
 
@@ -626,10 +791,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(ContactPayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for contact {}",
-                contact_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get raw payload for contact {}", contact_id),
+            )),
         }
     }
 
@@ -641,10 +806,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get contact of message {}",
-                message_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get contact of message {}", message_id),
+            )),
         }
     }
 
@@ -655,11 +820,11 @@ impl PuppetImpl for PuppetService {
             .message_file(MessageFileRequest { id: message_id.clone() })
             .await
         {
-            Ok(response) => Ok(FileBox::from(response.into_inner().filebox)),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get file of message {}",
-                message_id
-            ))),
+            Ok(response) => FileBox::try_from_gateway(Some(response.into_inner().filebox)),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get file of message {}", message_id),
+            )),
         }
     }
 
@@ -673,11 +838,11 @@ impl PuppetImpl for PuppetService {
             })
             .await
         {
-            Ok(response) => Ok(FileBox::from(response.into_inner().filebox)),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get image of message {}",
-                message_id
-            ))),
+            Ok(response) => FileBox::try_from_gateway(Some(response.into_inner().filebox)),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get image of message {}", message_id),
+            )),
         }
     }
 
@@ -688,11 +853,11 @@ impl PuppetImpl for PuppetService {
             .message_mini_program(MessageMiniProgramRequest { id: message_id.clone() })
             .await
         {
-            Ok(response) => Ok(from_str(&response.into_inner().mini_program).unwrap()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get mini_program of message {}",
-                message_id
-            ))),
+            Ok(response) => parse_json("mini_program", &response.into_inner().mini_program),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get mini_program of message {}", message_id),
+            )),
         }
     }
 
@@ -703,11 +868,11 @@ impl PuppetImpl for PuppetService {
             .message_url(MessageUrlRequest { id: message_id.clone() })
             .await
         {
-            Ok(response) => Ok(from_str(&response.into_inner().url_link).unwrap()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get url link of message {}",
-                message_id
-            ))),
+            Ok(response) => parse_json("url_link", &response.into_inner().url_link),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get url link of message {}", message_id),
+            )),
         }
     }
 
@@ -729,10 +894,13 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to send contact {} in conversation {}",
-                contact_id, conversation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!(
+                    "Failed to send contact {} in conversation {}",
+                    contact_id, conversation_id
+                ),
+            )),
         }
     }
 
@@ -750,10 +918,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to send file in conversation {}",
-                conversation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to send file in conversation {}", conversation_id),
+            )),
         }
     }
 
@@ -766,19 +934,20 @@ impl PuppetImpl for PuppetService {
             "message_send_file(conversation_id = {}, mini_program_payload = {:?})",
             conversation_id, mini_program_payload
         );
+        let mini_program = to_json("mini_program", &mini_program_payload, self.json_casing_)?;
         match self
             .client()
             .message_send_mini_program(MessageSendMiniProgramRequest {
                 conversation_id: conversation_id.clone(),
-                mini_program: to_string::<MiniProgramPayload>(&mini_program_payload).unwrap(),
+                mini_program,
             })
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to send mini program in conversation {}",
-                conversation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to send mini program in conversation {}", conversation_id),
+            )),
         }
     }
 
@@ -802,10 +971,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to send text in conversation {}",
-                conversation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to send text in conversation {}", conversation_id),
+            )),
         }
     }
 
@@ -818,19 +987,20 @@ impl PuppetImpl for PuppetService {
             "message_send_url(conversation_id = {}, url_link_payload = {:?})",
             conversation_id, url_link_payload
         );
+        let url_link = to_json("url_link", &url_link_payload, self.json_casing_)?;
         match self
             .client()
             .message_send_url(MessageSendUrlRequest {
                 conversation_id: conversation_id.clone(),
-                url_link: to_string::<UrlLinkPayload>(&url_link_payload).unwrap(),
+                url_link,
             })
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to send url link in conversation {}",
-                conversation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to send url link in conversation {}", conversation_id),
+            )),
         }
     }
 
@@ -842,10 +1012,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(MessagePayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for message {}",
-                message_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get raw payload for message {}", message_id),
+            )),
         }
     }
 
@@ -859,10 +1029,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to accept friendship {}",
-                friendship_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to accept friendship {}", friendship_id),
+            )),
         }
     }
 
@@ -877,7 +1047,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!("Failed to add contact {}", contact_id))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to add contact {}", contact_id),
+            )),
         }
     }
 
@@ -889,7 +1062,7 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().contact_id),
-            Err(_) => Err(PuppetError::Network(format!("Failed to search phone {}", phone))),
+            Err(status) => Err(map_status_error(status, format!("Failed to search phone {}", phone))),
         }
     }
 
@@ -901,7 +1074,7 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().contact_id),
-            Err(_) => Err(PuppetError::Network(format!("Failed to search weixin {}", weixin))),
+            Err(status) => Err(map_status_error(status, format!("Failed to search weixin {}", weixin))),
         }
     }
 
@@ -916,10 +1089,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(FriendshipPayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for friendship {}",
-                friendship_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get raw payload for friendship {}", friendship_id),
+            )),
         }
     }
 
@@ -933,10 +1106,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to accept room invitation {}",
-                room_invitation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to accept room invitation {}", room_invitation_id),
+            )),
         }
     }
 
@@ -957,10 +1130,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(RoomInvitationPayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for room invitation {}",
-                room_invitation_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get raw payload for room invitation {}", room_invitation_id),
+            )),
         }
     }
 
@@ -975,10 +1148,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to add contact {} into room {}",
-                contact_id, room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to add contact {} into room {}", contact_id, room_id),
+            )),
         }
     }
 
@@ -989,11 +1162,11 @@ impl PuppetImpl for PuppetService {
             .room_avatar(RoomAvatarRequest { id: room_id.clone() })
             .await
         {
-            Ok(response) => Ok(FileBox::from(response.into_inner().filebox)),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get avatar of room {}",
-                room_id
-            ))),
+            Ok(response) => FileBox::try_from_gateway(Some(response.into_inner().filebox)),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get avatar of room {}", room_id),
+            )),
         }
     }
 
@@ -1011,7 +1184,7 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(PuppetError::Network("Failed to create room".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to create room".to_owned())),
         }
     }
 
@@ -1026,10 +1199,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to remove contact {} from room {}",
-                contact_id, room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to remove contact {} from room {}", contact_id, room_id),
+            )),
         }
     }
 
@@ -1041,10 +1214,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().qrcode),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get qrcode of room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get qrcode of room {}", room_id),
+            )),
         }
     }
 
@@ -1052,7 +1225,7 @@ impl PuppetImpl for PuppetService {
         debug!("room_quit(room_id = {})", room_id);
         match self.client().room_quit(RoomQuitRequest { id: room_id.clone() }).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!("Failed to quit room {}", room_id))),
+            Err(status) => Err(map_status_error(status, format!("Failed to quit room {}", room_id))),
         }
     }
 
@@ -1067,7 +1240,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().topic.unwrap()),
-            Err(_) => Err(PuppetError::Network(format!("Failed to get topic of room {}", room_id))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get topic of room {}", room_id),
+            )),
         }
     }
 
@@ -1082,10 +1258,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set topic for room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set topic for room {}", room_id),
+            )),
         }
     }
 
@@ -1093,7 +1269,7 @@ impl PuppetImpl for PuppetService {
         debug!("room_list()");
         match self.client().room_list(RoomListRequest {}).await {
             Ok(response) => Ok(response.into_inner().ids),
-            Err(_) => Err(PuppetError::Network("Failed to get rooms".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to get rooms".to_owned())),
         }
     }
 
@@ -1105,10 +1281,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(RoomPayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get raw payload for room {}", room_id),
+            )),
         }
     }
 
@@ -1123,10 +1299,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().text.unwrap()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get announce of room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get announce of room {}", room_id),
+            )),
         }
     }
 
@@ -1141,10 +1317,10 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to set announce for room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to set announce for room {}", room_id),
+            )),
         }
     }
 
@@ -1156,13 +1332,15 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(response.into_inner().member_ids),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get members of room {}",
-                room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!("Failed to get members of room {}", room_id),
+            )),
         }
     }
 
+    /// The gRPC protocol has no bulk room member RPC, so `room_member_payload_list` is left unsupported and `Puppet`
+    /// loads the members one by one with this.
     async fn room_member_raw_payload(
         &self,
         room_id: String,
@@ -1181,26 +1359,32 @@ impl PuppetImpl for PuppetService {
             .await
         {
             Ok(response) => Ok(RoomMemberPayload::from_payload_response(response.into_inner())),
-            Err(_) => Err(PuppetError::Network(format!(
-                "Failed to get raw payload for member {} of room {}",
-                contact_id, room_id
-            ))),
+            Err(status) => Err(map_status_error(
+                status,
+                format!(
+                    "Failed to get raw payload for member {} of room {}",
+                    contact_id, room_id
+                ),
+            )),
         }
     }
 
     async fn start(&self) -> Result<(), PuppetError> {
         debug!("start()");
+        self.connection_.set_stopped(false);
         match self.client().start(StartRequest {}).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to start puppet".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to start puppet".to_owned())),
         }
     }
 
+    /// Stop the puppet, the event stream is no longer reestablished even if stopping the gateway fails.
     async fn stop(&self) -> Result<(), PuppetError> {
         debug!("stop()");
+        self.connection_.set_stopped(true);
         match self.client().stop(StopRequest {}).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to stop puppet".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to stop puppet".to_owned())),
         }
     }
 
@@ -1208,31 +1392,262 @@ impl PuppetImpl for PuppetService {
         debug!("ding(data = {})", data);
         match self.client().ding(DingRequest { data }).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to ding".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to ding".to_owned())),
         }
     }
 
     async fn version(&self) -> Result<String, PuppetError> {
         debug!("version()");
-        match self.client().version(VersionRequest {}).await {
-            Ok(response) => Ok(response.into_inner().version),
-            Err(_) => Err(PuppetError::Network("Failed to get puppet version".to_owned())),
-        }
+        gateway_version(&self.connection_).await
     }
 
     async fn logout(&self) -> Result<(), PuppetError> {
         debug!("logout()");
         match self.client().logout(LogoutRequest {}).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(PuppetError::Network("Failed to logout".to_owned())),
+            Err(status) => Err(map_status_error(status, "Failed to logout".to_owned())),
+        }
+    }
+
+    fn set_heartbeat_timeout(&self, heartbeat_timeout: Option<Duration>) -> Result<(), PuppetError> {
+        debug!("set_heartbeat_timeout(heartbeat_timeout = {:?})", heartbeat_timeout);
+        if heartbeat_timeout == Some(Duration::ZERO) {
+            return Err(PuppetError::InvalidArgument(
+                "heartbeat timeout must be positive".to_owned(),
+            ));
+        }
+        self.addr
+            .do_send(PuppetServiceInternalMessage::SetHeartbeatTimeout(heartbeat_timeout));
+        Ok(())
+    }
+
+    /// The gRPC protocol has no moment API, everything else is forwarded to the gateway.
+    fn capabilities(&self) -> PuppetCapabilities {
+        debug!("capabilities()");
+        PuppetCapabilities {
+            supports_moment: false,
+            ..Default::default()
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use actix::Actor;
+    use futures::future::BoxFuture;
+    use hyper::header::{HeaderValue, CONTENT_TYPE, USER_AGENT};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, HeaderMap};
+    use prost::Message as _;
+
     use super::*;
 
+    struct ConnectionStateRecorder {
+        states: Arc<Mutex<Vec<ConnectionState>>>,
+    }
+
+    impl Actor for ConnectionStateRecorder {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<PuppetEvent> for ConnectionStateRecorder {
+        type Result = ();
+
+        fn handle(&mut self, msg: PuppetEvent, _ctx: &mut Self::Context) -> Self::Result {
+            if let PuppetEvent::ConnectionChange(payload) = msg {
+                self.states.lock().unwrap().push(payload.state);
+            }
+        }
+    }
+
+    fn failing_stream(errors: usize) -> impl futures::Stream<Item = Result<EventResponse, Status>> {
+        use futures::StreamExt;
+
+        futures::stream::iter((0..errors).map(|_| Status::unavailable("connection reset")).map(Err))
+            .chain(futures::stream::pending())
+    }
+
+    fn stream_error_policy(action: StreamErrorAction) -> Option<StreamErrorPolicy> {
+        Some(StreamErrorPolicy {
+            max_errors: 3,
+            window: Duration::from_secs(60),
+            action,
+        })
+    }
+
+    /// A puppet service pointing at a port nobody listens on, so that every call to the gateway fails.
+    fn offline_service() -> Puppet<PuppetService> {
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let connection = Connection::new(channel);
+        Puppet::new(PuppetService {
+            connection_: connection.clone(),
+            addr: PuppetServiceInner::new(connection, None).start(),
+            json_casing_: JsonCasing::default(),
+        })
+    }
+
+    /// A gateway listening on `endpoint`, see `mock_gateway`.
+    struct MockGateway {
+        endpoint: String,
+        /// User agent of every request the gateway receives.
+        user_agents: Arc<Mutex<Vec<String>>>,
+        /// Number of connections made to the gateway.
+        dials: Arc<AtomicUsize>,
+    }
+
+    /// A gateway that reports `version`, or fails to if not given, and opens an event stream that stays silent.
+    fn mock_gateway(version: Option<&'static str>) -> MockGateway {
+        let user_agents = Arc::new(Mutex::new(vec![]));
+        let dials = Arc::new(AtomicUsize::new(0));
+        let recorded = user_agents.clone();
+        let dialed = dials.clone();
+        let make_service = make_service_fn(move |_| {
+            let recorded = recorded.clone();
+            dialed.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                    if let Some(user_agent) = request.headers().get(USER_AGENT) {
+                        recorded.lock().unwrap().push(user_agent.to_str().unwrap().to_owned());
+                    }
+                    let response = mock_gateway_response(request.uri().path(), version);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into())
+            .http2_only(true)
+            .serve(make_service);
+        let endpoint = format!("http://{}", server.local_addr());
+        actix_rt::spawn(server);
+        MockGateway {
+            endpoint,
+            user_agents,
+            dials,
+        }
+    }
+
+    fn mock_gateway_response(path: &str, version: Option<&str>) -> hyper::Response<Body> {
+        let response = hyper::Response::builder().header(CONTENT_TYPE, "application/grpc");
+        let (mut sender, body) = Body::channel();
+        match (path, version) {
+            ("/wechaty.Puppet/Version", Some(version)) => {
+                let mut message = vec![];
+                VersionResponse {
+                    version: version.to_owned(),
+                }
+                .encode(&mut message)
+                .unwrap();
+                // A gRPC message is framed by a compression flag and its length.
+                let mut frame = vec![0];
+                frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+                frame.extend(message);
+                tokio::spawn(async move {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    sender.send_data(frame.into()).await.unwrap();
+                    sender.send_trailers(trailers).await.unwrap();
+                });
+            }
+            ("/wechaty.Puppet/Event", _) => {
+                tokio::spawn(async move {
+                    let _sender = sender;
+                    futures::future::pending::<()>().await;
+                });
+            }
+            // Every other call is answered with a status only, telling that it is not implemented.
+            _ => return response.header("grpc-status", "12").body(Body::empty()).unwrap(),
+        }
+        response.body(body).unwrap()
+    }
+
+    #[test]
+    fn can_map_status_errors() {
+        let reason = "Failed to send text".to_owned();
+        assert!(matches!(
+            map_status_error(Status::resource_exhausted("slow down"), reason.clone()),
+            PuppetError::RateLimited(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::unknown("Operation too frequent"), reason.clone()),
+            PuppetError::RateLimited(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::permission_denied("Account is banned"), reason.clone()),
+            PuppetError::AccountBanned(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::unknown("Your account is blocked"), reason.clone()),
+            PuppetError::AccountBanned(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::not_found("no such contact"), reason.clone()),
+            PuppetError::NotFound(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::not_found("blocked contact not found"), reason.clone()),
+            PuppetError::NotFound(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::unknown("contact is blocked"), reason.clone()),
+            PuppetError::Network(_)
+        ));
+        assert!(matches!(
+            map_status_error(Status::unavailable("connection reset"), reason),
+            PuppetError::Network(_)
+        ));
+    }
+
+    #[test]
+    fn grpc_endpoint_requires_valid_user_agent() {
+        assert_eq!(default_user_agent(), "rust-wechaty/0.1.0-beta.1");
+        assert!(grpc_endpoint("http://127.0.0.1:1".to_owned(), default_user_agent()).is_ok());
+        assert!(matches!(
+            grpc_endpoint("http://127.0.0.1:1".to_owned(), "bad\nagent".to_owned()),
+            Err(PuppetError::InvalidArgument(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn gateway_receives_user_agent() {
+        let gateway = mock_gateway(Some("0.22.0"));
+        PuppetService::new(PuppetOptions {
+            endpoint: Some(gateway.endpoint),
+            user_agent: Some("my-bot/1.0".to_owned()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // Both the version check and the event stream carry the user agent, which tonic appends its own to.
+        let user_agents = gateway.user_agents.lock().unwrap().clone();
+        assert_eq!(user_agents.len(), 2);
+        assert!(user_agents
+            .iter()
+            .all(|user_agent| user_agent.starts_with("my-bot/1.0 tonic/")));
+    }
+
+    #[actix_rt::test]
+    async fn rpcs_share_one_connection() {
+        let gateway = mock_gateway(Some("0.22.0"));
+        let puppet = PuppetService::new(PuppetOptions {
+            endpoint: Some(gateway.endpoint),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        for _ in 0..3 {
+            assert_eq!(puppet.version().await.unwrap(), "0.22.0");
+        }
+
+        // The version check, the event stream and every call since have gone through the first connection.
+        assert_eq!(gateway.user_agents.lock().unwrap().len(), 5);
+        assert_eq!(gateway.dials.load(Ordering::SeqCst), 1);
+    }
+
     #[actix_rt::test]
     async fn cannot_create_puppet_service_with_invalid_token() {
         let invalid_token = uuid::Uuid::new_v4().to_string();
@@ -1241,6 +1656,7 @@ mod tests {
             endpoint: None,
             timeout: None,
             token: Some(invalid_token),
+            ..Default::default()
         })
         .await
         {
@@ -1248,4 +1664,425 @@ mod tests {
             Ok(_) => println!("Create puppet service successfully"),
         }
     }
+
+    #[actix_rt::test]
+    async fn options_are_validated_before_connecting() {
+        assert!(matches!(
+            PuppetService::new(PuppetOptions::default()).await,
+            Err(PuppetError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            PuppetService::new(PuppetOptions {
+                endpoint: Some("http://127.0.0.1:1".to_owned()),
+                timeout: Some(0),
+                ..Default::default()
+            })
+            .await,
+            Err(PuppetError::InvalidConfig(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn reconnect_delays_are_jittered() {
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None).with_reconnect_jitter(Some(0.5));
+        let mut backoff = MIN_RECONNECT_DELAY;
+        for _ in 0..10 {
+            let delay = inner.next_reconnect_delay();
+            assert!(
+                delay >= backoff / 2 && delay <= backoff,
+                "{:?} is not within the jittered range of {:?}",
+                delay,
+                backoff
+            );
+            backoff = (backoff * 2).min(MAX_RECONNECT_DELAY);
+        }
+        assert_eq!(inner.reconnect_delay, MAX_RECONNECT_DELAY);
+
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None).with_reconnect_jitter(Some(1.0));
+        for _ in 0..10 {
+            assert!(inner.next_reconnect_delay() >= MIN_RECONNECT_DELAY / 2);
+            inner.reconnect_delay = MIN_RECONNECT_DELAY;
+        }
+
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None);
+        assert_eq!(inner.next_reconnect_delay(), MIN_RECONNECT_DELAY);
+        assert_eq!(inner.next_reconnect_delay(), MIN_RECONNECT_DELAY * 2);
+    }
+
+    #[actix_rt::test]
+    async fn connection_state_changes_on_disconnect_and_reconnect() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        // Nothing listens on this port, so every reconnect attempt fails.
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None);
+        inner.callback_addr = Some(recorder.recipient());
+        inner.reconnect_delay = Duration::from_millis(10);
+        let _addr = PuppetServiceInner::create(|ctx| {
+            ctx.add_stream(futures::stream::empty::<Result<EventResponse, Status>>());
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(500)).await;
+        let states = states.lock().unwrap().clone();
+        assert_eq!(
+            states[..5],
+            [
+                ConnectionState::Connected,
+                ConnectionState::Disconnected,
+                ConnectionState::Reconnecting,
+                ConnectionState::Connecting,
+                ConnectionState::Reconnecting,
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn stopped_puppet_does_not_reconnect() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let connection = Connection::new(channel);
+        connection.set_stopped(true);
+        let mut inner = PuppetServiceInner::new(connection, None);
+        inner.callback_addr = Some(recorder.recipient());
+        inner.reconnect_delay = Duration::from_millis(10);
+        let _addr = PuppetServiceInner::create(|ctx| {
+            ctx.add_stream(futures::stream::empty::<Result<EventResponse, Status>>());
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            *states.lock().unwrap(),
+            [ConnectionState::Connected, ConnectionState::Disconnected]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn stream_error_policy_reconnects() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(
+            Connection::new(channel),
+            stream_error_policy(StreamErrorAction::Reconnect),
+        );
+        inner.callback_addr = Some(recorder.recipient());
+        inner.reconnect_delay = Duration::from_secs(60);
+        let _addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(failing_stream(3)));
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            states.lock().unwrap().clone(),
+            vec![
+                ConnectionState::Connected,
+                ConnectionState::Disconnected,
+                ConnectionState::Reconnecting,
+            ]
+        );
+    }
+
+    fn heartbeat_stream(interval: Duration) -> impl futures::Stream<Item = Result<EventResponse, Status>> {
+        futures::stream::unfold((), move |_| async move {
+            actix_rt::time::sleep(interval).await;
+            let heartbeat = EventResponse {
+                r#type: 1,
+                payload: r#"{"data":"heartbeat"}"#.to_owned(),
+            };
+            Some((Ok(heartbeat), ()))
+        })
+    }
+
+    #[actix_rt::test]
+    async fn heartbeat_watchdog_reconnects_when_events_stop() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None)
+            .with_heartbeat_timeout(Some(Duration::from_millis(50)));
+        inner.callback_addr = Some(recorder.recipient());
+        inner.reconnect_delay = Duration::from_secs(60);
+        let _addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(futures::stream::pending()));
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            states.lock().unwrap().clone(),
+            vec![
+                ConnectionState::Connected,
+                ConnectionState::Disconnected,
+                ConnectionState::Reconnecting,
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn heartbeat_timeout_can_be_set_after_start() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let connection = Connection::new(channel);
+        let mut inner = PuppetServiceInner::new(connection.clone(), None);
+        inner.callback_addr = Some(recorder.recipient());
+        inner.reconnect_delay = Duration::from_secs(60);
+        let addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(futures::stream::pending()));
+            inner
+        });
+        let puppet = Puppet::new(PuppetService {
+            connection_: connection,
+            addr,
+            json_casing_: JsonCasing::default(),
+        });
+        assert!(matches!(
+            puppet.set_heartbeat_timeout(Some(Duration::ZERO)),
+            Err(PuppetError::InvalidArgument(_))
+        ));
+
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(states.lock().unwrap().clone(), vec![ConnectionState::Connected]);
+        puppet.set_heartbeat_timeout(Some(Duration::from_millis(50))).unwrap();
+        actix_rt::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            states.lock().unwrap().clone(),
+            vec![
+                ConnectionState::Connected,
+                ConnectionState::Disconnected,
+                ConnectionState::Reconnecting,
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn heartbeats_keep_watchdog_quiet() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), None)
+            .with_heartbeat_timeout(Some(Duration::from_millis(50)));
+        inner.callback_addr = Some(recorder.recipient());
+        let _addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(heartbeat_stream(Duration::from_millis(10))));
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(states.lock().unwrap().clone(), vec![ConnectionState::Connected]);
+    }
+
+    #[actix_rt::test]
+    async fn stream_error_policy_stops_with_fatal_error() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let (sender, receiver) = oneshot::channel();
+        let connection = Connection::new(channel);
+        let mut inner = PuppetServiceInner::new(connection.clone(), stream_error_policy(StreamErrorAction::Stop));
+        inner.callback_addr = Some(recorder.recipient());
+        inner.fatal_error_sender = Some(sender);
+        let addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(failing_stream(3)));
+            inner
+        });
+
+        let fatal_error = actix_rt::time::timeout(Duration::from_secs(1), receiver).await;
+        assert!(matches!(fatal_error, Ok(Ok(PuppetError::Network(_)))));
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        assert!(!addr.connected());
+        assert!(!connection.is_connected());
+        assert_eq!(
+            states.lock().unwrap().clone(),
+            vec![ConnectionState::Connected, ConnectionState::Disconnected]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn stream_errors_below_threshold_are_only_logged() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorder = ConnectionStateRecorder { states: states.clone() }.start();
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let mut inner = PuppetServiceInner::new(Connection::new(channel), stream_error_policy(StreamErrorAction::Stop));
+        inner.callback_addr = Some(recorder.recipient());
+        let _addr = PuppetServiceInner::create(|ctx| {
+            inner.stream_handle = Some(ctx.add_stream(failing_stream(2)));
+            inner
+        });
+
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(states.lock().unwrap().clone(), vec![ConnectionState::Connected]);
+    }
+
+    #[actix_rt::test]
+    async fn can_box_different_puppets() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BoxedPuppet>();
+        assert_send_sync::<Puppet<BoxedPuppet>>();
+
+        let channel = Endpoint::from_static("http://127.0.0.1:1").connect_lazy().unwrap();
+        let connection = Connection::new(channel);
+        let puppet_service = PuppetService {
+            connection_: connection.clone(),
+            addr: PuppetServiceInner::new(connection, None).start(),
+            json_casing_: JsonCasing::default(),
+        };
+        let puppets = [
+            Puppet::new(BoxedPuppet::new(puppet_service)),
+            Puppet::new(BoxedPuppet::new(wechaty_puppet_mock::PuppetMock::default())),
+        ];
+
+        assert!(matches!(puppets[0].version().await, Err(PuppetError::Network(_))));
+        assert!(puppets[1].version().await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn gateway_features_without_rpcs_are_unsupported() {
+        let puppet = offline_service();
+        let calls: Vec<(&str, BoxFuture<'_, Result<(), PuppetError>>)> = vec![
+            (
+                "message_send_quote",
+                Box::pin(async {
+                    puppet
+                        .message_send_quote("contact".to_owned(), "hi".to_owned(), "message".to_owned())
+                        .await
+                        .map(drop)
+                }),
+            ),
+            (
+                "favorite_list",
+                Box::pin(async { puppet.favorite_list().await.map(drop) }),
+            ),
+            (
+                "favorite_add",
+                Box::pin(async { puppet.favorite_add("message".to_owned()).await.map(drop) }),
+            ),
+            (
+                "room_join_by_url",
+                Box::pin(async {
+                    puppet
+                        .room_join_by_url("https://weixin.qq.com/g/rust".to_owned())
+                        .await
+                        .map(drop)
+                }),
+            ),
+            (
+                "room_invitation_reject",
+                Box::pin(puppet.room_invitation_reject("invitation".to_owned())),
+            ),
+            ("contact_block", Box::pin(puppet.contact_block("contact".to_owned()))),
+            (
+                "contact_is_blocked",
+                Box::pin(async { puppet.contact_is_blocked("contact".to_owned()).await.map(drop) }),
+            ),
+            (
+                "message_chat_history",
+                Box::pin(async { puppet.message_chat_history("message".to_owned()).await.map(drop) }),
+            ),
+            ("room_mute", Box::pin(puppet.room_mute("room".to_owned()))),
+            ("room_unmute", Box::pin(puppet.room_unmute("room".to_owned()))),
+            (
+                "room_is_muted",
+                Box::pin(async { puppet.room_is_muted("room".to_owned()).await.map(drop) }),
+            ),
+        ];
+        for (method, call) in calls {
+            assert!(
+                matches!(call.await, Err(PuppetError::Unsupported(_))),
+                "{} should be unsupported",
+                method
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn room_search_fails_if_rooms_cannot_be_listed() {
+        let puppet = offline_service();
+        assert!(puppet.room_search(RoomQueryFilter::default()).await.is_err());
+    }
+
+    #[test]
+    fn malformed_json_is_a_deserialize_error() {
+        assert!(matches!(
+            parse_json::<MiniProgramPayload>("mini_program", "{\"appid\": 1"),
+            Err(PuppetError::Deserialize(reason)) if reason.starts_with("mini_program")
+        ));
+        assert!(matches!(
+            parse_json::<UrlLinkPayload>("url_link", "{\"title\": \"Wechaty\"}"),
+            Err(PuppetError::Deserialize(_))
+        ));
+        let url_link = UrlLinkPayload::new("Wechaty".to_owned(), "https://wechaty.js.org".to_owned()).unwrap();
+        let json = to_json("url_link", &url_link, JsonCasing::default()).unwrap();
+        assert_eq!(
+            parse_json::<UrlLinkPayload>("url_link", &json).unwrap().url,
+            url_link.url
+        );
+    }
+
+    #[test]
+    fn json_payloads_follow_the_configured_casing() {
+        let url_link = UrlLinkPayload::new("Wechaty".to_owned(), "https://wechaty.js.org".to_owned())
+            .unwrap()
+            .with_thumbnail("https://wechaty.js.org/img/icon.png".to_owned());
+        let camel_case = to_json("url_link", &url_link, JsonCasing::CamelCase).unwrap();
+        assert!(camel_case.contains("\"thumbnailUrl\""));
+        let snake_case = to_json("url_link", &url_link, JsonCasing::SnakeCase).unwrap();
+        assert!(snake_case.contains("\"thumbnail_url\""));
+        assert!(!snake_case.contains("\"thumbnailUrl\""));
+        for json in [camel_case, snake_case] {
+            assert_eq!(
+                parse_json::<UrlLinkPayload>("url_link", &json).unwrap().thumbnail_url,
+                url_link.thumbnail_url
+            );
+        }
+
+        let mini_program: MiniProgramPayload = parse_json(
+            "mini_program",
+            "{\"appid\": \"wx1234\", \"page_path\": \"pages/index\"}",
+        )
+        .unwrap();
+        let default = to_json("mini_program", &mini_program, JsonCasing::default()).unwrap();
+        assert!(default.contains("\"page_path\":\"pages/index\""));
+        let camel_case = to_json("mini_program", &mini_program, JsonCasing::CamelCase).unwrap();
+        assert!(camel_case.contains("\"pagePath\":\"pages/index\""));
+    }
+
+    #[test]
+    fn can_parse_versions() {
+        assert_eq!(parse_version("0.22.0"), Some((0, 22, 0)));
+        assert_eq!(parse_version("v1.2.3-beta.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("2.1"), Some((2, 1, 0)));
+        assert_eq!(parse_version("unknown"), None);
+    }
+
+    #[actix_rt::test]
+    async fn old_gateway_is_rejected_in_strict_mode() {
+        let connect = |version, min_version: Option<&str>| {
+            PuppetService::new(PuppetOptions {
+                endpoint: Some(mock_gateway(version).endpoint),
+                min_version: min_version.map(str::to_owned),
+                ..Default::default()
+            })
+        };
+        assert!(matches!(
+            connect(Some("0.1.0"), Some("0.2.0")).await,
+            Err(PuppetError::IncompatibleVersion(_))
+        ));
+        assert!(connect(Some("v0.2.0-beta.1"), Some("0.2.0")).await.is_ok());
+        assert!(matches!(
+            connect(None, Some("0.1.0")).await,
+            Err(PuppetError::IncompatibleVersion(_))
+        ));
+
+        // Without a minimum version, gateways that are outdated or do not report a version are only warned about.
+        assert!(connect(Some("0.1.0"), None).await.is_ok());
+        assert!(connect(None, None).await.is_ok());
+    }
 }
@@ -1,17 +1,39 @@
+#[cfg(feature = "discovery")]
 use serde::Deserialize;
 use wechaty_puppet::error::PuppetError;
 
+#[cfg(feature = "discovery")]
 #[derive(Debug, Deserialize)]
 struct Endpoint {
     ip: String,
     port: usize,
 }
 
+#[cfg(feature = "discovery")]
 const WECHATY_ENDPOINT_RESOLUTION_SERVICE_URI: &str = "https://api.chatie.io/v0/hosties/";
+#[cfg(feature = "discovery")]
 const ENDPOINT_SERVICE_ERROR: &str = "Endpoint service error";
 
-pub async fn discover(token: String) -> Result<String, PuppetError> {
-    match reqwest::get(&format!("{}{}", WECHATY_ENDPOINT_RESOLUTION_SERVICE_URI, token)).await {
+#[cfg(feature = "discovery")]
+pub async fn discover(token: String, user_agent: &str) -> Result<String, PuppetError> {
+    discover_with(WECHATY_ENDPOINT_RESOLUTION_SERVICE_URI, token, user_agent).await
+}
+
+/// Discovery is compiled out without the `discovery` feature, so the endpoint has to be given explicitly.
+#[cfg(not(feature = "discovery"))]
+pub async fn discover(_token: String, _user_agent: &str) -> Result<String, PuppetError> {
+    Err(PuppetError::InvalidArgument(
+        "Endpoint discovery is disabled, supply an endpoint or enable the discovery feature".to_owned(),
+    ))
+}
+
+#[cfg(feature = "discovery")]
+async fn discover_with(service_uri: &str, token: String, user_agent: &str) -> Result<String, PuppetError> {
+    let client = match reqwest::Client::builder().user_agent(user_agent).build() {
+        Ok(client) => client,
+        Err(e) => return Err(PuppetError::InvalidArgument(format!("Invalid user agent: {}", e))),
+    };
+    match client.get(format!("{}{}", service_uri, token)).send().await {
         Ok(res) => match res.json::<Endpoint>().await {
             Ok(endpoint) => {
                 if endpoint.port == 0 {
@@ -28,10 +50,54 @@ pub async fn discover(token: String) -> Result<String, PuppetError> {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "discovery")]
+    use std::io::{Read, Write};
+    #[cfg(feature = "discovery")]
+    use std::net::TcpListener;
+    #[cfg(feature = "discovery")]
+    use std::thread;
+
     use super::*;
 
+    #[cfg(feature = "discovery")]
     #[actix_rt::test]
     async fn can_discover() {
-        println!("{:?}", discover("123".to_owned()).await);
+        println!("{:?}", discover("123".to_owned(), "rust-wechaty/test").await);
+    }
+
+    #[cfg(feature = "discovery")]
+    #[actix_rt::test]
+    async fn discover_sends_user_agent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let service_uri = format!("http://{}/v0/hosties/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"{"ip":"127.0.0.1","port":8788}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_lowercase()
+        });
+
+        let endpoint = discover_with(&service_uri, "token".to_owned(), "my-bot/1.0")
+            .await
+            .unwrap();
+        assert_eq!(endpoint, "grpc://127.0.0.1:8788");
+        assert!(server.join().unwrap().contains("user-agent: my-bot/1.0\r\n"));
+    }
+
+    #[cfg(not(feature = "discovery"))]
+    #[actix_rt::test]
+    async fn cannot_discover_without_feature() {
+        match discover("token".to_owned(), "rust-wechaty/test").await {
+            Err(PuppetError::InvalidArgument(reason)) => assert!(reason.contains("supply an endpoint")),
+            other => panic!("unexpected {:?}", other),
+        }
     }
 }
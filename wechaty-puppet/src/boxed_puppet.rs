@@ -0,0 +1,387 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::{
+    ContactPayload, FavoritePayload, FileBox, FriendshipPayload, ImageType, MessagePayload, MiniProgramPayload,
+    MoneyPayload, PuppetCapabilities, PuppetError, PuppetImpl, RoomInvitationPayload, RoomMemberPayload, RoomPayload,
+    UrlLinkPayload,
+};
+
+/// A type-erased puppet implementation.
+///
+/// `Puppet<T>` and everything built on it are generic over the puppet implementation, which makes it hard to
+/// choose one at runtime, e.g. `PuppetService` in production and `PuppetMock` in tests. `BoxedPuppet` wraps any
+/// implementation behind a trait object, so that `Puppet<BoxedPuppet>` can be used with either of them.
+///
+/// The price is a dynamic dispatch and an extra allocation for each call, and the concrete type can no longer
+/// be recovered, so prefer the concrete type if it is known at compile time.
+#[derive(Clone)]
+pub struct BoxedPuppet(Arc<dyn PuppetImpl + Send + Sync>);
+
+impl BoxedPuppet {
+    pub fn new<T>(puppet_impl: T) -> Self
+    where
+        T: 'static + PuppetImpl + Send + Sync,
+    {
+        Self(Arc::new(puppet_impl))
+    }
+}
+
+impl fmt::Debug for BoxedPuppet {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "BoxedPuppet")
+    }
+}
+
+#[async_trait]
+impl PuppetImpl for BoxedPuppet {
+    async fn contact_self_name_set(&self, name: String) -> Result<(), PuppetError> {
+        self.0.contact_self_name_set(name).await
+    }
+
+    async fn contact_self_qr_code(&self) -> Result<String, PuppetError> {
+        self.0.contact_self_qr_code().await
+    }
+
+    async fn contact_self_signature_set(&self, signature: String) -> Result<(), PuppetError> {
+        self.0.contact_self_signature_set(signature).await
+    }
+
+    async fn tag_contact_add(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
+        self.0.tag_contact_add(tag_id, contact_id).await
+    }
+
+    async fn tag_contact_remove(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
+        self.0.tag_contact_remove(tag_id, contact_id).await
+    }
+
+    async fn tag_contact_delete(&self, tag_id: String) -> Result<(), PuppetError> {
+        self.0.tag_contact_delete(tag_id).await
+    }
+
+    async fn tag_contact_list(&self, contact_id: String) -> Result<Vec<String>, PuppetError> {
+        self.0.tag_contact_list(contact_id).await
+    }
+
+    async fn tag_list(&self) -> Result<Vec<String>, PuppetError> {
+        self.0.tag_list().await
+    }
+
+    async fn contact_alias(&self, contact_id: String) -> Result<String, PuppetError> {
+        self.0.contact_alias(contact_id).await
+    }
+
+    async fn contact_alias_set(&self, contact_id: String, alias: String) -> Result<(), PuppetError> {
+        self.0.contact_alias_set(contact_id, alias).await
+    }
+
+    async fn contact_avatar(&self, contact_id: String) -> Result<FileBox, PuppetError> {
+        self.0.contact_avatar(contact_id).await
+    }
+
+    async fn contact_avatar_set(&self, contact_id: String, file: FileBox) -> Result<(), PuppetError> {
+        self.0.contact_avatar_set(contact_id, file).await
+    }
+
+    async fn contact_phone_set(&self, contact_id: String, phone_list: Vec<String>) -> Result<(), PuppetError> {
+        self.0.contact_phone_set(contact_id, phone_list).await
+    }
+
+    async fn contact_corporation_remark_set(
+        &self,
+        contact_id: String,
+        corporation_remark: Option<String>,
+    ) -> Result<(), PuppetError> {
+        self.0
+            .contact_corporation_remark_set(contact_id, corporation_remark)
+            .await
+    }
+
+    async fn contact_description_set(
+        &self,
+        contact_id: String,
+        description: Option<String>,
+    ) -> Result<(), PuppetError> {
+        self.0.contact_description_set(contact_id, description).await
+    }
+
+    async fn contact_list(&self) -> Result<Vec<String>, PuppetError> {
+        self.0.contact_list().await
+    }
+
+    async fn contact_raw_payload(&self, contact_id: String) -> Result<ContactPayload, PuppetError> {
+        self.0.contact_raw_payload(contact_id).await
+    }
+
+    async fn contact_exists(&self, contact_id: String) -> Result<bool, PuppetError> {
+        self.0.contact_exists(contact_id).await
+    }
+
+    async fn contact_block(&self, contact_id: String) -> Result<(), PuppetError> {
+        self.0.contact_block(contact_id).await
+    }
+
+    async fn contact_unblock(&self, contact_id: String) -> Result<(), PuppetError> {
+        self.0.contact_unblock(contact_id).await
+    }
+
+    async fn contact_is_blocked(&self, contact_id: String) -> Result<bool, PuppetError> {
+        self.0.contact_is_blocked(contact_id).await
+    }
+
+    async fn message_contact(&self, message_id: String) -> Result<String, PuppetError> {
+        self.0.message_contact(message_id).await
+    }
+
+    async fn message_file(&self, message_id: String) -> Result<FileBox, PuppetError> {
+        self.0.message_file(message_id).await
+    }
+
+    async fn message_image(&self, message_id: String, image_type: ImageType) -> Result<FileBox, PuppetError> {
+        self.0.message_image(message_id, image_type).await
+    }
+
+    async fn message_mini_program(&self, message_id: String) -> Result<MiniProgramPayload, PuppetError> {
+        self.0.message_mini_program(message_id).await
+    }
+
+    async fn message_url(&self, message_id: String) -> Result<UrlLinkPayload, PuppetError> {
+        self.0.message_url(message_id).await
+    }
+
+    async fn message_chat_history(&self, message_id: String) -> Result<Vec<MessagePayload>, PuppetError> {
+        self.0.message_chat_history(message_id).await
+    }
+
+    async fn message_send_contact(
+        &self,
+        conversation_id: String,
+        contact_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0.message_send_contact(conversation_id, contact_id).await
+    }
+
+    async fn message_send_file(&self, conversation_id: String, file: FileBox) -> Result<Option<String>, PuppetError> {
+        self.0.message_send_file(conversation_id, file).await
+    }
+
+    async fn message_send_mini_program(
+        &self,
+        conversation_id: String,
+        mini_program_payload: MiniProgramPayload,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0
+            .message_send_mini_program(conversation_id, mini_program_payload)
+            .await
+    }
+
+    async fn message_send_text(
+        &self,
+        conversation_id: String,
+        text: String,
+        mention_id_list: Vec<String>,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0.message_send_text(conversation_id, text, mention_id_list).await
+    }
+
+    async fn message_send_url(
+        &self,
+        conversation_id: String,
+        url_link_payload: UrlLinkPayload,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0.message_send_url(conversation_id, url_link_payload).await
+    }
+
+    async fn message_raw_payload(&self, message_id: String) -> Result<MessagePayload, PuppetError> {
+        self.0.message_raw_payload(message_id).await
+    }
+
+    async fn message_send_quote(
+        &self,
+        conversation_id: String,
+        text: String,
+        quoted_message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0
+            .message_send_quote(conversation_id, text, quoted_message_id)
+            .await
+    }
+
+    async fn message_forward_native(
+        &self,
+        conversation_id: String,
+        message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        self.0.message_forward_native(conversation_id, message_id).await
+    }
+
+    async fn message_money(&self, message_id: String) -> Result<MoneyPayload, PuppetError> {
+        self.0.message_money(message_id).await
+    }
+
+    async fn favorite_list(&self) -> Result<Vec<String>, PuppetError> {
+        self.0.favorite_list().await
+    }
+
+    async fn favorite_payload(&self, favorite_id: String) -> Result<FavoritePayload, PuppetError> {
+        self.0.favorite_payload(favorite_id).await
+    }
+
+    async fn favorite_add(&self, message_id: String) -> Result<String, PuppetError> {
+        self.0.favorite_add(message_id).await
+    }
+
+    async fn friendship_accept(&self, friendship_id: String) -> Result<(), PuppetError> {
+        self.0.friendship_accept(friendship_id).await
+    }
+
+    async fn friendship_add(&self, contact_id: String, hello: Option<String>) -> Result<(), PuppetError> {
+        self.0.friendship_add(contact_id, hello).await
+    }
+
+    async fn friendship_search_phone(&self, phone: String) -> Result<Option<String>, PuppetError> {
+        self.0.friendship_search_phone(phone).await
+    }
+
+    async fn friendship_search_weixin(&self, weixin: String) -> Result<Option<String>, PuppetError> {
+        self.0.friendship_search_weixin(weixin).await
+    }
+
+    async fn friendship_raw_payload(&self, friendship_id: String) -> Result<FriendshipPayload, PuppetError> {
+        self.0.friendship_raw_payload(friendship_id).await
+    }
+
+    async fn room_invitation_accept(&self, room_invitation_id: String) -> Result<(), PuppetError> {
+        self.0.room_invitation_accept(room_invitation_id).await
+    }
+
+    async fn room_invitation_reject(&self, room_invitation_id: String) -> Result<(), PuppetError> {
+        self.0.room_invitation_reject(room_invitation_id).await
+    }
+
+    async fn room_join_by_url(&self, url: String) -> Result<String, PuppetError> {
+        self.0.room_join_by_url(url).await
+    }
+
+    async fn room_invitation_raw_payload(
+        &self,
+        room_invitation_id: String,
+    ) -> Result<RoomInvitationPayload, PuppetError> {
+        self.0.room_invitation_raw_payload(room_invitation_id).await
+    }
+
+    async fn room_add(&self, room_id: String, contact_id: String) -> Result<(), PuppetError> {
+        self.0.room_add(room_id, contact_id).await
+    }
+
+    async fn room_avatar(&self, room_id: String) -> Result<FileBox, PuppetError> {
+        self.0.room_avatar(room_id).await
+    }
+
+    async fn room_create(&self, contact_id_list: Vec<String>, topic: Option<String>) -> Result<String, PuppetError> {
+        self.0.room_create(contact_id_list, topic).await
+    }
+
+    async fn room_del(&self, room_id: String, contact_id: String) -> Result<(), PuppetError> {
+        self.0.room_del(room_id, contact_id).await
+    }
+
+    async fn room_qr_code(&self, room_id: String) -> Result<String, PuppetError> {
+        self.0.room_qr_code(room_id).await
+    }
+
+    async fn room_quit(&self, room_id: String) -> Result<(), PuppetError> {
+        self.0.room_quit(room_id).await
+    }
+
+    async fn room_topic(&self, room_id: String) -> Result<String, PuppetError> {
+        self.0.room_topic(room_id).await
+    }
+
+    async fn room_topic_set(&self, room_id: String, topic: String) -> Result<(), PuppetError> {
+        self.0.room_topic_set(room_id, topic).await
+    }
+
+    async fn room_list(&self) -> Result<Vec<String>, PuppetError> {
+        self.0.room_list().await
+    }
+
+    async fn room_raw_payload(&self, room_id: String) -> Result<RoomPayload, PuppetError> {
+        self.0.room_raw_payload(room_id).await
+    }
+
+    async fn room_exists(&self, room_id: String) -> Result<bool, PuppetError> {
+        self.0.room_exists(room_id).await
+    }
+
+    async fn room_announce(&self, room_id: String) -> Result<String, PuppetError> {
+        self.0.room_announce(room_id).await
+    }
+
+    async fn room_announce_set(&self, room_id: String, text: String) -> Result<(), PuppetError> {
+        self.0.room_announce_set(room_id, text).await
+    }
+
+    async fn room_mute(&self, room_id: String) -> Result<(), PuppetError> {
+        self.0.room_mute(room_id).await
+    }
+
+    async fn room_unmute(&self, room_id: String) -> Result<(), PuppetError> {
+        self.0.room_unmute(room_id).await
+    }
+
+    async fn room_is_muted(&self, room_id: String) -> Result<bool, PuppetError> {
+        self.0.room_is_muted(room_id).await
+    }
+
+    async fn room_member_list(&self, room_id: String) -> Result<Vec<String>, PuppetError> {
+        self.0.room_member_list(room_id).await
+    }
+
+    async fn room_member_raw_payload(
+        &self,
+        room_id: String,
+        contact_id: String,
+    ) -> Result<RoomMemberPayload, PuppetError> {
+        self.0.room_member_raw_payload(room_id, contact_id).await
+    }
+
+    async fn room_member_payload_list(
+        &self,
+        room_id: String,
+        member_id_list: Vec<String>,
+    ) -> Result<Vec<RoomMemberPayload>, PuppetError> {
+        self.0.room_member_payload_list(room_id, member_id_list).await
+    }
+
+    async fn start(&self) -> Result<(), PuppetError> {
+        self.0.start().await
+    }
+
+    async fn stop(&self) -> Result<(), PuppetError> {
+        self.0.stop().await
+    }
+
+    async fn ding(&self, data: String) -> Result<(), PuppetError> {
+        self.0.ding(data).await
+    }
+
+    async fn version(&self) -> Result<String, PuppetError> {
+        self.0.version().await
+    }
+
+    async fn logout(&self) -> Result<(), PuppetError> {
+        self.0.logout().await
+    }
+
+    fn capabilities(&self) -> PuppetCapabilities {
+        self.0.capabilities()
+    }
+
+    fn set_heartbeat_timeout(&self, heartbeat_timeout: Option<Duration>) -> Result<(), PuppetError> {
+        self.0.set_heartbeat_timeout(heartbeat_timeout)
+    }
+}
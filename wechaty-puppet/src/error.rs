@@ -3,12 +3,29 @@ use std::{error, fmt};
 /// The errors that can occur during the communication with the puppet.
 pub enum PuppetError {
     InvalidToken,
+    InvalidArgument(String),
+    InvalidConfig(String),
     Network(String),
+    RateLimited(String),
+    AccountBanned(String),
+    IncompatibleVersion(String),
     Unsupported(String),
+    Deserialize(String),
+    NotFound(String),
     UnknownPayloadType,
     UnknownMessageType,
 }
 
+impl PuppetError {
+    /// Check whether the failed call may succeed if retried later.
+    ///
+    /// Network failures are transient and rate limits pass after backing off, everything else fails the same
+    /// way when retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, PuppetError::Network(_) | PuppetError::RateLimited(_))
+    }
+}
+
 impl fmt::Debug for PuppetError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "PuppetError({})", self)
@@ -19,8 +36,15 @@ impl fmt::Display for PuppetError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PuppetError::InvalidToken => write!(fmt, "Invalid token"),
+            PuppetError::InvalidArgument(reason) => write!(fmt, "Invalid argument: {}", reason),
+            PuppetError::InvalidConfig(reason) => write!(fmt, "Invalid config: {}", reason),
             PuppetError::Network(reason) => write!(fmt, "Network failure, reason: {}", reason),
+            PuppetError::RateLimited(reason) => write!(fmt, "Rate limited, reason: {}", reason),
+            PuppetError::AccountBanned(reason) => write!(fmt, "Account banned, reason: {}", reason),
+            PuppetError::IncompatibleVersion(reason) => write!(fmt, "Incompatible version: {}", reason),
             PuppetError::Unsupported(function) => write!(fmt, "Unsupported function: {}", function),
+            PuppetError::Deserialize(reason) => write!(fmt, "Failed to deserialize {}", reason),
+            PuppetError::NotFound(reason) => write!(fmt, "Not found: {}", reason),
             PuppetError::UnknownPayloadType => write!(fmt, "Unknown payload type"),
             PuppetError::UnknownMessageType => write!(fmt, "Unknown message type"),
         }
@@ -28,3 +52,29 @@ impl fmt::Display for PuppetError {
 }
 
 impl error::Error for PuppetError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_classify_retryable_errors() {
+        let cases = [
+            (PuppetError::InvalidToken, false),
+            (PuppetError::InvalidArgument("empty id".to_owned()), false),
+            (PuppetError::InvalidConfig("timeout must be positive".to_owned()), false),
+            (PuppetError::Network("connection reset".to_owned()), true),
+            (PuppetError::RateLimited("too frequent".to_owned()), true),
+            (PuppetError::AccountBanned("banned".to_owned()), false),
+            (PuppetError::IncompatibleVersion("0.1.0".to_owned()), false),
+            (PuppetError::Unsupported("message_send_quote".to_owned()), false),
+            (PuppetError::Deserialize("mini_program: EOF".to_owned()), false),
+            (PuppetError::NotFound("contact alice".to_owned()), false),
+            (PuppetError::UnknownPayloadType, false),
+            (PuppetError::UnknownMessageType, false),
+        ];
+        for (error, retryable) in cases {
+            assert_eq!(error.is_retryable(), retryable, "{}", error);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use actix::Message;
+use serde::{Deserialize, Serialize};
 
 use crate::schemas::event::*;
 // use crate::types::AsyncFnPtr;
@@ -19,9 +20,10 @@ use crate::schemas::event::*;
 // pub type PuppetRoomTopicListener = AsyncFnPtr<EventRoomTopicPayload, ()>;
 // pub type PuppetScanListener = AsyncFnPtr<EventScanPayload, ()>;
 
-#[derive(Debug, Clone, Message)]
+#[derive(Debug, Clone, Message, Serialize, Deserialize)]
 #[rtype("()")]
 pub enum PuppetEvent {
+    ConnectionChange(EventConnectionChangePayload),
     Dirty(EventDirtyPayload),
     Dong(EventDongPayload),
     Error(EventErrorPayload),
@@ -38,3 +40,90 @@ pub enum PuppetEvent {
     RoomTopic(EventRoomTopicPayload),
     Scan(EventScanPayload),
 }
+
+impl PuppetEvent {
+    pub fn event_name(&self) -> EventName {
+        match self {
+            PuppetEvent::ConnectionChange(_) => EventName::ConnectionChange,
+            PuppetEvent::Dirty(_) => EventName::Dirty,
+            PuppetEvent::Dong(_) => EventName::Dong,
+            PuppetEvent::Error(_) => EventName::Error,
+            PuppetEvent::Friendship(_) => EventName::Friendship,
+            PuppetEvent::Heartbeat(_) => EventName::Heartbeat,
+            PuppetEvent::Login(_) => EventName::Login,
+            PuppetEvent::Logout(_) => EventName::Logout,
+            PuppetEvent::Message(_) => EventName::Message,
+            PuppetEvent::Ready(_) => EventName::Ready,
+            PuppetEvent::Reset(_) => EventName::Reset,
+            PuppetEvent::RoomInvite(_) => EventName::RoomInvite,
+            PuppetEvent::RoomJoin(_) => EventName::RoomJoin,
+            PuppetEvent::RoomLeave(_) => EventName::RoomLeave,
+            PuppetEvent::RoomTopic(_) => EventName::RoomTopic,
+            PuppetEvent::Scan(_) => EventName::Scan,
+        }
+    }
+}
+
+/// Names of the events that can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventName {
+    ConnectionChange,
+    Dirty,
+    Dong,
+    Error,
+    Friendship,
+    Heartbeat,
+    Login,
+    Logout,
+    Message,
+    Ready,
+    Reset,
+    RoomInvite,
+    RoomJoin,
+    RoomLeave,
+    RoomTopic,
+    Scan,
+}
+
+impl EventName {
+    /// All the events, e.g. for unsubscribing from everything.
+    pub const ALL: [EventName; 16] = [
+        EventName::ConnectionChange,
+        EventName::Dirty,
+        EventName::Dong,
+        EventName::Error,
+        EventName::Friendship,
+        EventName::Heartbeat,
+        EventName::Login,
+        EventName::Logout,
+        EventName::Message,
+        EventName::Ready,
+        EventName::Reset,
+        EventName::RoomInvite,
+        EventName::RoomJoin,
+        EventName::RoomLeave,
+        EventName::RoomTopic,
+        EventName::Scan,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventName::ConnectionChange => "connection-change",
+            EventName::Dirty => "dirty",
+            EventName::Dong => "dong",
+            EventName::Error => "error",
+            EventName::Friendship => "friendship",
+            EventName::Heartbeat => "heartbeat",
+            EventName::Login => "login",
+            EventName::Logout => "logout",
+            EventName::Message => "message",
+            EventName::Ready => "ready",
+            EventName::Reset => "reset",
+            EventName::RoomInvite => "room-invite",
+            EventName::RoomJoin => "room-join",
+            EventName::RoomLeave => "room-leave",
+            EventName::RoomTopic => "room-topic",
+            EventName::Scan => "scan",
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Payloads for tests, with every field not given left empty.
+//!
+//! Adjust other fields with struct update syntax, e.g.
+//! `ContactPayload { alias: "Ally".to_owned(), ..fixtures::contact("alice", "Alice") }`.
+use crate::{
+    ContactGender, ContactPayload, ContactType, FriendshipPayload, FriendshipSceneType, FriendshipType, MessagePayload,
+    MessageType, RoomInvitationPayload, RoomMemberPayload, RoomPayload,
+};
+
+/// An individual who is a friend of the bot.
+pub fn contact(id: &str, name: &str) -> ContactPayload {
+    ContactPayload {
+        id: id.to_owned(),
+        gender: ContactGender::Unknown,
+        contact_type: ContactType::Individual,
+        name: name.to_owned(),
+        avatar: String::new(),
+        address: String::new(),
+        alias: String::new(),
+        city: String::new(),
+        friend: true,
+        province: String::new(),
+        signature: String::new(),
+        star: false,
+        weixin: String::new(),
+        corporation: String::new(),
+        title: String::new(),
+        description: String::new(),
+        coworker: false,
+        phone: vec![],
+    }
+}
+
+/// A text message, neither in a room nor sent to anyone in particular.
+pub fn message(id: &str, from_id: &str, text: &str) -> MessagePayload {
+    MessagePayload {
+        id: id.to_owned(),
+        filename: String::new(),
+        text: text.to_owned(),
+        timestamp: 0,
+        message_type: MessageType::Text,
+        from_id: from_id.to_owned(),
+        mention_id_list: vec![],
+        room_id: String::new(),
+        to_id: String::new(),
+    }
+}
+
+/// A room without an owner or admins.
+pub fn room(id: &str, topic: &str, member_id_list: &[&str]) -> RoomPayload {
+    RoomPayload {
+        id: id.to_owned(),
+        topic: topic.to_owned(),
+        avatar: String::new(),
+        member_id_list: member_id_list.iter().map(|id| id.to_string()).collect(),
+        owner_id: String::new(),
+        admin_id_list: vec![],
+    }
+}
+
+/// A received friend request that came with no greeting.
+pub fn friendship(id: &str, contact_id: &str) -> FriendshipPayload {
+    FriendshipPayload {
+        id: id.to_owned(),
+        contact_id: contact_id.to_owned(),
+        hello: String::new(),
+        timestamp: 0,
+        scene: FriendshipSceneType::Weixin,
+        stranger: String::new(),
+        ticket: String::new(),
+        friendship_type: FriendshipType::Receive,
+    }
+}
+
+/// A room member without a room alias.
+pub fn room_member(id: &str, name: &str) -> RoomMemberPayload {
+    RoomMemberPayload {
+        id: id.to_owned(),
+        room_alias: String::new(),
+        inviter_id: String::new(),
+        avatar: String::new(),
+        name: name.to_owned(),
+    }
+}
+
+/// An invitation to an empty room.
+pub fn room_invitation(id: &str, inviter_id: &str) -> RoomInvitationPayload {
+    RoomInvitationPayload {
+        id: id.to_owned(),
+        inviter_id: inviter_id.to_owned(),
+        topic: String::new(),
+        avatar: String::new(),
+        invitation: String::new(),
+        member_count: 0,
+        member_id_list: vec![],
+        timestamp: 0,
+        receiver_id: String::new(),
+    }
+}
@@ -1,24 +1,36 @@
 #[macro_use]
 extern crate num_derive;
 
+mod boxed_puppet;
 pub mod error;
 pub mod events;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures;
 pub mod puppet;
+mod recording;
 pub mod schemas;
+mod send_queue;
 pub mod types;
 
+pub use boxed_puppet::BoxedPuppet;
 pub use error::PuppetError;
-pub use events::PuppetEvent;
-pub use file_box::FileBox;
+pub use events::{EventName, PuppetEvent};
+pub use file_box::{FileBox, FileBoxSource};
 pub use puppet::{Puppet, PuppetImpl, Subscribe, UnSubscribe};
+pub use recording::{read_events, write_event, EventRecorder};
 pub use schemas::contact::*;
+pub use schemas::conversation::{ConversationId, DEFAULT_ROOM_ID_PATTERN};
 pub use schemas::event::*;
+pub use schemas::favorite::FavoritePayload;
 pub use schemas::friendship::*;
 pub use schemas::image::ImageType;
 pub use schemas::message::*;
 pub use schemas::mini_program::MiniProgramPayload;
+pub use schemas::money::{MoneyPayload, MoneyStatus};
 pub use schemas::payload::PayloadType;
-pub use schemas::puppet::PuppetOptions;
+pub use schemas::puppet::{
+    JsonCasing, PuppetCapabilities, PuppetFeature, PuppetOptions, StreamErrorAction, StreamErrorPolicy,
+};
 pub use schemas::room::*;
 pub use schemas::room_invitation::RoomInvitationPayload;
 pub use schemas::url_link::UrlLinkPayload;
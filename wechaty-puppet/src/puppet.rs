@@ -1,15 +1,25 @@
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use actix::dev::SendError;
 use actix::{Actor, Addr, Context, Handler, Message, Recipient};
 use async_trait::async_trait;
 use futures::StreamExt;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use lru::LruCache;
+use regex::Regex;
+use tokio::sync::oneshot;
 
+use crate::schemas::conversation::DEFAULT_ROOM_ID_REGEX;
+use crate::send_queue::SendQueue;
 use crate::{
-    ContactPayload, ContactQueryFilter, FileBox, FriendshipPayload, FriendshipSearchQueryFilter, ImageType,
-    MessagePayload, MessageQueryFilter, MessageType, MiniProgramPayload, PayloadType, PuppetError, PuppetEvent,
+    ContactPayload, ContactQueryFilter, ConversationId, EventName, FavoritePayload, FileBox, ForwardPath,
+    FriendshipPayload, FriendshipSearchQueryFilter, ImageType, MessageCategory, MessagePayload, MessageQueryFilter,
+    MessageType, MiniProgramPayload, MoneyPayload, PayloadType, PuppetCapabilities, PuppetError, PuppetEvent,
     RoomInvitationPayload, RoomMemberPayload, RoomMemberQueryFilter, RoomPayload, RoomQueryFilter, UrlLinkPayload,
 };
 
@@ -19,8 +29,35 @@ const DEFAULT_MESSAGE_CACHE_CAP: usize = 500;
 const DEFAULT_ROOM_CACHE_CAP: usize = 500;
 const DEFAULT_ROOM_MEMBER_CACHE_CAP: usize = 30000;
 const DEFAULT_ROOM_INVITATION_CACHE_CAP: usize = 100;
+const DEFAULT_BATCH_SIZE: usize = 16;
+const BATCH_SIZE_ENV: &str = "WECHATY_BATCH_SIZE";
 
 type LruCachePtr<T> = Arc<Mutex<LruCache<String, T>>>;
+type FatalErrorSenderPtr = Arc<Mutex<Option<oneshot::Sender<PuppetError>>>>;
+type FatalErrorReceiverPtr = Arc<Mutex<Option<oneshot::Receiver<PuppetError>>>>;
+
+/// Read the batch size from the `WECHATY_BATCH_SIZE` environment variable, see `parse_batch_size`.
+fn batch_size_from_env() -> usize {
+    parse_batch_size(env::var(BATCH_SIZE_ENV).ok().as_deref())
+}
+
+/// Parse the value of `WECHATY_BATCH_SIZE`, fall back to the default batch size if it is not set or is not a
+/// positive integer.
+fn parse_batch_size(value: Option<&str>) -> usize {
+    match value {
+        Some(value) => match value.parse::<usize>() {
+            Ok(batch_size) if batch_size > 0 => batch_size,
+            _ => {
+                warn!(
+                    "Invalid {} {:?}, fall back to {}",
+                    BATCH_SIZE_ENV, value, DEFAULT_BATCH_SIZE
+                );
+                DEFAULT_BATCH_SIZE
+            }
+        },
+        None => DEFAULT_BATCH_SIZE,
+    }
+}
 
 #[derive(Clone)]
 pub struct Puppet<T>
@@ -35,6 +72,13 @@ where
     cache_room_payload: LruCachePtr<RoomPayload>,
     cache_room_member_payload: LruCachePtr<RoomMemberPayload>,
     cache_room_invitation_payload: LruCachePtr<RoomInvitationPayload>,
+    send_queue: Option<SendQueue>,
+    batch_size: usize,
+    dry_run: bool,
+    dry_run_counter: Arc<AtomicUsize>,
+    fatal_error_sender: FatalErrorSenderPtr,
+    fatal_error_receiver: FatalErrorReceiverPtr,
+    room_id_pattern: Regex,
     id: Option<String>,
 }
 
@@ -45,18 +89,27 @@ type SubscribersPtr = Arc<Mutex<HashMap<String, Recipient<PuppetEvent>>>>;
 pub struct Subscribe {
     pub addr: Recipient<PuppetEvent>,
     pub name: String,
-    pub event_name: &'static str,
+    pub event_name: EventName,
 }
 
 #[derive(Message)]
 #[rtype("()")]
 pub struct UnSubscribe {
     pub name: String,
-    pub event_name: &'static str,
+    pub event_name: EventName,
+}
+
+/// Get the names of the subscribers of an event.
+#[derive(Message)]
+#[rtype("Vec<String>")]
+struct Subscribers {
+    event_name: EventName,
 }
 
 #[derive(Clone)]
 struct PuppetInner {
+    connection_change_subscribers: SubscribersPtr,
+    dirty_subscribers: SubscribersPtr,
     dong_subscribers: SubscribersPtr,
     error_subscribers: SubscribersPtr,
     friendship_subscribers: SubscribersPtr,
@@ -76,6 +129,8 @@ struct PuppetInner {
 impl PuppetInner {
     fn new() -> Self {
         Self {
+            connection_change_subscribers: Arc::new(Mutex::new(HashMap::new())),
+            dirty_subscribers: Arc::new(Mutex::new(HashMap::new())),
             dong_subscribers: Arc::new(Mutex::new(HashMap::new())),
             error_subscribers: Arc::new(Mutex::new(HashMap::new())),
             friendship_subscribers: Arc::new(Mutex::new(HashMap::new())),
@@ -93,10 +148,42 @@ impl PuppetInner {
         }
     }
 
+    fn subscribers(&self, event_name: EventName) -> SubscribersPtr {
+        match event_name {
+            EventName::ConnectionChange => self.connection_change_subscribers.clone(),
+            EventName::Dirty => self.dirty_subscribers.clone(),
+            EventName::Dong => self.dong_subscribers.clone(),
+            EventName::Error => self.error_subscribers.clone(),
+            EventName::Friendship => self.friendship_subscribers.clone(),
+            EventName::Heartbeat => self.heartbeat_subscribers.clone(),
+            EventName::Login => self.login_subscribers.clone(),
+            EventName::Logout => self.logout_subscribers.clone(),
+            EventName::Message => self.message_subscribers.clone(),
+            EventName::Ready => self.ready_subscribers.clone(),
+            EventName::Reset => self.reset_subscribers.clone(),
+            EventName::RoomInvite => self.room_invite_subscribers.clone(),
+            EventName::RoomJoin => self.room_join_subscribers.clone(),
+            EventName::RoomLeave => self.room_leave_subscribers.clone(),
+            EventName::RoomTopic => self.room_topic_subscribers.clone(),
+            EventName::Scan => self.scan_subscribers.clone(),
+        }
+    }
+
+    /// Send an event to its subscribers, pruning the ones whose actor has stopped so that they are not notified
+    /// again. A stopped listener has to subscribe again to receive events.
     fn notify(&self, msg: PuppetEvent, subscribers: SubscribersPtr) {
-        for (name, subscriber) in subscribers.lock().unwrap().clone() {
-            if let Err(e) = subscriber.do_send(msg.clone()) {
-                error!("Failed to notify {} : {}", name, e);
+        let subscriber_list = subscribers.lock().unwrap().clone();
+        if subscriber_list.is_empty() {
+            debug!("No subscriber for event {}, dropping it", msg.event_name().as_str());
+        }
+        for (name, subscriber) in subscriber_list {
+            match subscriber.do_send(msg.clone()) {
+                Ok(_) => {}
+                Err(SendError::Closed(_)) => {
+                    warn!("Subscriber {} has stopped, unsubscribing it", name);
+                    subscribers.lock().unwrap().remove(&name);
+                }
+                Err(e) => error!("Failed to notify {} : {}", name, e),
             }
         }
     }
@@ -118,54 +205,11 @@ impl Handler<Subscribe> for PuppetInner {
     type Result = ();
 
     fn handle(&mut self, msg: Subscribe, _ctx: &mut Self::Context) -> Self::Result {
-        info!("{} is trying to subscribe to {}", msg.name, msg.event_name);
-        match msg.event_name {
-            "dong" => {
-                self.dong_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "error" => {
-                self.error_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "friendship" => {
-                self.friendship_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "heartbeat" => {
-                self.heartbeat_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "login" => {
-                self.login_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "logout" => {
-                self.logout_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "message" => {
-                self.message_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "ready" => {
-                self.ready_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "reset" => {
-                self.reset_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "room-invite" => {
-                self.room_invite_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "room-join" => {
-                self.room_join_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "room-leave" => {
-                self.room_leave_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "room-topic" => {
-                self.room_topic_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            "scan" => {
-                self.scan_subscribers.lock().unwrap().insert(msg.name, msg.addr);
-            }
-            _ => {
-                error!("Trying to subscribe to unknown event: {}", msg.name);
-            }
-        }
+        info!("{} is trying to subscribe to {}", msg.name, msg.event_name.as_str());
+        self.subscribers(msg.event_name)
+            .lock()
+            .unwrap()
+            .insert(msg.name, msg.addr);
     }
 }
 
@@ -173,54 +217,24 @@ impl Handler<UnSubscribe> for PuppetInner {
     type Result = ();
 
     fn handle(&mut self, msg: UnSubscribe, _ctx: &mut Self::Context) -> Self::Result {
-        info!("{} is trying to unsubscribe from {}", msg.name, msg.event_name);
-        match msg.event_name {
-            "dong" => {
-                self.dong_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "error" => {
-                self.error_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "friendship" => {
-                self.friendship_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "heartbeat" => {
-                self.heartbeat_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "login" => {
-                self.login_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "logout" => {
-                self.logout_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "message" => {
-                self.message_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "ready" => {
-                self.ready_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "reset" => {
-                self.reset_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "room-invite" => {
-                self.room_invite_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "room-join" => {
-                self.room_join_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "room-leave" => {
-                self.room_leave_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "room-topic" => {
-                self.room_topic_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            "scan" => {
-                self.scan_subscribers.lock().unwrap().remove(&msg.name);
-            }
-            _ => {
-                error!("Trying to unsubscribe from unknown event: {}", msg.name);
-            }
-        }
+        info!("{} is trying to unsubscribe from {}", msg.name, msg.event_name.as_str());
+        self.subscribers(msg.event_name).lock().unwrap().remove(&msg.name);
+    }
+}
+
+impl Handler<Subscribers> for PuppetInner {
+    type Result = Vec<String>;
+
+    fn handle(&mut self, msg: Subscribers, _ctx: &mut Self::Context) -> Self::Result {
+        let mut names: Vec<String> = self
+            .subscribers(msg.event_name)
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
     }
 }
 
@@ -228,23 +242,8 @@ impl Handler<PuppetEvent> for PuppetInner {
     type Result = ();
 
     fn handle(&mut self, msg: PuppetEvent, _ctx: &mut Self::Context) -> Self::Result {
-        match msg {
-            PuppetEvent::Dong(_) => self.notify(msg, self.dong_subscribers.clone()),
-            PuppetEvent::Error(_) => self.notify(msg, self.error_subscribers.clone()),
-            PuppetEvent::Friendship(_) => self.notify(msg, self.friendship_subscribers.clone()),
-            PuppetEvent::Heartbeat(_) => self.notify(msg, self.heartbeat_subscribers.clone()),
-            PuppetEvent::Login(_) => self.notify(msg, self.login_subscribers.clone()),
-            PuppetEvent::Logout(_) => self.notify(msg, self.logout_subscribers.clone()),
-            PuppetEvent::Message(_) => self.notify(msg, self.message_subscribers.clone()),
-            PuppetEvent::Ready(_) => self.notify(msg, self.ready_subscribers.clone()),
-            PuppetEvent::Reset(_) => self.notify(msg, self.reset_subscribers.clone()),
-            PuppetEvent::RoomInvite(_) => self.notify(msg, self.room_invite_subscribers.clone()),
-            PuppetEvent::RoomJoin(_) => self.notify(msg, self.room_join_subscribers.clone()),
-            PuppetEvent::RoomLeave(_) => self.notify(msg, self.room_leave_subscribers.clone()),
-            PuppetEvent::RoomTopic(_) => self.notify(msg, self.room_topic_subscribers.clone()),
-            PuppetEvent::Scan(_) => self.notify(msg, self.scan_subscribers.clone()),
-            _ => {}
-        }
+        let subscribers = self.subscribers(msg.event_name());
+        self.notify(msg, subscribers);
     }
 }
 
@@ -254,6 +253,7 @@ where
 {
     pub fn new(puppet_impl: T) -> Self {
         let addr = PuppetInner::new().start();
+        let (fatal_error_sender, fatal_error_receiver) = oneshot::channel();
 
         Self {
             puppet_impl,
@@ -264,10 +264,105 @@ where
             cache_room_payload: Arc::new(Mutex::new(LruCache::new(DEFAULT_ROOM_CACHE_CAP))),
             cache_room_member_payload: Arc::new(Mutex::new(LruCache::new(DEFAULT_ROOM_MEMBER_CACHE_CAP))),
             cache_room_invitation_payload: Arc::new(Mutex::new(LruCache::new(DEFAULT_ROOM_INVITATION_CACHE_CAP))),
+            send_queue: None,
+            batch_size: batch_size_from_env(),
+            dry_run: false,
+            dry_run_counter: Arc::new(AtomicUsize::new(0)),
+            fatal_error_sender: Arc::new(Mutex::new(Some(fatal_error_sender))),
+            fatal_error_receiver: Arc::new(Mutex::new(Some(fatal_error_receiver))),
+            room_id_pattern: DEFAULT_ROOM_ID_REGEX.clone(),
             id: None,
         }
     }
 
+    /// Deliver messages sent to the same conversation in the order they are sent, even if they are sent
+    /// concurrently. Messages sent to different conversations are still delivered in parallel.
+    pub fn with_ordered_delivery(mut self, enabled: bool) -> Self {
+        debug!("with_ordered_delivery(enabled = {})", enabled);
+        self.send_queue = if enabled { Some(SendQueue::default()) } else { None };
+        self
+    }
+
+    /// Number of messages waiting or being sent in the ordered delivery queue, which is always 0 without ordered
+    /// delivery, see `Puppet::with_ordered_delivery`.
+    pub fn send_queue_depth(&self) -> usize {
+        match &self.send_queue {
+            Some(send_queue) => send_queue.depth(),
+            None => 0,
+        }
+    }
+
+    /// Log mutating calls and return a synthetic success instead of calling the puppet implementation.
+    ///
+    /// Reading calls are not affected, so a bot can be tested against production data without side effects.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        debug!("with_dry_run(enabled = {})", enabled);
+        self.dry_run = enabled;
+        self
+    }
+
+    fn dry_run_id(&self, prefix: &str) -> String {
+        format!(
+            "dry-run-{}-{}",
+            prefix,
+            self.dry_run_counter.fetch_add(1, Ordering::SeqCst) + 1
+        )
+    }
+
+    async fn send<F>(&self, conversation_id: String, task: F) -> Result<Option<String>, PuppetError>
+    where
+        F: Future<Output = Result<Option<String>, PuppetError>>,
+    {
+        if self.dry_run {
+            info!("Dry run: send message to conversation {}", conversation_id);
+            return Ok(Some(self.dry_run_id("message")));
+        }
+        match &self.send_queue {
+            Some(send_queue) => send_queue.run(conversation_id, task).await,
+            None => task.await,
+        }
+    }
+
+    /// Set the pattern of room ids used to classify conversation ids, see `DEFAULT_ROOM_ID_PATTERN`.
+    pub fn with_room_id_pattern(mut self, room_id_pattern: Regex) -> Self {
+        debug!("with_room_id_pattern(room_id_pattern = {})", room_id_pattern);
+        self.room_id_pattern = room_id_pattern;
+        self
+    }
+
+    /// Classify an id as a room or a contact with the room id pattern of this puppet.
+    pub fn parse_conversation_id(&self, id: &str) -> Option<ConversationId> {
+        ConversationId::parse_with(id, &self.room_id_pattern)
+    }
+
+    /// Set the max number of payloads loaded concurrently by the batch loaders, which overrides `WECHATY_BATCH_SIZE`.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        debug!("with_batch_size(batch_size = {})", batch_size);
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Max number of payloads loaded concurrently by the batch loaders.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Take the sender used by the puppet implementation to report an unrecoverable error.
+    ///
+    /// Return `None` if it has already been taken.
+    pub fn fatal_error_sender(&self) -> Option<oneshot::Sender<PuppetError>> {
+        debug!("fatal_error_sender()");
+        self.fatal_error_sender.lock().unwrap().take()
+    }
+
+    /// Take the receiver of the unrecoverable error, the application can await it and shut down the bot.
+    ///
+    /// Return `None` if it has already been taken.
+    pub fn fatal_error(&self) -> Option<oneshot::Receiver<PuppetError>> {
+        debug!("fatal_error()");
+        self.fatal_error_receiver.lock().unwrap().take()
+    }
+
     pub fn self_addr(&self) -> Recipient<PuppetEvent> {
         debug!("self_addr()");
         self.addr.clone().recipient()
@@ -283,6 +378,12 @@ where
         self.addr.clone().recipient()
     }
 
+    /// Get the names of the listeners subscribing to an event, sorted.
+    pub async fn subscriber_names(&self, event_name: EventName) -> Vec<String> {
+        debug!("subscriber_names(event_name = {})", event_name.as_str());
+        self.addr.send(Subscribers { event_name }).await.unwrap_or_default()
+    }
+
     pub fn self_id(self) -> Option<String> {
         debug!("self_id()");
         self.id
@@ -314,7 +415,7 @@ where
         }
     }
 
-    /// Batch load contacts with a default batch size of 16.
+    /// Batch load contacts with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     ///
     /// A key point here is that the method called in stream::iter(...).map() cannot hold &mut self.
     ///
@@ -328,7 +429,7 @@ where
         let mut contact_list = vec![];
         let mut stream = tokio_stream::iter(contact_id_list)
             .map(|contact_id| self.contact_payload(contact_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.batch_size);
         while let Some(result) = stream.next().await {
             if let Ok(contact) = result {
                 contact_list.push(contact);
@@ -341,7 +442,7 @@ where
     ///
     /// Return all contacts that has an alias or name that matches the query string.
     pub async fn contact_search_by_string(
-        &mut self,
+        &self,
         query_str: String,
         search_id_list: Option<Vec<String>>,
     ) -> Result<Vec<String>, PuppetError> {
@@ -351,9 +452,13 @@ where
                 ContactQueryFilter {
                     alias: None,
                     alias_regex: None,
+                    city: None,
+                    city_regex: None,
                     id: Some(query_str.clone()),
                     name: None,
                     name_regex: None,
+                    province: None,
+                    province_regex: None,
                     weixin: None,
                 },
                 search_id_list.clone(),
@@ -364,9 +469,13 @@ where
                 ContactQueryFilter {
                     alias: Some(query_str.clone()),
                     alias_regex: None,
+                    city: None,
+                    city_regex: None,
                     id: None,
                     name: None,
                     name_regex: None,
+                    province: None,
+                    province_regex: None,
                     weixin: None,
                 },
                 search_id_list,
@@ -392,7 +501,7 @@ where
 
     /// Search contacts by query.
     pub async fn contact_search(
-        &mut self,
+        &self,
         query: ContactQueryFilter,
         contact_id_list: Option<Vec<String>>,
     ) -> Result<Vec<String>, PuppetError> {
@@ -456,6 +565,26 @@ where
                     return false;
                 }
             }
+            if let Some(city) = query.city {
+                if payload.city != city {
+                    return false;
+                }
+            }
+            if let Some(city_regex) = query.city_regex {
+                if !city_regex.is_match(&payload.city) {
+                    return false;
+                }
+            }
+            if let Some(province) = query.province {
+                if payload.province != province {
+                    return false;
+                }
+            }
+            if let Some(province_regex) = query.province_regex {
+                if !province_regex.is_match(&payload.province) {
+                    return false;
+                }
+            }
             true
         }
     }
@@ -481,14 +610,14 @@ where
         }
     }
 
-    /// Batch load messages with a default batch size of 16.
+    /// Batch load messages with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     #[allow(dead_code)]
-    async fn message_payload_batch(&mut self, message_id_list: Vec<String>) -> Vec<MessagePayload> {
+    async fn message_payload_batch(&self, message_id_list: Vec<String>) -> Vec<MessagePayload> {
         debug!("message_payload_batch(message_id_list = {:?})", message_id_list);
         let mut message_list = vec![];
         let mut stream = tokio_stream::iter(message_id_list)
             .map(|message_id| self.message_payload(message_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.batch_size);
         while let Some(result) = stream.next().await {
             if let Ok(message) = result {
                 message_list.push(message);
@@ -507,7 +636,7 @@ where
         message_id_list
     }
 
-    pub async fn message_search(&mut self, query: MessageQueryFilter) -> Result<Vec<String>, PuppetError> {
+    pub async fn message_search(&self, query: MessageQueryFilter) -> Result<Vec<String>, PuppetError> {
         debug!("message_search(query = {:?})", query);
 
         let message_id_list = self.message_list();
@@ -571,8 +700,9 @@ where
         }
     }
 
+    /// Forward a message to a conversation, see `message_forward_with_path`.
     pub async fn message_forward(
-        &mut self,
+        &self,
         conversation_id: String,
         message_id: String,
     ) -> Result<Option<String>, PuppetError> {
@@ -580,51 +710,85 @@ where
             "message_forward(conversation_id = {}, message_id = {})",
             conversation_id, message_id
         );
+        match self.message_forward_with_path(conversation_id, message_id).await {
+            Ok((message_id, _)) => Ok(message_id),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Forward a message to a conversation, return the id of the new message and how it was forwarded.
+    ///
+    /// The gateway forwards the message natively if it can, otherwise the message is re-sent from its content,
+    /// which loses the forwarded marker and only works for the message types that can be sent.
+    pub async fn message_forward_with_path(
+        &self,
+        conversation_id: String,
+        message_id: String,
+    ) -> Result<(Option<String>, ForwardPath), PuppetError> {
+        debug!(
+            "message_forward_with_path(conversation_id = {}, message_id = {})",
+            conversation_id, message_id
+        );
+        match self
+            .message_forward_native(conversation_id.clone(), message_id.clone())
+            .await
+        {
+            Ok(message_id) => Ok((message_id, ForwardPath::Native)),
+            Err(PuppetError::Unsupported(_)) => {
+                debug!("Native forwarding is unsupported, re-sending message {}", message_id);
+                match self.message_forward_reconstructed(conversation_id, message_id).await {
+                    Ok(message_id) => Ok((message_id, ForwardPath::Reconstructed)),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Re-send a message from its content.
+    async fn message_forward_reconstructed(
+        &self,
+        conversation_id: String,
+        message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
         let payload = self.message_payload(message_id.clone()).await;
         match payload {
-            Ok(payload) => match payload.message_type {
-                MessageType::Attachment | MessageType::Audio | MessageType::Image | MessageType::Video => {
-                    match self.puppet_impl.message_file(message_id).await {
-                        Ok(file) => self.puppet_impl.message_send_file(conversation_id, file).await,
-                        Err(e) => Err(e),
-                    }
-                }
-                MessageType::Text => {
-                    self.puppet_impl
-                        .message_send_text(conversation_id, payload.text, Vec::new())
-                        .await
-                }
-                MessageType::MiniProgram => match self.puppet_impl.message_mini_program(message_id).await {
-                    Ok(mini_program_payload) => {
-                        self.puppet_impl
-                            .message_send_mini_program(conversation_id, mini_program_payload)
-                            .await
-                    }
+            Ok(payload) => match payload.message_type.category() {
+                MessageCategory::Text => self.message_send_text(conversation_id, payload.text, Vec::new()).await,
+                // Re-sending an emoticon as a file would turn the sticker into an image attachment.
+                MessageCategory::Media if payload.message_type == MessageType::Emoticon => Err(
+                    PuppetError::Unsupported(format!("sending {:?} messages", payload.message_type)),
+                ),
+                MessageCategory::Media => match self.puppet_impl.message_file(message_id).await {
+                    Ok(file) => self.message_send_file(conversation_id, file).await,
                     Err(e) => Err(e),
                 },
-                MessageType::Url => match self.puppet_impl.message_url(message_id).await {
-                    Ok(url_link_payload) => {
-                        self.puppet_impl
-                            .message_send_url(conversation_id, url_link_payload)
-                            .await
-                    }
-                    Err(e) => Err(e),
-                },
-                MessageType::Contact => match self.puppet_impl.message_contact(message_id).await {
-                    Ok(contact_id) => self.puppet_impl.message_send_contact(conversation_id, contact_id).await,
-                    Err(e) => Err(e),
+                MessageCategory::Rich => match payload.message_type {
+                    MessageType::MiniProgram => match self.puppet_impl.message_mini_program(message_id).await {
+                        Ok(mini_program_payload) => {
+                            self.message_send_mini_program(conversation_id, mini_program_payload)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    },
+                    MessageType::Url => match self.puppet_impl.message_url(message_id).await {
+                        Ok(url_link_payload) => self.message_send_url(conversation_id, url_link_payload).await,
+                        Err(e) => Err(e),
+                    },
+                    MessageType::Contact => match self.puppet_impl.message_contact(message_id).await {
+                        Ok(contact_id) => self.message_send_contact(conversation_id, contact_id).await,
+                        Err(e) => Err(e),
+                    },
+                    _ => Err(PuppetError::Unsupported(format!(
+                        "sending {:?} messages",
+                        payload.message_type
+                    ))),
                 },
-                MessageType::ChatHistory
-                | MessageType::Location
-                | MessageType::Emoticon
-                | MessageType::GroupNote
-                | MessageType::Transfer
-                | MessageType::RedEnvelope
-                | MessageType::Recalled => Err(PuppetError::Unsupported(format!(
+                MessageCategory::System | MessageCategory::Money => Err(PuppetError::Unsupported(format!(
                     "sending {:?} messages",
                     payload.message_type
                 ))),
-                MessageType::Unknown => Err(PuppetError::UnknownMessageType),
+                MessageCategory::Unknown => Err(PuppetError::UnknownMessageType),
             },
             Err(e) => Err(e),
         }
@@ -634,19 +798,17 @@ where
         Friendship
     */
 
-    /// Search friendship.
+    /// Search a contact by phone if given, otherwise by weixin, empty values are regarded as not given.
     ///
-    /// First search by phone, then search by weixin.
-    pub async fn friendship_search(
-        &mut self,
-        query: FriendshipSearchQueryFilter,
-    ) -> Result<Option<String>, PuppetError> {
-        if let Some(phone) = query.phone {
-            self.friendship_search_phone(phone).await
-        } else if let Some(weixin) = query.weixin {
-            self.friendship_search_weixin(weixin).await
-        } else {
-            Ok(None)
+    /// Return `Ok(None)` if nobody is found, and `PuppetError::InvalidConfig` if neither phone nor weixin is given.
+    pub async fn friendship_search(&self, query: FriendshipSearchQueryFilter) -> Result<Option<String>, PuppetError> {
+        debug!("friendship_search(query = {:?})", query);
+        match (query.phone, query.weixin) {
+            (Some(phone), _) if !phone.is_empty() => self.friendship_search_phone(phone).await,
+            (_, Some(weixin)) if !weixin.is_empty() => self.friendship_search_weixin(weixin).await,
+            _ => Err(PuppetError::InvalidConfig(
+                "Must specify either phone or weixin".to_owned(),
+            )),
         }
     }
 
@@ -667,9 +829,9 @@ where
         }
     }
 
-    /// Batch load friendships with a default batch size of 16.
+    /// Batch load friendships with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     #[allow(dead_code)]
-    async fn friendship_payload_batch(&mut self, friendship_id_list: Vec<String>) -> Vec<FriendshipPayload> {
+    async fn friendship_payload_batch(&self, friendship_id_list: Vec<String>) -> Vec<FriendshipPayload> {
         debug!(
             "friendship_payload_batch(friendship_id_list = {:?})",
             friendship_id_list
@@ -677,7 +839,7 @@ where
         let mut friendship_list = vec![];
         let mut stream = tokio_stream::iter(friendship_id_list)
             .map(|friendship_id| self.friendship_payload(friendship_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.batch_size);
         while let Some(result) = stream.next().await {
             if let Ok(friendship) = result {
                 friendship_list.push(friendship);
@@ -688,7 +850,7 @@ where
 
     /// Friendship payload setter.
     pub async fn friendship_payload_set(
-        &mut self,
+        &self,
         friendship_id: String,
         new_payload: FriendshipPayload,
     ) -> Result<(), PuppetError> {
@@ -731,12 +893,9 @@ where
         }
     }
 
-    /// Batch load room invitations with a default batch size of 16.
+    /// Batch load room invitations with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     #[allow(dead_code)]
-    async fn room_invitation_payload_batch(
-        &mut self,
-        room_invitation_id_list: Vec<String>,
-    ) -> Vec<RoomInvitationPayload> {
+    async fn room_invitation_payload_batch(&self, room_invitation_id_list: Vec<String>) -> Vec<RoomInvitationPayload> {
         debug!(
             "room_invitation_payload_batch(room_invitation_id_list = {:?})",
             room_invitation_id_list
@@ -744,7 +903,7 @@ where
         let mut room_invitation_list = vec![];
         let mut stream = tokio_stream::iter(room_invitation_id_list)
             .map(|room_invitation_id| self.room_invitation_payload(room_invitation_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.batch_size);
         while let Some(result) = stream.next().await {
             if let Ok(room_invitation) = result {
                 room_invitation_list.push(room_invitation);
@@ -755,7 +914,7 @@ where
 
     /// Room invitation payload setter.
     pub async fn room_invitation_payload_set(
-        &mut self,
+        &self,
         room_invitation_id: String,
         new_payload: RoomInvitationPayload,
     ) -> Result<(), PuppetError> {
@@ -791,13 +950,13 @@ where
         }
     }
 
-    /// Batch load rooms with a default batch size of 16.
-    async fn room_payload_batch(&mut self, room_id_list: Vec<String>) -> Vec<RoomPayload> {
+    /// Batch load rooms with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
+    async fn room_payload_batch(&self, room_id_list: Vec<String>) -> Vec<RoomPayload> {
         debug!("room_payload_batch(room_id_list = {:?})", room_id_list);
         let mut room_list = vec![];
         let mut stream = tokio_stream::iter(room_id_list)
             .map(|room_id| self.room_payload(room_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.batch_size);
         while let Some(result) = stream.next().await {
             if let Ok(room) = result {
                 room_list.push(room);
@@ -813,7 +972,7 @@ where
 
     /// Search room members by string.
     pub async fn room_member_search_by_string(
-        &mut self,
+        &self,
         room_id: String,
         query_str: String,
     ) -> Result<Vec<String>, PuppetError> {
@@ -862,7 +1021,7 @@ where
     ///
     /// Currently, searching by contact alias is not supported.
     pub async fn room_member_search(
-        &mut self,
+        &self,
         room_id: String,
         query: RoomMemberQueryFilter,
     ) -> Result<Vec<String>, PuppetError> {
@@ -917,21 +1076,55 @@ where
         }
     }
 
-    /// Batch load room members with a default batch size of 16.
+    /// Batch load room members, members that fail to load are left out.
     async fn room_member_payload_batch(&self, room_id: String, member_id_list: Vec<String>) -> Vec<RoomMemberPayload> {
         debug!(
             "room_member_payload_batch(room_id = {}, member_id_list = {:?})",
             room_id, member_id_list
         );
+        match self.room_member_payload_list(room_id.clone(), member_id_list).await {
+            Ok(member_list) => member_list,
+            Err(e) => {
+                error!("Failed to load members of room {}, reason: {}", room_id, e);
+                vec![]
+            }
+        }
+    }
+
+    /// Load room members one by one, `batch_size` at a time, for puppets without a bulk RPC.
+    ///
+    /// Members that fail to load are left out and logged.
+    async fn room_member_raw_payload_each(
+        &self,
+        room_id: String,
+        member_id_list: Vec<String>,
+    ) -> Vec<RoomMemberPayload> {
         let mut member_list = vec![];
+        let mut failed_id_list = vec![];
         let mut stream = tokio_stream::iter(member_id_list)
-            .map(|member_id| self.room_member_payload(room_id.clone(), member_id))
-            .buffer_unordered(16);
-        while let Some(result) = stream.next().await {
-            if let Ok(member) = result {
-                member_list.push(member);
+            .map(|member_id| {
+                let room_id = room_id.clone();
+                async move {
+                    let result = self
+                        .puppet_impl
+                        .room_member_raw_payload(room_id, member_id.clone())
+                        .await;
+                    (member_id, result)
+                }
+            })
+            .buffer_unordered(self.batch_size);
+        while let Some((member_id, result)) = stream.next().await {
+            match result {
+                Ok(member) => member_list.push(member),
+                Err(e) => {
+                    debug!("Failed to load member {} of room {}, reason: {}", member_id, room_id, e);
+                    failed_id_list.push(member_id);
+                }
             }
         }
+        if !failed_id_list.is_empty() {
+            warn!("Failed to load members {:?} of room {}", failed_id_list, room_id);
+        }
         member_list
     }
 
@@ -961,11 +1154,30 @@ where
         }
     }
 
-    pub async fn room_search(&mut self, query: RoomQueryFilter) -> Result<Vec<String>, PuppetError> {
+    /// Room member payload setter.
+    pub async fn room_member_payload_set(
+        &self,
+        room_id: String,
+        member_id: String,
+        new_payload: RoomMemberPayload,
+    ) -> Result<(), PuppetError> {
+        debug!(
+            "room_member_payload_set(room_id = {}, member_id = {}, new_payload = {:?})",
+            room_id, member_id, new_payload
+        );
+        let cache_key = Puppet::<T>::cache_key_room_member(room_id, member_id);
+        (*self.cache_room_member_payload)
+            .lock()
+            .unwrap()
+            .put(cache_key, new_payload);
+        Ok(())
+    }
+
+    pub async fn room_search(&self, query: RoomQueryFilter) -> Result<Vec<String>, PuppetError> {
         debug!("room_search(query = {:?})", query);
         let room_id_list = match self.puppet_impl.room_list().await {
             Ok(room_id_list) => room_id_list,
-            _ => Vec::new(),
+            Err(e) => return Err(e),
         };
         debug!("room_search(room_id_list.len() = {})", room_id_list.len());
 
@@ -1011,25 +1223,25 @@ where
        Dirty payload
     */
 
-    async fn dirty_payload_message(&mut self, message_id: String) -> Result<(), PuppetError> {
+    async fn dirty_payload_message(&self, message_id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload_message(message_id = {})", message_id);
         (*self.cache_message_payload).lock().unwrap().pop(&message_id);
         Ok(())
     }
 
-    async fn dirty_payload_contact(&mut self, contact_id: String) -> Result<(), PuppetError> {
+    async fn dirty_payload_contact(&self, contact_id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload_contact(contact_id = {})", contact_id);
         (*self.cache_contact_payload).lock().unwrap().pop(&contact_id);
         Ok(())
     }
 
-    async fn dirty_payload_room(&mut self, room_id: String) -> Result<(), PuppetError> {
+    async fn dirty_payload_room(&self, room_id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload_room(room_id = {})", room_id);
-        (*self.cache_contact_payload).lock().unwrap().pop(&room_id);
+        (*self.cache_room_payload).lock().unwrap().pop(&room_id);
         Ok(())
     }
 
-    async fn dirty_payload_room_member(&mut self, room_id: String) -> Result<(), PuppetError> {
+    async fn dirty_payload_room_member(&self, room_id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload_room_member(room_id = {})", room_id);
 
         match self.puppet_impl.room_member_list(room_id.clone()).await {
@@ -1044,13 +1256,73 @@ where
         }
     }
 
-    async fn dirty_payload_friendship(&mut self, friendship_id: String) -> Result<(), PuppetError> {
+    async fn dirty_payload_friendship(&self, friendship_id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload_friendship(friendship_id = {})", friendship_id);
         (*self.cache_friendship_payload).lock().unwrap().pop(&friendship_id);
         Ok(())
     }
 
-    pub async fn dirty_payload(&mut self, payload_type: PayloadType, id: String) -> Result<(), PuppetError> {
+    /// Empty every payload cache, so that all payloads are fetched from the puppet again, e.g. after the caches are
+    /// known to be inconsistent.
+    pub fn clear_all_caches(&self) {
+        debug!("clear_all_caches()");
+        self.cache_contact_payload.lock().unwrap().clear();
+        self.cache_friendship_payload.lock().unwrap().clear();
+        self.cache_message_payload.lock().unwrap().clear();
+        self.cache_room_payload.lock().unwrap().clear();
+        self.cache_room_member_payload.lock().unwrap().clear();
+        self.cache_room_invitation_payload.lock().unwrap().clear();
+    }
+
+    /// Clone the payloads in a cache from the most to the least recently used, holding the lock only while cloning.
+    fn snapshot<Payload: Clone>(cache: &LruCachePtr<Payload>) -> Vec<Payload> {
+        cache
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+
+    /// Snapshot the cached message payloads without fetching anything.
+    pub fn cached_messages(&self) -> Vec<MessagePayload> {
+        debug!("cached_messages()");
+        Self::snapshot(&self.cache_message_payload)
+    }
+
+    /// Snapshot the cached contact payloads without fetching anything.
+    pub fn cached_contacts(&self) -> Vec<ContactPayload> {
+        debug!("cached_contacts()");
+        Self::snapshot(&self.cache_contact_payload)
+    }
+
+    /// Snapshot the cached room payloads without fetching anything.
+    pub fn cached_rooms(&self) -> Vec<RoomPayload> {
+        debug!("cached_rooms()");
+        Self::snapshot(&self.cache_room_payload)
+    }
+
+    /// Check whether a payload is in the cache, for room members `id` is the room id.
+    pub fn is_payload_cached(&self, payload_type: PayloadType, id: String) -> bool {
+        debug!("is_payload_cached(payload_type = {:?}, id = {})", payload_type, id);
+        match payload_type {
+            PayloadType::Message => self.cache_message_payload.lock().unwrap().contains(&id),
+            PayloadType::Contact => self.cache_contact_payload.lock().unwrap().contains(&id),
+            PayloadType::Room => self.cache_room_payload.lock().unwrap().contains(&id),
+            PayloadType::RoomMember => {
+                let suffix = Puppet::<T>::cache_key_room_member(id, String::new());
+                self.cache_room_member_payload
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|(key, _)| key.ends_with(&suffix))
+            }
+            PayloadType::Friendship => self.cache_friendship_payload.lock().unwrap().contains(&id),
+            PayloadType::Unknown => false,
+        }
+    }
+
+    pub async fn dirty_payload(&self, payload_type: PayloadType, id: String) -> Result<(), PuppetError> {
         debug!("dirty_payload(payload_type = {:?}, id = {})", payload_type, id);
 
         match payload_type {
@@ -1070,6 +1342,10 @@ where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
 {
     async fn contact_self_name_set(&self, name: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: contact_self_name_set(name = {})", name);
+            return Ok(());
+        }
         self.puppet_impl.contact_self_name_set(name).await
     }
 
@@ -1078,18 +1354,40 @@ where
     }
 
     async fn contact_self_signature_set(&self, signature: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: contact_self_signature_set(signature = {})", signature);
+            return Ok(());
+        }
         self.puppet_impl.contact_self_signature_set(signature).await
     }
 
     async fn tag_contact_add(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: tag_contact_add(tag_id = {}, contact_id = {})",
+                tag_id, contact_id
+            );
+            return Ok(());
+        }
         self.puppet_impl.tag_contact_add(tag_id, contact_id).await
     }
 
     async fn tag_contact_remove(&self, tag_id: String, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: tag_contact_remove(tag_id = {}, contact_id = {})",
+                tag_id, contact_id
+            );
+            return Ok(());
+        }
         self.puppet_impl.tag_contact_remove(tag_id, contact_id).await
     }
 
     async fn tag_contact_delete(&self, tag_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: tag_contact_delete(tag_id = {})", tag_id);
+            return Ok(());
+        }
         self.puppet_impl.tag_contact_delete(tag_id).await
     }
 
@@ -1106,6 +1404,13 @@ where
     }
 
     async fn contact_alias_set(&self, contact_id: String, alias: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: contact_alias_set(contact_id = {}, alias = {})",
+                contact_id, alias
+            );
+            return Ok(());
+        }
         self.puppet_impl.contact_alias_set(contact_id, alias).await
     }
 
@@ -1114,10 +1419,21 @@ where
     }
 
     async fn contact_avatar_set(&self, contact_id: String, file: FileBox) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: contact_avatar_set(contact_id = {})", contact_id);
+            return Ok(());
+        }
         self.puppet_impl.contact_avatar_set(contact_id, file).await
     }
 
     async fn contact_phone_set(&self, contact_id: String, phone_list: Vec<String>) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: contact_phone_set(contact_id = {}, phone_list = {:?})",
+                contact_id, phone_list
+            );
+            return Ok(());
+        }
         self.puppet_impl.contact_phone_set(contact_id, phone_list).await
     }
 
@@ -1126,6 +1442,13 @@ where
         contact_id: String,
         corporation_remark: Option<String>,
     ) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: contact_corporation_remark_set(contact_id = {}, corporation_remark = {:?})",
+                contact_id, corporation_remark
+            );
+            return Ok(());
+        }
         self.puppet_impl
             .contact_corporation_remark_set(contact_id, corporation_remark)
             .await
@@ -1136,6 +1459,13 @@ where
         contact_id: String,
         description: Option<String>,
     ) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: contact_description_set(contact_id = {}, description = {:?})",
+                contact_id, description
+            );
+            return Ok(());
+        }
         self.puppet_impl.contact_description_set(contact_id, description).await
     }
 
@@ -1147,6 +1477,30 @@ where
         self.puppet_impl.contact_raw_payload(contact_id).await
     }
 
+    async fn contact_exists(&self, contact_id: String) -> Result<bool, PuppetError> {
+        self.puppet_impl.contact_exists(contact_id).await
+    }
+
+    async fn contact_block(&self, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: contact_block(contact_id = {})", contact_id);
+            return Ok(());
+        }
+        self.puppet_impl.contact_block(contact_id).await
+    }
+
+    async fn contact_unblock(&self, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: contact_unblock(contact_id = {})", contact_id);
+            return Ok(());
+        }
+        self.puppet_impl.contact_unblock(contact_id).await
+    }
+
+    async fn contact_is_blocked(&self, contact_id: String) -> Result<bool, PuppetError> {
+        self.puppet_impl.contact_is_blocked(contact_id).await
+    }
+
     async fn message_contact(&self, message_id: String) -> Result<String, PuppetError> {
         self.puppet_impl.message_contact(message_id).await
     }
@@ -1167,16 +1521,28 @@ where
         self.puppet_impl.message_url(message_id).await
     }
 
+    async fn message_chat_history(&self, message_id: String) -> Result<Vec<MessagePayload>, PuppetError> {
+        self.puppet_impl.message_chat_history(message_id).await
+    }
+
     async fn message_send_contact(
         &self,
         conversation_id: String,
         contact_id: String,
     ) -> Result<Option<String>, PuppetError> {
-        self.puppet_impl.message_send_contact(conversation_id, contact_id).await
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl.message_send_contact(conversation_id, contact_id),
+        )
+        .await
     }
 
     async fn message_send_file(&self, conversation_id: String, file: FileBox) -> Result<Option<String>, PuppetError> {
-        self.puppet_impl.message_send_file(conversation_id, file).await
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl.message_send_file(conversation_id, file),
+        )
+        .await
     }
 
     async fn message_send_mini_program(
@@ -1184,9 +1550,12 @@ where
         conversation_id: String,
         mini_program_payload: MiniProgramPayload,
     ) -> Result<Option<String>, PuppetError> {
-        self.puppet_impl
-            .message_send_mini_program(conversation_id, mini_program_payload)
-            .await
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl
+                .message_send_mini_program(conversation_id, mini_program_payload),
+        )
+        .await
     }
 
     async fn message_send_text(
@@ -1195,9 +1564,12 @@ where
         text: String,
         mention_id_list: Vec<String>,
     ) -> Result<Option<String>, PuppetError> {
-        self.puppet_impl
-            .message_send_text(conversation_id, text, mention_id_list)
-            .await
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl
+                .message_send_text(conversation_id, text, mention_id_list),
+        )
+        .await
     }
 
     async fn message_send_url(
@@ -1205,20 +1577,79 @@ where
         conversation_id: String,
         url_link_payload: UrlLinkPayload,
     ) -> Result<Option<String>, PuppetError> {
-        self.puppet_impl
-            .message_send_url(conversation_id, url_link_payload)
-            .await
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl.message_send_url(conversation_id, url_link_payload),
+        )
+        .await
     }
 
     async fn message_raw_payload(&self, message_id: String) -> Result<MessagePayload, PuppetError> {
         self.puppet_impl.message_raw_payload(message_id).await
     }
 
+    async fn message_send_quote(
+        &self,
+        conversation_id: String,
+        text: String,
+        quoted_message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl
+                .message_send_quote(conversation_id, text, quoted_message_id),
+        )
+        .await
+    }
+
+    async fn message_forward_native(
+        &self,
+        conversation_id: String,
+        message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        self.send(
+            conversation_id.clone(),
+            self.puppet_impl.message_forward_native(conversation_id, message_id),
+        )
+        .await
+    }
+
+    async fn message_money(&self, message_id: String) -> Result<MoneyPayload, PuppetError> {
+        self.puppet_impl.message_money(message_id).await
+    }
+
+    async fn favorite_list(&self) -> Result<Vec<String>, PuppetError> {
+        self.puppet_impl.favorite_list().await
+    }
+
+    async fn favorite_payload(&self, favorite_id: String) -> Result<FavoritePayload, PuppetError> {
+        self.puppet_impl.favorite_payload(favorite_id).await
+    }
+
+    async fn favorite_add(&self, message_id: String) -> Result<String, PuppetError> {
+        if self.dry_run {
+            info!("Dry run: favorite_add(message_id = {})", message_id);
+            return Ok(self.dry_run_id("favorite"));
+        }
+        self.puppet_impl.favorite_add(message_id).await
+    }
+
     async fn friendship_accept(&self, friendship_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: friendship_accept(friendship_id = {})", friendship_id);
+            return Ok(());
+        }
         self.puppet_impl.friendship_accept(friendship_id).await
     }
 
     async fn friendship_add(&self, contact_id: String, hello: Option<String>) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: friendship_add(contact_id = {}, hello = {:?})",
+                contact_id, hello
+            );
+            return Ok(());
+        }
         self.puppet_impl.friendship_add(contact_id, hello).await
     }
 
@@ -1235,9 +1666,35 @@ where
     }
 
     async fn room_invitation_accept(&self, room_invitation_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: room_invitation_accept(room_invitation_id = {})",
+                room_invitation_id
+            );
+            return Ok(());
+        }
         self.puppet_impl.room_invitation_accept(room_invitation_id).await
     }
 
+    async fn room_invitation_reject(&self, room_invitation_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: room_invitation_reject(room_invitation_id = {})",
+                room_invitation_id
+            );
+            return Ok(());
+        }
+        self.puppet_impl.room_invitation_reject(room_invitation_id).await
+    }
+
+    async fn room_join_by_url(&self, url: String) -> Result<String, PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_join_by_url(url = {})", url);
+            return Ok(self.dry_run_id("room"));
+        }
+        self.puppet_impl.room_join_by_url(url).await
+    }
+
     async fn room_invitation_raw_payload(
         &self,
         room_invitation_id: String,
@@ -1246,6 +1703,10 @@ where
     }
 
     async fn room_add(&self, room_id: String, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_add(room_id = {}, contact_id = {})", room_id, contact_id);
+            return Ok(());
+        }
         self.puppet_impl.room_add(room_id, contact_id).await
     }
 
@@ -1254,10 +1715,21 @@ where
     }
 
     async fn room_create(&self, contact_id_list: Vec<String>, topic: Option<String>) -> Result<String, PuppetError> {
+        if self.dry_run {
+            info!(
+                "Dry run: room_create(contact_id_list = {:?}, topic = {:?})",
+                contact_id_list, topic
+            );
+            return Ok(self.dry_run_id("room"));
+        }
         self.puppet_impl.room_create(contact_id_list, topic).await
     }
 
     async fn room_del(&self, room_id: String, contact_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_del(room_id = {}, contact_id = {})", room_id, contact_id);
+            return Ok(());
+        }
         self.puppet_impl.room_del(room_id, contact_id).await
     }
 
@@ -1266,6 +1738,10 @@ where
     }
 
     async fn room_quit(&self, room_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_quit(room_id = {})", room_id);
+            return Ok(());
+        }
         self.puppet_impl.room_quit(room_id).await
     }
 
@@ -1274,6 +1750,10 @@ where
     }
 
     async fn room_topic_set(&self, room_id: String, topic: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_topic_set(room_id = {}, topic = {})", room_id, topic);
+            return Ok(());
+        }
         self.puppet_impl.room_topic_set(room_id, topic).await
     }
 
@@ -1285,14 +1765,42 @@ where
         self.puppet_impl.room_raw_payload(room_id).await
     }
 
+    async fn room_exists(&self, room_id: String) -> Result<bool, PuppetError> {
+        self.puppet_impl.room_exists(room_id).await
+    }
+
     async fn room_announce(&self, room_id: String) -> Result<String, PuppetError> {
         self.puppet_impl.room_announce(room_id).await
     }
 
     async fn room_announce_set(&self, room_id: String, text: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_announce_set(room_id = {}, text = {})", room_id, text);
+            return Ok(());
+        }
         self.puppet_impl.room_announce_set(room_id, text).await
     }
 
+    async fn room_mute(&self, room_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_mute(room_id = {})", room_id);
+            return Ok(());
+        }
+        self.puppet_impl.room_mute(room_id).await
+    }
+
+    async fn room_unmute(&self, room_id: String) -> Result<(), PuppetError> {
+        if self.dry_run {
+            info!("Dry run: room_unmute(room_id = {})", room_id);
+            return Ok(());
+        }
+        self.puppet_impl.room_unmute(room_id).await
+    }
+
+    async fn room_is_muted(&self, room_id: String) -> Result<bool, PuppetError> {
+        self.puppet_impl.room_is_muted(room_id).await
+    }
+
     async fn room_member_list(&self, room_id: String) -> Result<Vec<String>, PuppetError> {
         self.puppet_impl.room_member_list(room_id).await
     }
@@ -1305,6 +1813,50 @@ where
         self.puppet_impl.room_member_raw_payload(room_id, contact_id).await
     }
 
+    /// Load cached members from the cache, and the rest from the puppet in one call, in the order of the ids.
+    async fn room_member_payload_list(
+        &self,
+        room_id: String,
+        member_id_list: Vec<String>,
+    ) -> Result<Vec<RoomMemberPayload>, PuppetError> {
+        let cache = &*self.cache_room_member_payload;
+        let mut member_map = HashMap::new();
+        let mut missing_id_list = vec![];
+        for member_id in &member_id_list {
+            let cache_key = Puppet::<T>::cache_key_room_member(room_id.clone(), member_id.clone());
+            match cache.lock().unwrap().get(&cache_key) {
+                Some(payload) => {
+                    member_map.insert(member_id.clone(), payload.clone());
+                }
+                None => missing_id_list.push(member_id.clone()),
+            }
+        }
+        if !missing_id_list.is_empty() {
+            let payload_list = match self
+                .puppet_impl
+                .room_member_payload_list(room_id.clone(), missing_id_list.clone())
+                .await
+            {
+                Ok(payload_list) => payload_list,
+                Err(PuppetError::Unsupported(_)) => {
+                    self.room_member_raw_payload_each(room_id.clone(), missing_id_list)
+                        .await
+                }
+                Err(e) => return Err(e),
+            };
+            for payload in payload_list {
+                let cache_key = Puppet::<T>::cache_key_room_member(room_id.clone(), payload.id.clone());
+                cache.lock().unwrap().put(cache_key, payload.clone());
+                member_map.insert(payload.id.clone(), payload);
+            }
+        }
+        // Members that fail to load are left out.
+        Ok(member_id_list
+            .iter()
+            .filter_map(|member_id| member_map.get(member_id).cloned())
+            .collect())
+    }
+
     async fn start(&self) -> Result<(), PuppetError> {
         self.puppet_impl.start().await
     }
@@ -1324,6 +1876,14 @@ where
     async fn logout(&self) -> Result<(), PuppetError> {
         self.puppet_impl.logout().await
     }
+
+    fn capabilities(&self) -> PuppetCapabilities {
+        self.puppet_impl.capabilities()
+    }
+
+    fn set_heartbeat_timeout(&self, heartbeat_timeout: Option<Duration>) -> Result<(), PuppetError> {
+        self.puppet_impl.set_heartbeat_timeout(heartbeat_timeout)
+    }
 }
 
 #[async_trait]
@@ -1353,11 +1913,38 @@ pub trait PuppetImpl {
     async fn contact_list(&self) -> Result<Vec<String>, PuppetError>;
     async fn contact_raw_payload(&self, contact_id: String) -> Result<ContactPayload, PuppetError>;
 
+    /// Check whether a contact exists without loading its payload, only some gateways have a lightweight way to
+    /// check, so it returns `PuppetError::Unsupported` by default.
+    async fn contact_exists(&self, _contact_id: String) -> Result<bool, PuppetError> {
+        Err(PuppetError::Unsupported("contact_exists".to_owned()))
+    }
+
+    /// Block a contact, only some gateways support blocking, so it returns `PuppetError::Unsupported` by default.
+    async fn contact_block(&self, _contact_id: String) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("contact_block".to_owned()))
+    }
+
+    async fn contact_unblock(&self, _contact_id: String) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("contact_unblock".to_owned()))
+    }
+
+    async fn contact_is_blocked(&self, _contact_id: String) -> Result<bool, PuppetError> {
+        Err(PuppetError::Unsupported("contact_is_blocked".to_owned()))
+    }
+
     async fn message_contact(&self, message_id: String) -> Result<String, PuppetError>;
     async fn message_file(&self, message_id: String) -> Result<FileBox, PuppetError>;
     async fn message_image(&self, message_id: String, image_type: ImageType) -> Result<FileBox, PuppetError>;
     async fn message_mini_program(&self, message_id: String) -> Result<MiniProgramPayload, PuppetError>;
     async fn message_url(&self, message_id: String) -> Result<UrlLinkPayload, PuppetError>;
+
+    /// Expand a chat history message into the messages forwarded in it.
+    ///
+    /// Not every gateway can expand chat histories, so it returns `PuppetError::Unsupported` by default.
+    async fn message_chat_history(&self, _message_id: String) -> Result<Vec<MessagePayload>, PuppetError> {
+        Err(PuppetError::Unsupported("message_chat_history".to_owned()))
+    }
+
     async fn message_send_contact(
         &self,
         conversation_id: String,
@@ -1382,6 +1969,65 @@ pub trait PuppetImpl {
     ) -> Result<Option<String>, PuppetError>;
     async fn message_raw_payload(&self, message_id: String) -> Result<MessagePayload, PuppetError>;
 
+    /// Send a text quoting another message, which is different from a plain text as the quoted message is
+    /// displayed along with it.
+    ///
+    /// Not every puppet supports quoting, so it returns `PuppetError::Unsupported` by default.
+    async fn message_send_quote(
+        &self,
+        _conversation_id: String,
+        _text: String,
+        _quoted_message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        Err(PuppetError::Unsupported("message_send_quote".to_owned()))
+    }
+
+    /// Forward a message as the gateway does, keeping the forwarded marker, see `Puppet::message_forward_with_path`.
+    ///
+    /// Not every puppet supports native forwarding, so it returns `PuppetError::Unsupported` by default.
+    async fn message_forward_native(
+        &self,
+        _conversation_id: String,
+        _message_id: String,
+    ) -> Result<Option<String>, PuppetError> {
+        Err(PuppetError::Unsupported("message_forward_native".to_owned()))
+    }
+
+    /// Get the amount, status and memo of a transfer or red envelope message.
+    ///
+    /// By default they are parsed from the app message xml in the text of the message, and
+    /// `PuppetError::Unsupported` is returned if the gateway doesn't embed it there.
+    async fn message_money(&self, message_id: String) -> Result<MoneyPayload, PuppetError> {
+        let payload = self.message_raw_payload(message_id).await?;
+        match payload.message_type {
+            MessageType::Transfer | MessageType::RedEnvelope => match MoneyPayload::from_xml(&payload.text) {
+                Ok(money) => Ok(money),
+                Err(_) => Err(PuppetError::Unsupported("message_money".to_owned())),
+            },
+            message_type => Err(PuppetError::InvalidArgument(format!(
+                "Cannot get money from a {:?} message",
+                message_type
+            ))),
+        }
+    }
+
+    /// Get the ids of the favorites of the bot.
+    ///
+    /// Not every gateway exposes favorites, so this and the other favorite functions return
+    /// `PuppetError::Unsupported` by default.
+    async fn favorite_list(&self) -> Result<Vec<String>, PuppetError> {
+        Err(PuppetError::Unsupported("favorite_list".to_owned()))
+    }
+
+    async fn favorite_payload(&self, _favorite_id: String) -> Result<FavoritePayload, PuppetError> {
+        Err(PuppetError::Unsupported("favorite_payload".to_owned()))
+    }
+
+    /// Star a message, return the id of the new favorite.
+    async fn favorite_add(&self, _message_id: String) -> Result<String, PuppetError> {
+        Err(PuppetError::Unsupported("favorite_add".to_owned()))
+    }
+
     async fn friendship_accept(&self, friendship_id: String) -> Result<(), PuppetError>;
     async fn friendship_add(&self, contact_id: String, hello: Option<String>) -> Result<(), PuppetError>;
     async fn friendship_search_phone(&self, phone: String) -> Result<Option<String>, PuppetError>;
@@ -1389,11 +2035,26 @@ pub trait PuppetImpl {
     async fn friendship_raw_payload(&self, friendship_id: String) -> Result<FriendshipPayload, PuppetError>;
 
     async fn room_invitation_accept(&self, room_invitation_id: String) -> Result<(), PuppetError>;
+
+    /// Decline a room invitation.
+    ///
+    /// Not every gateway can decline invitations, so it returns `PuppetError::Unsupported` by default.
+    async fn room_invitation_reject(&self, _room_invitation_id: String) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("room_invitation_reject".to_owned()))
+    }
+
     async fn room_invitation_raw_payload(
         &self,
         room_invitation_id: String,
     ) -> Result<RoomInvitationPayload, PuppetError>;
 
+    /// Join a room by its QR code or invitation url, return the id of the room.
+    ///
+    /// Only some gateways allow joining by url, so it returns `PuppetError::Unsupported` by default.
+    async fn room_join_by_url(&self, _url: String) -> Result<String, PuppetError> {
+        Err(PuppetError::Unsupported("room_join_by_url".to_owned()))
+    }
+
     async fn room_add(&self, room_id: String, contact_id: String) -> Result<(), PuppetError>;
     async fn room_avatar(&self, room_id: String) -> Result<FileBox, PuppetError>;
     async fn room_create(&self, contact_id_list: Vec<String>, topic: Option<String>) -> Result<String, PuppetError>;
@@ -1405,8 +2066,29 @@ pub trait PuppetImpl {
     async fn room_list(&self) -> Result<Vec<String>, PuppetError>;
     async fn room_raw_payload(&self, room_id: String) -> Result<RoomPayload, PuppetError>;
 
+    /// Check whether a room exists without loading its payload, like `contact_exists` it returns
+    /// `PuppetError::Unsupported` by default.
+    async fn room_exists(&self, _room_id: String) -> Result<bool, PuppetError> {
+        Err(PuppetError::Unsupported("room_exists".to_owned()))
+    }
+
     async fn room_announce(&self, room_id: String) -> Result<String, PuppetError>;
     async fn room_announce_set(&self, room_id: String, text: String) -> Result<(), PuppetError>;
+
+    /// Mute the notifications of a room, only some gateways support muting, so it returns `PuppetError::Unsupported`
+    /// by default.
+    async fn room_mute(&self, _room_id: String) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("room_mute".to_owned()))
+    }
+
+    async fn room_unmute(&self, _room_id: String) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("room_unmute".to_owned()))
+    }
+
+    async fn room_is_muted(&self, _room_id: String) -> Result<bool, PuppetError> {
+        Err(PuppetError::Unsupported("room_is_muted".to_owned()))
+    }
+
     async fn room_member_list(&self, room_id: String) -> Result<Vec<String>, PuppetError>;
     async fn room_member_raw_payload(
         &self,
@@ -1414,9 +2096,202 @@ pub trait PuppetImpl {
         contact_id: String,
     ) -> Result<RoomMemberPayload, PuppetError>;
 
+    /// Load the raw payloads of room members in one call, members that fail to load are left out.
+    ///
+    /// Unsupported by default, in which case `Puppet` loads the members one by one. Puppets whose gateway has a bulk
+    /// RPC should override it to save round trips in large rooms.
+    async fn room_member_payload_list(
+        &self,
+        _room_id: String,
+        _member_id_list: Vec<String>,
+    ) -> Result<Vec<RoomMemberPayload>, PuppetError> {
+        Err(PuppetError::Unsupported("room_member_payload_list".to_owned()))
+    }
+
     async fn start(&self) -> Result<(), PuppetError>;
     async fn stop(&self) -> Result<(), PuppetError>;
     async fn ding(&self, data: String) -> Result<(), PuppetError>;
     async fn version(&self) -> Result<String, PuppetError>;
     async fn logout(&self) -> Result<(), PuppetError>;
+
+    /// Features supported by this puppet, check it before calling a method that may be unsupported.
+    ///
+    /// Everything is reported as supported by default, so that existing puppets keep working, puppets should
+    /// override it to opt out of the features they lack.
+    fn capabilities(&self) -> PuppetCapabilities {
+        PuppetCapabilities::default()
+    }
+
+    /// Reconnect if no event, including heartbeats, arrives within this timeout, or stop watching if `None`.
+    ///
+    /// Only puppets connected to a gateway have a connection to watch, so it returns `PuppetError::Unsupported` by
+    /// default.
+    fn set_heartbeat_timeout(&self, _heartbeat_timeout: Option<Duration>) -> Result<(), PuppetError> {
+        Err(PuppetError::Unsupported("set_heartbeat_timeout".to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix::ActorContext;
+
+    use super::*;
+    use crate::{fixtures, BoxedPuppet, EventDongPayload, EventReadyPayload};
+
+    #[test]
+    fn can_parse_batch_sizes() {
+        assert_eq!(parse_batch_size(None), DEFAULT_BATCH_SIZE);
+        assert_eq!(parse_batch_size(Some("2")), 2);
+        assert_eq!(parse_batch_size(Some("0")), DEFAULT_BATCH_SIZE);
+        assert_eq!(parse_batch_size(Some("-1")), DEFAULT_BATCH_SIZE);
+        assert_eq!(parse_batch_size(Some("many")), DEFAULT_BATCH_SIZE);
+    }
+
+    struct EventRecorder {
+        events: Arc<Mutex<Vec<EventName>>>,
+    }
+
+    impl Actor for EventRecorder {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<PuppetEvent> for EventRecorder {
+        type Result = ();
+
+        fn handle(&mut self, msg: PuppetEvent, _ctx: &mut Self::Context) -> Self::Result {
+            self.events.lock().unwrap().push(msg.event_name());
+        }
+    }
+
+    fn located_contact(id: &str, province: &str, city: &str) -> ContactPayload {
+        ContactPayload {
+            city: city.to_owned(),
+            province: province.to_owned(),
+            ..fixtures::contact(id, id)
+        }
+    }
+
+    #[test]
+    fn can_filter_contacts_by_region() {
+        let contacts = [
+            located_contact("alice", "Guangdong", "Shenzhen"),
+            located_contact("bob", "Guangdong", "Guangzhou"),
+            located_contact("carol", "Zhejiang", "Hangzhou"),
+            located_contact("dave", "", ""),
+        ];
+        let search = |query: ContactQueryFilter| -> Vec<String> {
+            let filter = Puppet::<BoxedPuppet>::contact_query_filter_factory(query);
+            contacts
+                .iter()
+                .filter(|payload| filter((*payload).clone()))
+                .map(|payload| payload.id.clone())
+                .collect()
+        };
+
+        let in_guangdong = search(ContactQueryFilter {
+            province: Some("Guangdong".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(in_guangdong, vec!["alice", "bob"]);
+        let in_shenzhen = search(ContactQueryFilter {
+            province: Some("Guangdong".to_owned()),
+            city: Some("Shenzhen".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(in_shenzhen, vec!["alice"]);
+        let by_regex = search(ContactQueryFilter {
+            province_regex: Some(Regex::new("^(Guangdong|Zhejiang)$").unwrap()),
+            city_regex: Some(Regex::new("zhou$").unwrap()),
+            ..Default::default()
+        });
+        assert_eq!(by_regex, vec!["bob", "carol"]);
+    }
+
+    #[actix_rt::test]
+    async fn can_subscribe_by_event_name() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorder = EventRecorder { events: events.clone() }.start();
+        let addr = PuppetInner::new().start();
+        addr.send(Subscribe {
+            addr: recorder.recipient(),
+            name: "recorder".to_owned(),
+            event_name: EventName::Dong,
+        })
+        .await
+        .unwrap();
+        addr.send(PuppetEvent::Dong(EventDongPayload {
+            data: "dong".to_owned(),
+        }))
+        .await
+        .unwrap();
+        addr.send(PuppetEvent::Ready(EventReadyPayload {
+            data: "ready".to_owned(),
+        }))
+        .await
+        .unwrap();
+
+        actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(*events.lock().unwrap(), vec![EventName::Dong]);
+    }
+
+    /// An actor that stops as soon as it starts.
+    struct StoppedRecorder;
+
+    impl Actor for StoppedRecorder {
+        type Context = Context<Self>;
+
+        fn started(&mut self, ctx: &mut Self::Context) {
+            ctx.stop();
+        }
+    }
+
+    impl Handler<PuppetEvent> for StoppedRecorder {
+        type Result = ();
+
+        fn handle(&mut self, _msg: PuppetEvent, _ctx: &mut Self::Context) -> Self::Result {}
+    }
+
+    #[actix_rt::test]
+    async fn stopped_subscribers_are_pruned() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorder = EventRecorder { events: events.clone() }.start();
+        let stopped = StoppedRecorder.start();
+        actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!stopped.connected());
+
+        let addr = PuppetInner::new().start();
+        for (name, recipient) in [("recorder", recorder.recipient()), ("stopped", stopped.recipient())] {
+            addr.send(Subscribe {
+                addr: recipient,
+                name: name.to_owned(),
+                event_name: EventName::Dong,
+            })
+            .await
+            .unwrap();
+        }
+        assert_eq!(
+            addr.send(Subscribers {
+                event_name: EventName::Dong
+            })
+            .await
+            .unwrap(),
+            vec!["recorder", "stopped"]
+        );
+
+        addr.send(PuppetEvent::Dong(EventDongPayload {
+            data: "dong".to_owned(),
+        }))
+        .await
+        .unwrap();
+        assert_eq!(
+            addr.send(Subscribers {
+                event_name: EventName::Dong
+            })
+            .await
+            .unwrap(),
+            vec!["recorder"]
+        );
+        actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(*events.lock().unwrap(), vec![EventName::Dong]);
+    }
 }
@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use actix::{Actor, Addr, Context, Handler};
+use log::{debug, error};
+
+use crate::{EventName, Puppet, PuppetEvent, PuppetImpl, Subscribe};
+
+/// Write an event as a line of JSON, see `read_events`.
+pub fn write_event<W: Write>(writer: &mut W, event: &PuppetEvent) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, event)?;
+    writer.write_all(b"\n")
+}
+
+/// Read the events written by `write_event` in order, skipping blank lines.
+pub fn read_events<R: BufRead>(reader: R) -> io::Result<Vec<PuppetEvent>> {
+    let mut events = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            events.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(events)
+}
+
+/// Record every event emitted by a puppet to a file, so that a real session can be replayed against a bot later.
+pub struct EventRecorder {
+    file: File,
+}
+
+impl EventRecorder {
+    /// Start recording the events of the puppet to the file, which is truncated if it exists.
+    pub fn start<T>(puppet: &Puppet<T>, path: &Path) -> io::Result<Addr<Self>>
+    where
+        T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+    {
+        debug!("EventRecorder.start(path = {:?})", path);
+        let addr = Self {
+            file: File::create(path)?,
+        }
+        .start();
+        for event_name in EventName::ALL {
+            if let Err(e) = puppet.get_subscribe_addr().do_send(Subscribe {
+                addr: addr.clone().recipient(),
+                name: format!("EventRecorder({})", path.display()),
+                event_name,
+            }) {
+                error!(
+                    "EventRecorder failed to subscribe to event {}: {}",
+                    event_name.as_str(),
+                    e
+                );
+            }
+        }
+        Ok(addr)
+    }
+}
+
+impl Actor for EventRecorder {
+    type Context = Context<Self>;
+}
+
+impl Handler<PuppetEvent> for EventRecorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: PuppetEvent, _ctx: &mut Self::Context) -> Self::Result {
+        if let Err(e) = write_event(&mut self.file, &msg) {
+            error!("Failed to record event {}: {}", msg.event_name().as_str(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::event::*;
+    use crate::PayloadType;
+
+    fn all_events() -> Vec<PuppetEvent> {
+        vec![
+            PuppetEvent::ConnectionChange(EventConnectionChangePayload {
+                state: ConnectionState::Reconnecting,
+            }),
+            PuppetEvent::Dirty(EventDirtyPayload {
+                payload_type: PayloadType::RoomMember,
+                payload_id: "room".to_owned(),
+            }),
+            PuppetEvent::Dong(EventDongPayload {
+                data: "dong".to_owned(),
+            }),
+            PuppetEvent::Error(EventErrorPayload {
+                data: "error".to_owned(),
+            }),
+            PuppetEvent::Friendship(EventFriendshipPayload {
+                friendship_id: "friendship".to_owned(),
+            }),
+            PuppetEvent::Heartbeat(EventHeartbeatPayload {
+                data: "heartbeat".to_owned(),
+            }),
+            PuppetEvent::Login(EventLoginPayload {
+                contact_id: "self".to_owned(),
+            }),
+            PuppetEvent::Logout(EventLogoutPayload {
+                contact_id: "self".to_owned(),
+                data: "bye".to_owned(),
+            }),
+            PuppetEvent::Message(EventMessagePayload {
+                message_id: "message".to_owned(),
+            }),
+            PuppetEvent::Ready(EventReadyPayload {
+                data: "ready".to_owned(),
+            }),
+            PuppetEvent::Reset(EventResetPayload {
+                data: "reset".to_owned(),
+            }),
+            PuppetEvent::RoomInvite(EventRoomInvitePayload {
+                room_invitation_id: "invitation".to_owned(),
+            }),
+            PuppetEvent::RoomJoin(EventRoomJoinPayload {
+                invitee_id_list: vec!["alice".to_owned(), "bob".to_owned()],
+                inviter_id: "carol".to_owned(),
+                room_id: "room".to_owned(),
+                timestamp: 1,
+            }),
+            PuppetEvent::RoomLeave(EventRoomLeavePayload {
+                removee_id_list: vec!["alice".to_owned()],
+                remover_id: "carol".to_owned(),
+                room_id: "room".to_owned(),
+                timestamp: 2,
+            }),
+            PuppetEvent::RoomTopic(EventRoomTopicPayload {
+                changer_id: "carol".to_owned(),
+                new_topic: "新话题".to_owned(),
+                old_topic: "Rust".to_owned(),
+                room_id: "room".to_owned(),
+                timestamp: 3,
+            }),
+            PuppetEvent::Scan(EventScanPayload {
+                status: ScanStatus::Waiting,
+                qrcode: Some("qrcode".to_owned()),
+                data: None,
+            }),
+        ]
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let events = all_events();
+        let mut names: Vec<EventName> = events.iter().map(PuppetEvent::event_name).collect();
+        names.dedup();
+        assert_eq!(names, EventName::ALL);
+
+        let mut recording = vec![];
+        for event in &events {
+            write_event(&mut recording, event).unwrap();
+        }
+        let replayed = read_events(recording.as_slice()).unwrap();
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", events));
+    }
+}
@@ -1,4 +1,5 @@
 use regex::Regex;
+use serde::Serialize;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 #[derive(Debug, Clone, PartialEq, FromPrimitive, Deserialize_repr, Serialize_repr)]
@@ -18,7 +19,7 @@ pub enum ContactType {
     Corporation,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ContactPayload {
     pub id: String,
     pub gender: ContactGender,
@@ -44,9 +45,13 @@ pub struct ContactPayload {
 pub struct ContactQueryFilter {
     pub alias: Option<String>,
     pub alias_regex: Option<Regex>,
+    pub city: Option<String>,
+    pub city_regex: Option<Regex>,
     pub id: Option<String>,
     pub name: Option<String>,
     pub name_regex: Option<Regex>,
+    pub province: Option<String>,
+    pub province_regex: Option<Regex>,
     pub weixin: Option<String>,
 }
 
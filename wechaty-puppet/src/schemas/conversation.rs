@@ -0,0 +1,77 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Room ids start with `@@` in web protocol based puppets, and end with `@chatroom` in most other puppets.
+pub const DEFAULT_ROOM_ID_PATTERN: &str = "^@@|@chatroom$";
+
+pub(crate) static DEFAULT_ROOM_ID_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(DEFAULT_ROOM_ID_PATTERN).unwrap());
+
+/// A conversation, which is either a room or a contact, classified by the shape of its id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConversationId {
+    Room(String),
+    Contact(String),
+}
+
+impl ConversationId {
+    /// Classify an id with `DEFAULT_ROOM_ID_PATTERN`, return `None` if the id is empty.
+    pub fn parse(id: &str) -> Option<Self> {
+        ConversationId::parse_with(id, &DEFAULT_ROOM_ID_REGEX)
+    }
+
+    /// Classify an id as a room if it matches `room_id_pattern`, return `None` if the id is empty.
+    pub fn parse_with(id: &str, room_id_pattern: &Regex) -> Option<Self> {
+        if id.is_empty() {
+            None
+        } else if room_id_pattern.is_match(id) {
+            Some(ConversationId::Room(id.to_owned()))
+        } else {
+            Some(ConversationId::Contact(id.to_owned()))
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            ConversationId::Room(id) | ConversationId::Contact(id) => id,
+        }
+    }
+
+    pub fn is_room(&self) -> bool {
+        matches!(self, ConversationId::Room(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_conversation_ids() {
+        let cases = [
+            ("@@0123456789abcdef", Some(true)),
+            ("12345678@chatroom", Some(true)),
+            ("@0123456789abcdef", Some(false)),
+            ("wxid_abcdefg", Some(false)),
+            ("filehelper", Some(false)),
+            ("", None),
+        ];
+        for (id, is_room) in cases.iter() {
+            assert_eq!(
+                ConversationId::parse(id).map(|conversation_id| conversation_id.is_room()),
+                *is_room,
+                "{}",
+                id
+            );
+        }
+        assert_eq!(ConversationId::parse("wxid_abcdefg").unwrap().id(), "wxid_abcdefg");
+    }
+
+    #[test]
+    fn can_parse_with_custom_pattern() {
+        let pattern = Regex::new("^R:").unwrap();
+        assert!(ConversationId::parse_with("R:10001", &pattern).unwrap().is_room());
+        assert!(!ConversationId::parse_with("12345678@chatroom", &pattern)
+            .unwrap()
+            .is_room());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::schemas::payload::PayloadType;
@@ -13,33 +14,46 @@ pub enum ScanStatus {
     Timeout,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventConnectionChangePayload {
+    pub state: ConnectionState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventFriendshipPayload {
     pub friendship_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventLoginPayload {
     pub contact_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventLogoutPayload {
     pub contact_id: String,
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventMessagePayload {
     pub message_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventRoomInvitePayload {
     pub room_invitation_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventRoomJoinPayload {
     pub invitee_id_list: Vec<String>,
     pub inviter_id: String,
@@ -47,7 +61,7 @@ pub struct EventRoomJoinPayload {
     pub timestamp: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventRoomLeavePayload {
     pub removee_id_list: Vec<String>,
     pub remover_id: String,
@@ -55,7 +69,7 @@ pub struct EventRoomLeavePayload {
     pub timestamp: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventRoomTopicPayload {
     pub changer_id: String,
     pub new_topic: String,
@@ -64,39 +78,39 @@ pub struct EventRoomTopicPayload {
     pub timestamp: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventScanPayload {
     pub status: ScanStatus,
     pub qrcode: Option<String>,
     pub data: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventDongPayload {
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventErrorPayload {
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventReadyPayload {
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventResetPayload {
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventHeartbeatPayload {
     pub data: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventDirtyPayload {
     pub payload_type: PayloadType,
     pub payload_id: String,
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::MessageType;
+
+/// A message starred by the bot.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FavoritePayload {
+    pub id: String,
+    pub message_id: String,
+    pub message_type: MessageType,
+    pub text: String,
+    pub timestamp: u64,
+}
@@ -1,4 +1,5 @@
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 #[derive(Debug, Clone, PartialEq, FromPrimitive, Deserialize_repr, Serialize_repr)]
@@ -22,6 +23,47 @@ pub enum MessageType {
     Video,
 }
 
+/// Coarse grouping of message types, for handlers that only care about the kind of content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageCategory {
+    Text,
+    Media,
+    Rich,
+    System,
+    Money,
+    Unknown,
+}
+
+/// How a message was forwarded, see `Puppet::message_forward_with_path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardPath {
+    /// Forwarded by the gateway, which keeps the forwarded marker.
+    Native,
+    /// Re-sent from the content of the message, which loses the forwarded marker.
+    Reconstructed,
+}
+
+impl MessageType {
+    pub fn category(&self) -> MessageCategory {
+        match self {
+            MessageType::Text => MessageCategory::Text,
+            MessageType::Image
+            | MessageType::Audio
+            | MessageType::Video
+            | MessageType::Attachment
+            | MessageType::Emoticon => MessageCategory::Media,
+            MessageType::Url
+            | MessageType::MiniProgram
+            | MessageType::Location
+            | MessageType::Contact
+            | MessageType::ChatHistory => MessageCategory::Rich,
+            MessageType::Recalled | MessageType::GroupNote => MessageCategory::System,
+            MessageType::Transfer | MessageType::RedEnvelope => MessageCategory::Money,
+            MessageType::Unknown => MessageCategory::Unknown,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, FromPrimitive, Deserialize_repr, Serialize_repr)]
 #[repr(i32)]
 pub enum WechatAppMessageType {
@@ -74,7 +116,7 @@ pub enum WechatMessageType {
     Recalled = 10002,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MessagePayload {
     pub id: String,
     pub filename: String,
@@ -87,6 +129,38 @@ pub struct MessagePayload {
     pub to_id: String,
 }
 
+/// A stable, serializable shape of a message for persistence, which does not change when `MessagePayload` gains
+/// fields.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MessageRecord {
+    pub id: String,
+    pub conversation_id: String,
+    pub sender_id: String,
+    pub message_type: String,
+    pub text: String,
+    pub timestamp: u64,
+}
+
+impl From<MessagePayload> for MessageRecord {
+    /// The conversation is the room for room messages, and the sender otherwise, which is only right for
+    /// received messages. Use `Message::to_record` to resolve the conversation of sent messages as well.
+    fn from(payload: MessagePayload) -> Self {
+        let conversation_id = if payload.room_id.is_empty() {
+            payload.from_id.clone()
+        } else {
+            payload.room_id
+        };
+        Self {
+            id: payload.id,
+            conversation_id,
+            sender_id: payload.from_id,
+            message_type: format!("{:?}", payload.message_type),
+            text: payload.text,
+            timestamp: payload.timestamp,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct MessageQueryFilter {
     pub from_id: Option<String>,
@@ -103,3 +177,68 @@ pub struct MessageQueryFilter {
 //
 // pub trait MessagePayloadFilterFactory = Fn(MessageQueryFilter) ->
 // MessagePayloadFilterFunction;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn message_payload(room_id: &str) -> MessagePayload {
+        MessagePayload {
+            timestamp: 1_600_000_000,
+            room_id: room_id.to_owned(),
+            to_id: "self".to_owned(),
+            ..fixtures::message("message", "alice", "你好")
+        }
+    }
+
+    #[test]
+    fn can_convert_payload_to_record() {
+        let record = MessageRecord::from(message_payload(""));
+        assert_eq!(record.id, "message");
+        assert_eq!(record.conversation_id, "alice");
+        assert_eq!(record.sender_id, "alice");
+        assert_eq!(record.message_type, "Text");
+        assert_eq!(record.text, "你好");
+        assert_eq!(record.timestamp, 1_600_000_000);
+
+        let record: MessageRecord = message_payload("room").into();
+        assert_eq!(record.conversation_id, "room");
+    }
+
+    #[test]
+    fn record_round_trips_through_json() {
+        let record = MessageRecord::from(message_payload("room"));
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"message","conversation_id":"room","sender_id":"alice","message_type":"Text","text":"你好","timestamp":1600000000}"#
+        );
+        assert_eq!(serde_json::from_str::<MessageRecord>(&json).unwrap(), record);
+    }
+
+    #[test]
+    fn can_map_message_types_to_categories() {
+        let expected = [
+            (MessageType::Unknown, MessageCategory::Unknown),
+            (MessageType::Attachment, MessageCategory::Media),
+            (MessageType::Audio, MessageCategory::Media),
+            (MessageType::Contact, MessageCategory::Rich),
+            (MessageType::ChatHistory, MessageCategory::Rich),
+            (MessageType::Emoticon, MessageCategory::Media),
+            (MessageType::Image, MessageCategory::Media),
+            (MessageType::Text, MessageCategory::Text),
+            (MessageType::Location, MessageCategory::Rich),
+            (MessageType::MiniProgram, MessageCategory::Rich),
+            (MessageType::GroupNote, MessageCategory::System),
+            (MessageType::Transfer, MessageCategory::Money),
+            (MessageType::RedEnvelope, MessageCategory::Money),
+            (MessageType::Recalled, MessageCategory::System),
+            (MessageType::Url, MessageCategory::Rich),
+            (MessageType::Video, MessageCategory::Media),
+        ];
+        for (message_type, category) in expected {
+            assert_eq!(message_type.category(), category, "{:?}", message_type);
+        }
+    }
+}
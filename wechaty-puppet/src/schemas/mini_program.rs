@@ -1,14 +1,68 @@
 use serde::{Deserialize, Serialize};
 
+/// Field names are serialized in snake_case, camelCase names are accepted as well, see `JsonCasing`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MiniProgramPayload {
     appid: Option<String>,
     description: Option<String>,
+    #[serde(alias = "pagePath")]
     page_path: Option<String>,
+    #[serde(alias = "iconUrl")]
     icon_url: Option<String>,
+    #[serde(alias = "shareId")]
     share_id: Option<String>,
+    #[serde(alias = "thumbUrl")]
     thumb_url: Option<String>,
     title: Option<String>,
     username: Option<String>,
+    #[serde(alias = "thumbKey")]
     thumb_key: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, to_value};
+
+    use super::*;
+
+    #[test]
+    fn mini_program_payload_uses_snake_case_field_names() {
+        let payload = MiniProgramPayload {
+            appid: Some("wx1234".to_owned()),
+            description: Some("Wechaty".to_owned()),
+            page_path: Some("pages/index".to_owned()),
+            icon_url: Some("https://wechaty.js.org/img/icon.png".to_owned()),
+            share_id: Some("share".to_owned()),
+            thumb_url: Some("https://wechaty.js.org/img/thumb.png".to_owned()),
+            title: Some("Wechaty".to_owned()),
+            username: Some("gh_wechaty".to_owned()),
+            thumb_key: Some("key".to_owned()),
+        };
+        let value = to_value(&payload).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "appid": "wx1234",
+                "description": "Wechaty",
+                "page_path": "pages/index",
+                "icon_url": "https://wechaty.js.org/img/icon.png",
+                "share_id": "share",
+                "thumb_url": "https://wechaty.js.org/img/thumb.png",
+                "title": "Wechaty",
+                "username": "gh_wechaty",
+                "thumb_key": "key",
+            })
+        );
+        let parsed: MiniProgramPayload = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.page_path, payload.page_path);
+    }
+
+    #[test]
+    fn can_read_camel_case_mini_program_payload() {
+        let payload: MiniProgramPayload =
+            serde_json::from_str(r#"{"appid": "wx1234", "pagePath": "pages/index", "thumbKey": "key"}"#).unwrap();
+        assert_eq!(payload.page_path, Some("pages/index".to_owned()));
+        assert_eq!(payload.thumb_key, Some("key".to_owned()));
+        assert_eq!(payload.title, None);
+    }
+}
@@ -1,9 +1,12 @@
 pub mod contact;
+pub mod conversation;
 pub mod event;
+pub mod favorite;
 pub mod friendship;
 pub mod image;
 pub mod message;
 pub mod mini_program;
+pub mod money;
 pub mod payload;
 pub mod puppet;
 pub mod room;
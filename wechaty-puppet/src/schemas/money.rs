@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::PuppetError;
+
+const DEFAULT_CURRENCY: &str = "CNY";
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MoneyStatus {
+    Unknown,
+    Pending,
+    Received,
+    Refunded,
+}
+
+impl MoneyStatus {
+    /// Map the `paysubtype` of a wechat pay message to a status.
+    fn from_pay_subtype(pay_subtype: &str) -> Self {
+        match pay_subtype {
+            "1" | "8" => MoneyStatus::Pending,
+            "3" => MoneyStatus::Received,
+            "4" => MoneyStatus::Refunded,
+            _ => MoneyStatus::Unknown,
+        }
+    }
+}
+
+/// Metadata of a transfer or red envelope message.
+///
+/// The amount of a red envelope is not revealed in the message, so it is `None` for them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MoneyPayload {
+    pub amount: Option<String>,
+    pub currency: String,
+    pub status: MoneyStatus,
+    pub memo: Option<String>,
+}
+
+/// Elements read from the xml of a money message.
+const XML_TAGS: [&str; 5] = ["wcpayinfo", "feedesc", "paysubtype", "pay_memo", "sendertitle"];
+
+static XML_TEXT_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
+    XML_TAGS
+        .iter()
+        .map(|tag| {
+            let pattern = format!(r"(?s)<{0}>\s*(?:<!\[CDATA\[(.*?)\]\]>|(.*?))\s*</{0}>", tag);
+            (*tag, Regex::new(&pattern).unwrap())
+        })
+        .collect()
+});
+
+/// Get the text of the first `tag` element in the xml, with the CDATA wrapper stripped, `tag` must be one of
+/// `XML_TAGS`.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    XML_TEXT_PATTERNS[tag]
+        .captures(xml)
+        .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|text| text.as_str().trim().to_owned())
+        .filter(|text| !text.is_empty())
+}
+
+impl MoneyPayload {
+    /// Parse the payload from the app message xml of a transfer or red envelope message, which carries a
+    /// `wcpayinfo` element.
+    pub fn from_xml(xml: &str) -> Result<Self, PuppetError> {
+        let pay_info = match xml_text(xml, "wcpayinfo") {
+            Some(pay_info) => pay_info,
+            None => {
+                return Err(PuppetError::InvalidArgument(
+                    "Xml of a money message must contain wcpayinfo".to_owned(),
+                ))
+            }
+        };
+        let (amount, currency) = match xml_text(&pay_info, "feedesc") {
+            Some(fee) => match fee.strip_prefix('￥').or_else(|| fee.strip_prefix('¥')) {
+                Some(amount) => (Some(amount.trim().to_owned()), DEFAULT_CURRENCY.to_owned()),
+                None => (Some(fee), DEFAULT_CURRENCY.to_owned()),
+            },
+            None => (None, DEFAULT_CURRENCY.to_owned()),
+        };
+        let status = match xml_text(&pay_info, "paysubtype") {
+            Some(pay_subtype) => MoneyStatus::from_pay_subtype(&pay_subtype),
+            None => MoneyStatus::Unknown,
+        };
+        let memo = xml_text(&pay_info, "pay_memo").or_else(|| xml_text(&pay_info, "sendertitle"));
+        Ok(Self {
+            amount,
+            currency,
+            status,
+            memo,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFER_XML: &str = r#"<msg>
+<appmsg appid="" sdkver="">
+    <title><![CDATA[微信转账]]></title>
+    <des><![CDATA[收到转账0.10元。如需收钱，请点此升级至最新版本]]></des>
+    <type>2000</type>
+    <wcpayinfo>
+        <paysubtype>1</paysubtype>
+        <feedesc><![CDATA[￥0.10]]></feedesc>
+        <transcationid><![CDATA[100005010120200311000511]]></transcationid>
+        <transferid><![CDATA[1000050001202003111000277]]></transferid>
+        <invalidtime><![CDATA[1584000000]]></invalidtime>
+        <pay_memo><![CDATA[lunch]]></pay_memo>
+    </wcpayinfo>
+</appmsg>
+</msg>"#;
+
+    const RED_ENVELOPE_XML: &str = r#"<msg>
+<appmsg appid="" sdkver="">
+    <type>2001</type>
+    <wcpayinfo>
+        <templateid><![CDATA[7a2a165d31da7fce6dd77e05c300028a]]></templateid>
+        <sendertitle><![CDATA[恭喜发财，大吉大利]]></sendertitle>
+        <paysubtype>0</paysubtype>
+    </wcpayinfo>
+</appmsg>
+</msg>"#;
+
+    #[test]
+    fn can_parse_transfer_xml() {
+        let payload = MoneyPayload::from_xml(TRANSFER_XML).unwrap();
+        assert_eq!(payload.amount, Some("0.10".to_owned()));
+        assert_eq!(payload.currency, "CNY");
+        assert_eq!(payload.status, MoneyStatus::Pending);
+        assert_eq!(payload.memo, Some("lunch".to_owned()));
+    }
+
+    #[test]
+    fn can_parse_red_envelope_xml() {
+        let payload = MoneyPayload::from_xml(RED_ENVELOPE_XML).unwrap();
+        assert_eq!(payload.amount, None);
+        assert_eq!(payload.status, MoneyStatus::Unknown);
+        assert_eq!(payload.memo, Some("恭喜发财，大吉大利".to_owned()));
+    }
+
+    #[test]
+    fn cannot_parse_xml_without_pay_info() {
+        assert!(matches!(
+            MoneyPayload::from_xml("<msg><appmsg><type>5</type></appmsg></msg>"),
+            Err(PuppetError::InvalidArgument(_))
+        ));
+    }
+}
@@ -1,5 +1,295 @@
+use std::time::Duration;
+
+use crate::PuppetError;
+
+#[derive(Debug, Clone, Default)]
 pub struct PuppetOptions {
     pub endpoint: Option<String>,
     pub timeout: Option<u64>,
     pub token: Option<String>,
+    /// Do not send anything, mutating calls are only logged, see `Puppet::with_dry_run`.
+    pub dry_run: bool,
+    /// What to do when the event stream keeps failing, errors are only logged if not given.
+    pub stream_error_policy: Option<StreamErrorPolicy>,
+    /// Refuse to connect to a gateway older than this version, a warning is logged instead if not given.
+    pub min_version: Option<String>,
+    /// Client identifier sent to the gateway and the endpoint discovery service, defaults to
+    /// `rust-wechaty/<version>`.
+    pub user_agent: Option<String>,
+    /// Reconnect if no event, including heartbeats, arrives from the gateway within this interval, the watchdog
+    /// is disabled if not given.
+    pub heartbeat_timeout: Option<Duration>,
+    /// Fraction of the reconnect delay to randomly take off, between 0 and 1, so that bots sharing a gateway do
+    /// not all reconnect at once when it restarts, reconnects are not jittered if not given.
+    pub reconnect_jitter: Option<f64>,
+    /// Field name casing of the json payloads sent to the gateway, such as mini programs and url links, defaults
+    /// to snake_case.
+    pub json_casing: JsonCasing,
+}
+
+impl PuppetOptions {
+    /// Set the field name casing of the json payloads sent to the gateway.
+    pub fn with_json_casing(mut self, json_casing: JsonCasing) -> Self {
+        self.json_casing = json_casing;
+        self
+    }
+
+    /// Check that the options are coherent before connecting, so that mistakes are reported up front.
+    pub fn validate(&self) -> Result<(), PuppetError> {
+        let has_endpoint = self
+            .endpoint
+            .as_ref()
+            .is_some_and(|endpoint| !endpoint.trim().is_empty());
+        let has_token = self.token.as_ref().is_some_and(|token| !token.trim().is_empty());
+        if !has_endpoint && !has_token {
+            return Err(PuppetError::InvalidConfig(
+                "either endpoint or token must be given".to_owned(),
+            ));
+        }
+        if let Some(endpoint) = self.endpoint.as_ref().filter(|_| has_endpoint) {
+            match endpoint.split_once("://") {
+                Some((scheme, address)) if !scheme.is_empty() && !address.is_empty() => {}
+                _ => {
+                    return Err(PuppetError::InvalidConfig(format!(
+                        "endpoint {} must be a url like http://127.0.0.1:8788",
+                        endpoint
+                    )))
+                }
+            }
+        }
+        if self.timeout == Some(0) {
+            return Err(PuppetError::InvalidConfig("timeout must be positive".to_owned()));
+        }
+        if self.heartbeat_timeout == Some(Duration::ZERO) {
+            return Err(PuppetError::InvalidConfig(
+                "heartbeat timeout must be positive".to_owned(),
+            ));
+        }
+        if self
+            .reconnect_jitter
+            .is_some_and(|jitter| !(0.0..=1.0).contains(&jitter))
+        {
+            return Err(PuppetError::InvalidConfig(
+                "reconnect jitter must be between 0 and 1".to_owned(),
+            ));
+        }
+        if let Some(policy) = &self.stream_error_policy {
+            if policy.max_errors == 0 {
+                return Err(PuppetError::InvalidConfig(
+                    "max errors of the stream error policy must be positive".to_owned(),
+                ));
+            }
+            if policy.window == Duration::ZERO {
+                return Err(PuppetError::InvalidConfig(
+                    "window of the stream error policy must be positive".to_owned(),
+                ));
+            }
+        }
+        if self
+            .user_agent
+            .as_ref()
+            .is_some_and(|user_agent| user_agent.trim().is_empty())
+        {
+            return Err(PuppetError::InvalidConfig("user agent must not be empty".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+/// Field name casing of json payloads exchanged with the gateway.
+///
+/// Payloads are sent in snake_case as they always have been, recent gateways which expect camelCase need
+/// `JsonCasing::CamelCase`. Payloads in either casing are accepted when reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonCasing {
+    #[default]
+    SnakeCase,
+    CamelCase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamErrorAction {
+    /// Drop the current event stream and subscribe again.
+    Reconnect,
+    /// Stop the event stream and send a fatal error, see `Puppet::fatal_error`.
+    Stop,
+}
+
+/// Fire `action` after `max_errors` consecutive event stream errors within `window`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamErrorPolicy {
+    pub max_errors: usize,
+    pub window: Duration,
+    pub action: StreamErrorAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PuppetFeature {
+    ContactSelf,
+    Tag,
+    Friendship,
+    RoomInvitation,
+    RoomAnnounce,
+    File,
+    MiniProgram,
+    UrlLink,
+    Moment,
+}
+
+/// Features supported by a puppet implementation.
+///
+/// The default value supports everything, so that puppets only have to opt out of the features they lack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PuppetCapabilities {
+    pub supports_contact_self: bool,
+    pub supports_tag: bool,
+    pub supports_friendship: bool,
+    pub supports_room_invitation: bool,
+    pub supports_room_announce: bool,
+    pub supports_file: bool,
+    pub supports_mini_program: bool,
+    pub supports_url_link: bool,
+    pub supports_moment: bool,
+}
+
+impl Default for PuppetCapabilities {
+    fn default() -> Self {
+        Self {
+            supports_contact_self: true,
+            supports_tag: true,
+            supports_friendship: true,
+            supports_room_invitation: true,
+            supports_room_announce: true,
+            supports_file: true,
+            supports_mini_program: true,
+            supports_url_link: true,
+            supports_moment: true,
+        }
+    }
+}
+
+impl PuppetCapabilities {
+    pub fn supports(&self, feature: PuppetFeature) -> bool {
+        match feature {
+            PuppetFeature::ContactSelf => self.supports_contact_self,
+            PuppetFeature::Tag => self.supports_tag,
+            PuppetFeature::Friendship => self.supports_friendship,
+            PuppetFeature::RoomInvitation => self.supports_room_invitation,
+            PuppetFeature::RoomAnnounce => self.supports_room_announce,
+            PuppetFeature::File => self.supports_file,
+            PuppetFeature::MiniProgram => self.supports_mini_program,
+            PuppetFeature::UrlLink => self.supports_url_link,
+            PuppetFeature::Moment => self.supports_moment,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> PuppetOptions {
+        PuppetOptions {
+            endpoint: Some("http://127.0.0.1:8788".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn invalid_reason(options: PuppetOptions) -> String {
+        match options.validate() {
+            Err(PuppetError::InvalidConfig(reason)) => reason,
+            result => panic!("expected an invalid config, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn default_capabilities_support_everything() {
+        let capabilities = PuppetCapabilities::default();
+        assert!(capabilities.supports(PuppetFeature::File));
+        assert!(capabilities.supports(PuppetFeature::Moment));
+    }
+
+    #[test]
+    fn can_validate_options() {
+        assert!(options().validate().is_ok());
+        assert!(PuppetOptions {
+            token: Some("token".to_owned()),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn endpoint_or_token_is_required() {
+        assert!(invalid_reason(PuppetOptions::default()).contains("endpoint or token"));
+        assert!(invalid_reason(PuppetOptions {
+            endpoint: Some(" ".to_owned()),
+            token: Some(String::new()),
+            ..Default::default()
+        })
+        .contains("endpoint or token"));
+    }
+
+    #[test]
+    fn endpoint_must_be_a_url() {
+        for endpoint in ["127.0.0.1:8788", "://127.0.0.1:8788", "http://"] {
+            let reason = invalid_reason(PuppetOptions {
+                endpoint: Some(endpoint.to_owned()),
+                ..Default::default()
+            });
+            assert!(reason.contains("must be a url"), "{}", reason);
+        }
+    }
+
+    #[test]
+    fn durations_and_limits_must_be_positive() {
+        assert!(invalid_reason(PuppetOptions {
+            timeout: Some(0),
+            ..options()
+        })
+        .starts_with("timeout"));
+        assert!(invalid_reason(PuppetOptions {
+            heartbeat_timeout: Some(Duration::ZERO),
+            ..options()
+        })
+        .starts_with("heartbeat timeout"));
+        for jitter in [-0.1, 1.5, f64::NAN] {
+            assert!(invalid_reason(PuppetOptions {
+                reconnect_jitter: Some(jitter),
+                ..options()
+            })
+            .starts_with("reconnect jitter"));
+        }
+        let policy = StreamErrorPolicy {
+            max_errors: 3,
+            window: Duration::from_secs(60),
+            action: StreamErrorAction::Reconnect,
+        };
+        assert!(invalid_reason(PuppetOptions {
+            stream_error_policy: Some(StreamErrorPolicy {
+                max_errors: 0,
+                ..policy.clone()
+            }),
+            ..options()
+        })
+        .starts_with("max errors"));
+        assert!(invalid_reason(PuppetOptions {
+            stream_error_policy: Some(StreamErrorPolicy {
+                window: Duration::ZERO,
+                ..policy
+            }),
+            ..options()
+        })
+        .starts_with("window"));
+    }
+
+    #[test]
+    fn user_agent_must_not_be_empty() {
+        assert!(invalid_reason(PuppetOptions {
+            user_agent: Some(String::new()),
+            ..options()
+        })
+        .starts_with("user agent"));
+    }
 }
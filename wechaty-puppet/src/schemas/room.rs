@@ -1,4 +1,5 @@
 use regex::Regex;
+use serde::Serialize;
 
 #[derive(Default, Debug, Clone)]
 pub struct RoomMemberQueryFilter {
@@ -15,7 +16,7 @@ pub struct RoomQueryFilter {
     pub topic_regex: Option<Regex>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RoomPayload {
     pub id: String,
     pub topic: String,
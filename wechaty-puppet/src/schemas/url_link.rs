@@ -1,9 +1,91 @@
 use serde::{Deserialize, Serialize};
 
+use crate::PuppetError;
+
+/// Field names are serialized in snake_case, camelCase names are accepted as well, see `JsonCasing`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UrlLinkPayload {
     pub description: Option<String>,
+    #[serde(alias = "thumbnailUrl")]
     pub thumbnail_url: Option<String>,
     pub title: String,
     pub url: String,
 }
+
+impl UrlLinkPayload {
+    /// Create a url link payload, the url must not be empty.
+    pub fn new(title: String, url: String) -> Result<Self, PuppetError> {
+        if url.trim().is_empty() {
+            return Err(PuppetError::InvalidArgument(
+                "Url of a url link must not be empty".to_owned(),
+            ));
+        }
+        Ok(Self {
+            description: None,
+            thumbnail_url: None,
+            title,
+            url,
+        })
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    pub fn with_thumbnail(mut self, thumbnail_url: String) -> Self {
+        self.thumbnail_url = Some(thumbnail_url);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, to_value};
+
+    use super::*;
+
+    #[test]
+    fn can_build_url_link_payload() {
+        let payload = UrlLinkPayload::new("Wechaty".to_owned(), "https://wechaty.js.org".to_owned())
+            .unwrap()
+            .with_description("Conversational RPA SDK".to_owned())
+            .with_thumbnail("https://wechaty.js.org/img/icon.png".to_owned());
+        assert_eq!(payload.title, "Wechaty");
+        assert_eq!(payload.url, "https://wechaty.js.org");
+        assert_eq!(payload.description, Some("Conversational RPA SDK".to_owned()));
+        assert_eq!(
+            payload.thumbnail_url,
+            Some("https://wechaty.js.org/img/icon.png".to_owned())
+        );
+    }
+
+    #[test]
+    fn cannot_build_url_link_payload_without_url() {
+        assert!(matches!(
+            UrlLinkPayload::new("Wechaty".to_owned(), " ".to_owned()),
+            Err(PuppetError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn url_link_payload_uses_snake_case_field_names() {
+        let payload = UrlLinkPayload::new("Wechaty".to_owned(), "https://wechaty.js.org".to_owned())
+            .unwrap()
+            .with_thumbnail("https://wechaty.js.org/img/icon.png".to_owned());
+        assert_eq!(
+            to_value(&payload).unwrap(),
+            json!({
+                "description": null,
+                "thumbnail_url": "https://wechaty.js.org/img/icon.png",
+                "title": "Wechaty",
+                "url": "https://wechaty.js.org",
+            })
+        );
+        let camel_case: UrlLinkPayload = serde_json::from_str(
+            r#"{"thumbnailUrl": "https://wechaty.js.org/img/icon.png", "title": "Wechaty", "url": "https://wechaty.js.org"}"#,
+        )
+        .unwrap();
+        assert_eq!(camel_case.thumbnail_url, payload.thumbnail_url);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Mutex as AsyncMutex;
+
+/// Per-conversation FIFO queue.
+///
+/// Tasks sent to the same conversation run one at a time in the order they are enqueued, while tasks sent to
+/// different conversations run concurrently. `tokio::sync::Mutex` is fair, which is what guarantees the order.
+#[derive(Clone, Default)]
+pub(crate) struct SendQueue {
+    queues: Arc<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>>,
+    depth: Arc<AtomicUsize>,
+}
+
+/// Count a task out of the queue even if it is cancelled.
+struct DepthGuard<'a>(&'a AtomicUsize);
+
+impl Drop for DepthGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Remove the queue of a conversation once no other task is holding or waiting for it, even if the task is cancelled.
+struct QueueGuard<'a> {
+    queues: &'a Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    conversation_id: String,
+    queue: Arc<AsyncMutex<()>>,
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        // Only the map and this guard are left holding the queue.
+        if Arc::strong_count(&self.queue) == 2 {
+            queues.remove(&self.conversation_id);
+        }
+    }
+}
+
+impl SendQueue {
+    /// Number of tasks waiting or running in all conversations.
+    pub(crate) fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn run<F>(&self, conversation_id: String, task: F) -> F::Output
+    where
+        F: Future,
+    {
+        let queue = self
+            .queues
+            .lock()
+            .unwrap()
+            .entry(conversation_id.clone())
+            .or_default()
+            .clone();
+        let queue = QueueGuard {
+            queues: &self.queues,
+            conversation_id,
+            queue,
+        };
+        self.depth.fetch_add(1, Ordering::SeqCst);
+        let _depth = DepthGuard(&self.depth);
+        let _guard = queue.queue.lock().await;
+        task.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future::join_all;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn tasks_in_same_conversation_run_in_order() {
+        let queue = SendQueue::default();
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let tasks = (0..5u64).map(|i| {
+            let delivered = delivered.clone();
+            queue.run("room".to_owned(), async move {
+                // Earlier messages take longer, so they would be overtaken without the queue.
+                actix_rt::time::sleep(Duration::from_millis((5 - i) * 10)).await;
+                let message_id = format!("message-{}", i);
+                delivered.lock().unwrap().push(message_id.clone());
+                message_id
+            })
+        });
+
+        let message_id_list = join_all(tasks).await;
+        let expected: Vec<String> = (0..5).map(|i| format!("message-{}", i)).collect();
+        assert_eq!(message_id_list, expected);
+        assert_eq!(*delivered.lock().unwrap(), expected);
+        assert!(queue.queues.lock().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn depth_counts_waiting_and_running_tasks() {
+        let queue = SendQueue::default();
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let blocked = queue.run("room".to_owned(), async move {
+            receiver.await.unwrap_or_default();
+        });
+        let waiting = queue.run("room".to_owned(), async {});
+        let other = queue.run("contact".to_owned(), async {});
+        let all = futures::future::join3(blocked, waiting, other);
+        futures::pin_mut!(all);
+
+        assert!(futures::poll!(all.as_mut()).is_pending());
+        assert_eq!(queue.depth(), 2);
+        sender.send(()).unwrap();
+        all.await;
+        assert_eq!(queue.depth(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn cancelled_tasks_remove_their_queue() {
+        let queue = SendQueue::default();
+        let mut blocked = Box::pin(queue.run("room".to_owned(), futures::future::pending::<()>()));
+        let mut waiting = Box::pin(queue.run("room".to_owned(), async {}));
+        assert!(futures::poll!(blocked.as_mut()).is_pending());
+        assert!(futures::poll!(waiting.as_mut()).is_pending());
+
+        drop(waiting);
+        assert_eq!(queue.queues.lock().unwrap().len(), 1);
+        drop(blocked);
+        assert!(queue.queues.lock().unwrap().is_empty());
+        assert_eq!(queue.depth(), 0);
+    }
+}
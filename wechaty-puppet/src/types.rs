@@ -22,6 +22,11 @@ where
     pub async fn run(&self, t: Payload, ctx: Context) -> Result {
         (self.func)(t, ctx).await
     }
+
+    /// Call the function, the returned future does not borrow the function, e.g. for wrapping it in another one.
+    pub fn call(&self, t: Payload, ctx: Context) -> BoxFuture<'static, Result> {
+        (self.func)(t, ctx)
+    }
 }
 
 pub trait IntoAsyncFnPtr<Payload, Context, Result>
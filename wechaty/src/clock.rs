@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use futures::future::BoxFuture;
+use tokio::sync::Notify;
+
+/// Source of the current time, used for message ages, cache expiry and rate limits.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    /// Wait until the clock has moved on by `duration`, which takes real time unless overridden.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(actix_rt::time::sleep(duration))
+    }
+}
+
+/// The system clock, which is used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+    moved: Notify,
+}
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+            moved: Notify::new(),
+        }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+        self.moved.notify_waiters();
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+        self.moved.notify_waiters();
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+
+    /// Wait until the clock is set or advanced past the deadline, no matter how much real time passes.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        let deadline = self.now() + duration;
+        Box::pin(async move {
+            loop {
+                // Registered before checking, so that a move in between is not missed.
+                let moved = self.moved.notified();
+                if self.now() >= deadline {
+                    break;
+                }
+                moved.await;
+            }
+        })
+    }
+}
@@ -0,0 +1,457 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use log::{debug, error};
+use regex::Regex;
+use tokio::signal;
+use wechaty_puppet::{Puppet, PuppetImpl};
+
+use crate::{
+    ConnectionChangePayload, DirtyPayload, DongPayload, ErrorPayload, EventListener, FriendshipPayload,
+    HeartbeatPayload, LoginPayload, LogoutPayload, MessageMatchOptions, MessagePayload, ReadyPayload, ResetPayload,
+    RoomInvitePayload, RoomJoinPayload, RoomLeavePayload, RoomTopicPayload, ScanPayload, Wechaty, WechatyContext,
+    WechatyError,
+};
+
+type ClusterHandler<T, Payload> = Box<dyn Fn(Payload, WechatyContext<T>) -> BoxFuture<'static, ()> + Send>;
+type Registration<T> = Box<dyn Fn(&mut Wechaty<T>, String) + Send + Sync>;
+
+/// Multiple Wechaty instances, one for each account, sharing the same handlers.
+///
+/// Handlers receive the account id (as given to `add`) as the third argument, so that they can tell which
+/// account an event comes from. Handlers apply to every account, including the ones added after the handlers are
+/// registered.
+pub struct WechatyCluster<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    bots: Vec<(String, Wechaty<T>)>,
+    registrations: Vec<Registration<T>>,
+}
+
+impl<T> Default for WechatyCluster<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> WechatyCluster<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    pub fn new() -> Self {
+        Self {
+            bots: vec![],
+            registrations: vec![],
+        }
+    }
+
+    /// Add an account to the cluster, with the handlers registered so far.
+    ///
+    /// Return `WechatyError::InvalidOperation` if the account id is already taken, as handlers could not tell the two
+    /// accounts apart.
+    pub fn add(&mut self, account_id: String, puppet: Puppet<T>) -> Result<&mut Self, WechatyError> {
+        debug!("WechatyCluster.add(account_id = {})", account_id);
+        if self.bots.iter().any(|(id, _)| *id == account_id) {
+            return Err(WechatyError::InvalidOperation(format!(
+                "Account {} is already in the cluster",
+                account_id
+            )));
+        }
+        let mut bot = Wechaty::with_name(puppet, format!("Wechaty({})", account_id));
+        for registration in self.registrations.iter() {
+            registration(&mut bot, account_id.clone());
+        }
+        self.bots.push((account_id, bot));
+        Ok(self)
+    }
+
+    /// Ids of all the accounts in the cluster.
+    pub fn account_id_list(&self) -> Vec<String> {
+        self.bots.iter().map(|(account_id, _)| account_id.clone()).collect()
+    }
+
+    fn on_event<Payload, F, Fut, R>(&mut self, handler: F, register: R) -> &mut Self
+    where
+        Payload: 'static,
+        F: Fn(Payload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+        R: Fn(&mut Wechaty<T>, ClusterHandler<T, Payload>) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let registration: Registration<T> = Box::new(move |bot, account_id| {
+            let handler = handler.clone();
+            register(
+                bot,
+                Box::new(move |payload, ctx| Box::pin(handler(payload, ctx, account_id.clone()))),
+            );
+        });
+        for (account_id, bot) in self.bots.iter_mut() {
+            registration(bot, account_id.clone());
+        }
+        self.registrations.push(registration);
+        self
+    }
+
+    pub fn on_connection_change<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ConnectionChangePayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_connection_change(handler);
+        })
+    }
+
+    pub fn on_dirty<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(DirtyPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_dirty(handler);
+        })
+    }
+
+    pub fn on_dong<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(DongPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_dong(handler);
+        })
+    }
+
+    pub fn on_error<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ErrorPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_error(handler);
+        })
+    }
+
+    pub fn on_friendship<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(FriendshipPayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_friendship(handler);
+        })
+    }
+
+    pub fn on_heartbeat<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(HeartbeatPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_heartbeat(handler);
+        })
+    }
+
+    pub fn on_login<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(LoginPayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_login(handler);
+        })
+    }
+
+    pub fn on_logout<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(LogoutPayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_logout(handler);
+        })
+    }
+
+    pub fn on_message<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(MessagePayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_message(handler);
+        })
+    }
+
+    /// Handle the messages whose text matches a pattern, see `EventListener::on_message_matching`.
+    pub fn on_message_matching<F, Fut>(&mut self, pattern: Regex, handler: F) -> &mut Self
+    where
+        F: Fn(MessagePayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_message_matching_with_options(pattern, handler, MessageMatchOptions::default())
+    }
+
+    /// Handle the messages whose text matches a pattern, see `EventListener::on_message_matching_with_options`.
+    ///
+    /// The limit in the options applies to each account separately.
+    pub fn on_message_matching_with_options<F, Fut>(
+        &mut self,
+        pattern: Regex,
+        handler: F,
+        options: MessageMatchOptions,
+    ) -> &mut Self
+    where
+        F: Fn(MessagePayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, move |bot, handler| {
+            bot.on_message_matching_with_options(pattern.clone(), handler, options.clone());
+        })
+    }
+
+    pub fn on_ready<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ReadyPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_ready(handler);
+        })
+    }
+
+    pub fn on_reset<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ResetPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_reset(handler);
+        })
+    }
+
+    pub fn on_room_invite<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(RoomInvitePayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_room_invite(handler);
+        })
+    }
+
+    pub fn on_room_join<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(RoomJoinPayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_room_join(handler);
+        })
+    }
+
+    pub fn on_room_leave<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(RoomLeavePayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_room_leave(handler);
+        })
+    }
+
+    pub fn on_room_topic<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(RoomTopicPayload<T>, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_room_topic(handler);
+        })
+    }
+
+    pub fn on_scan<F, Fut>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(ScanPayload, WechatyContext<T>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_event(handler, |bot, handler| {
+            bot.on_scan(handler);
+        })
+    }
+
+    /// Run the cluster until ctrl-c is pressed, then stop every account, see `stop`.
+    pub async fn start(&self) -> Result<(), WechatyError> {
+        for (_, bot) in self.bots.iter() {
+            bot.ctx().set_cancelled(false);
+        }
+        signal::ctrl_c()
+            .await
+            .expect("Failed to establish the listener for graceful exit");
+        self.stop().await
+    }
+
+    /// Stop the bot of every account, see `Wechaty::stop`.
+    ///
+    /// All bots are stopped even if some of them fail, the first error is returned.
+    pub async fn stop(&self) -> Result<(), WechatyError> {
+        debug!("WechatyCluster.stop()");
+        let mut result = Ok(());
+        for (account_id, bot) in self.bots.iter() {
+            if let Err(e) = bot.stop().await {
+                error!("Failed to stop account {}, reason: {}", account_id, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+    use wechaty_puppet::{
+        fixtures, EventDirtyPayload, EventDongPayload, EventMessagePayload, PayloadType, PuppetEvent,
+    };
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+
+    fn message_event(message_id: &str) -> PuppetEvent {
+        PuppetEvent::Message(EventMessagePayload {
+            message_id: message_id.to_owned(),
+        })
+    }
+
+    #[actix_rt::test]
+    async fn messages_are_tagged_by_account() {
+        let puppet_a = Puppet::new(PuppetMock::default());
+        let puppet_b = Puppet::new(PuppetMock::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut cluster = WechatyCluster::new();
+        cluster.add("a".to_owned(), puppet_a.clone()).unwrap();
+        cluster.on_message(move |payload: MessagePayload<PuppetMock>, _ctx, account_id| {
+            sender.send((account_id, payload.message.id())).unwrap();
+            async {}
+        });
+        // Accounts added later get the handlers registered so far.
+        cluster.add("b".to_owned(), puppet_b.clone()).unwrap();
+        for (_, bot) in cluster.bots.iter() {
+            for message_id in ["message-a", "message-b"].iter() {
+                bot.ctx()
+                    .messages()
+                    .insert(message_id.to_string(), fixtures::message(message_id, "", "hello"));
+            }
+        }
+
+        puppet_a.self_addr().do_send(message_event("message-a")).unwrap();
+        puppet_b.self_addr().do_send(message_event("message-b")).unwrap();
+
+        let mut received = vec![receiver.recv().await.unwrap(), receiver.recv().await.unwrap()];
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                ("a".to_owned(), "message-a".to_owned()),
+                ("b".to_owned(), "message-b".to_owned()),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn dongs_are_tagged_by_account() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut cluster = WechatyCluster::new();
+        cluster.on_dong(move |payload: DongPayload, _ctx, account_id| {
+            sender.send((account_id, payload.data)).unwrap();
+            async {}
+        });
+        cluster.add("a".to_owned(), puppet.clone()).unwrap();
+
+        puppet
+            .self_addr()
+            .do_send(PuppetEvent::Dong(EventDongPayload {
+                data: "pong".to_owned(),
+            }))
+            .unwrap();
+
+        assert_eq!(receiver.recv().await, Some(("a".to_owned(), "pong".to_owned())));
+    }
+
+    #[actix_rt::test]
+    async fn dirty_events_are_tagged_by_account() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut cluster = WechatyCluster::new();
+        cluster.add("a".to_owned(), puppet.clone()).unwrap();
+        cluster.on_dirty(move |payload: DirtyPayload, _ctx, account_id| {
+            sender.send((account_id, payload.payload_id)).unwrap();
+            async {}
+        });
+
+        puppet
+            .self_addr()
+            .do_send(PuppetEvent::Dirty(EventDirtyPayload {
+                payload_type: PayloadType::Contact,
+                payload_id: "alice".to_owned(),
+            }))
+            .unwrap();
+
+        assert_eq!(receiver.recv().await, Some(("a".to_owned(), "alice".to_owned())));
+    }
+
+    #[actix_rt::test]
+    async fn only_matching_messages_are_handled() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut cluster = WechatyCluster::new();
+        cluster.add("a".to_owned(), puppet.clone()).unwrap();
+        cluster.on_message_matching(
+            Regex::new("^ding$").unwrap(),
+            move |payload: MessagePayload<PuppetMock>, _ctx, account_id| {
+                sender.send((account_id, payload.message.id())).unwrap();
+                async {}
+            },
+        );
+        let ctx = cluster.bots[0].1.ctx();
+        ctx.messages()
+            .insert("hello".to_owned(), fixtures::message("hello", "alice", "hello"));
+        ctx.messages()
+            .insert("ding".to_owned(), fixtures::message("ding", "alice", "ding"));
+
+        // Events are handled in order, so the first message has been filtered out once the second one arrives.
+        puppet.self_addr().do_send(message_event("hello")).unwrap();
+        puppet.self_addr().do_send(message_event("ding")).unwrap();
+
+        assert_eq!(receiver.recv().await, Some(("a".to_owned(), "ding".to_owned())));
+    }
+
+    #[actix_rt::test]
+    async fn account_ids_must_be_unique() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let mut cluster = WechatyCluster::new();
+        cluster.add("a".to_owned(), puppet.clone()).unwrap();
+        assert!(matches!(
+            cluster.add("a".to_owned(), puppet.clone()),
+            Err(WechatyError::InvalidOperation(_))
+        ));
+        assert_eq!(cluster.account_id_list(), vec!["a".to_owned()]);
+    }
+
+    #[actix_rt::test]
+    async fn stopping_stops_every_account() {
+        let mut cluster = WechatyCluster::new();
+        cluster.add("a".to_owned(), Puppet::new(PuppetMock::default())).unwrap();
+        cluster.add("b".to_owned(), Puppet::new(PuppetMock::default())).unwrap();
+
+        cluster.stop().await.unwrap();
+        assert!(cluster.bots.iter().all(|(_, bot)| bot.ctx().is_cancelled()));
+    }
+}
@@ -1,14 +1,45 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
 
-use futures::StreamExt;
-use log::{debug, error};
+use futures::{future, Future, Stream, StreamExt};
+use log::{debug, error, warn};
+use lru::LruCache;
+use regex::Regex;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
 use wechaty_puppet::{
-    ContactPayload, ContactQueryFilter, FriendshipPayload, FriendshipSearchQueryFilter, MessagePayload,
-    MessageQueryFilter, Puppet, PuppetImpl, RoomInvitationPayload, RoomPayload, RoomQueryFilter,
+    ContactPayload, ContactQueryFilter, ContactType, ConversationId, EventRoomJoinPayload, EventRoomLeavePayload,
+    FileBox, FriendshipPayload, FriendshipSearchQueryFilter, MessagePayload, MessageQueryFilter, PayloadType, Puppet,
+    PuppetError, PuppetFeature, PuppetImpl, RoomInvitationPayload, RoomPayload, RoomQueryFilter,
 };
 
-use crate::{Contact, Friendship, IntoContact, Message, Room, WechatyError};
+use crate::clock::{Clock, SystemClock};
+use crate::outbox::Outbox;
+use crate::traits::talkable::send_text_unchecked;
+use crate::user::entity::EntityPayload;
+use crate::{room_member_diff, Contact, Entity, Favorite, Friendship, IntoContact, Message, Room, Tag, WechatyError};
+
+const SELF_QR_CODE_TTL: Duration = Duration::from_secs(60);
+const AVATAR_TTL: Duration = Duration::from_secs(300);
+const MESSAGE_FEED_CAPACITY: usize = 256;
+const DEFAULT_ROOM_ECHO_WINDOW: Duration = Duration::from_secs(3);
+const DEFAULT_AVATAR_PREFETCH_INTERVAL: Duration = Duration::from_millis(200);
+const MENTION_NAMES_CAP: usize = 500;
+
+/// Check whether a url looks like a room QR code or invitation url, i.e. an http or https url with a host.
+fn is_room_url(url: &str) -> bool {
+    if url.chars().any(char::is_whitespace) {
+        return false;
+    }
+    match url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")) {
+        Some(rest) => !rest.split('/').next().unwrap_or_default().is_empty(),
+        None => false,
+    }
+}
 
 #[derive(Clone)]
 pub struct WechatyContext<T>
@@ -20,8 +51,44 @@ where
     contacts_: Arc<Mutex<HashMap<String, ContactPayload>>>,
     friendships_: Arc<Mutex<HashMap<String, FriendshipPayload>>>,
     messages_: Arc<Mutex<HashMap<String, MessagePayload>>>,
+    mention_names_: Arc<Mutex<LruCache<String, Vec<String>>>>,
     rooms_: Arc<Mutex<HashMap<String, RoomPayload>>>,
     room_invitations_: Arc<Mutex<HashMap<String, RoomInvitationPayload>>>,
+    track_contact_history_: Arc<AtomicBool>,
+    reconcile_room_members_: Arc<AtomicBool>,
+    lazy_entities_: Arc<AtomicBool>,
+    track_unread_: Arc<AtomicBool>,
+    ignore_self_messages_: Arc<AtomicBool>,
+    verbose_contact_identity_: Arc<AtomicBool>,
+    cancelled_: Arc<AtomicBool>,
+    unread_: Arc<Mutex<HashMap<String, usize>>>,
+    contact_history_: Arc<Mutex<HashMap<String, ContactHistory>>>,
+    self_qr_code_: Arc<Mutex<Option<(String, SystemTime)>>>,
+    restricted_contact_types_: Arc<Mutex<Vec<ContactType>>>,
+    message_feed_: broadcast::Sender<Message<T>>,
+    room_echo_window_: Arc<Mutex<Duration>>,
+    room_echoes_: Arc<Mutex<HashMap<String, (String, SystemTime)>>>,
+    clock_: Arc<Mutex<Arc<dyn Clock>>>,
+    avatars_: Arc<Mutex<HashMap<String, (FileBox, SystemTime)>>>,
+    avatar_prefetch_: Arc<Mutex<AvatarPrefetch>>,
+    avatar_prefetch_interval_: Arc<Mutex<Duration>>,
+    outbox_: Arc<Mutex<Outbox>>,
+    started_at_: Arc<Mutex<SystemTime>>,
+    logged_in_at_: Arc<Mutex<Option<SystemTime>>>,
+}
+
+/// Contacts whose avatars are waiting to be prefetched, see `WechatyContext::prefetch_avatars`.
+#[derive(Default)]
+struct AvatarPrefetch {
+    queue: VecDeque<String>,
+    running: bool,
+}
+
+/// Last seen name and alias of a contact before they were changed.
+#[derive(Clone, Debug, Default)]
+struct ContactHistory {
+    previous_name: Option<String>,
+    previous_alias: Option<String>,
 }
 
 impl<T> WechatyContext<T>
@@ -35,8 +102,30 @@ where
             contacts_: Arc::new(Mutex::new(Default::default())),
             friendships_: Arc::new(Mutex::new(Default::default())),
             messages_: Arc::new(Mutex::new(Default::default())),
+            mention_names_: Arc::new(Mutex::new(LruCache::new(MENTION_NAMES_CAP))),
             rooms_: Arc::new(Mutex::new(Default::default())),
             room_invitations_: Arc::new(Mutex::new(Default::default())),
+            track_contact_history_: Arc::new(AtomicBool::new(false)),
+            reconcile_room_members_: Arc::new(AtomicBool::new(false)),
+            lazy_entities_: Arc::new(AtomicBool::new(false)),
+            track_unread_: Arc::new(AtomicBool::new(false)),
+            ignore_self_messages_: Arc::new(AtomicBool::new(false)),
+            verbose_contact_identity_: Arc::new(AtomicBool::new(false)),
+            cancelled_: Arc::new(AtomicBool::new(false)),
+            unread_: Arc::new(Mutex::new(Default::default())),
+            contact_history_: Arc::new(Mutex::new(Default::default())),
+            self_qr_code_: Arc::new(Mutex::new(None)),
+            restricted_contact_types_: Arc::new(Mutex::new(vec![ContactType::Official])),
+            message_feed_: broadcast::channel(MESSAGE_FEED_CAPACITY).0,
+            room_echo_window_: Arc::new(Mutex::new(DEFAULT_ROOM_ECHO_WINDOW)),
+            room_echoes_: Arc::new(Mutex::new(Default::default())),
+            clock_: Arc::new(Mutex::new(Arc::new(SystemClock))),
+            avatars_: Arc::new(Mutex::new(Default::default())),
+            avatar_prefetch_: Arc::new(Mutex::new(Default::default())),
+            avatar_prefetch_interval_: Arc::new(Mutex::new(DEFAULT_AVATAR_PREFETCH_INTERVAL)),
+            outbox_: Arc::new(Mutex::new(Default::default())),
+            started_at_: Arc::new(Mutex::new(SystemClock.now())),
+            logged_in_at_: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -56,6 +145,11 @@ where
         self.messages_.lock().unwrap()
     }
 
+    /// Resolved mention names of the most recent messages, see `Message::mention_names`.
+    pub(crate) fn mention_names(&self) -> MutexGuard<'_, LruCache<String, Vec<String>>> {
+        self.mention_names_.lock().unwrap()
+    }
+
     pub(crate) fn rooms(&self) -> MutexGuard<HashMap<String, RoomPayload>> {
         self.rooms_.lock().unwrap()
     }
@@ -64,22 +158,578 @@ where
         self.room_invitations_.lock().unwrap()
     }
 
+    /// Publish a received message to all message feeds.
+    pub(crate) fn publish_message(&self, message: Message<T>) {
+        // Sending only fails when there are no feeds, which is fine.
+        self.message_feed_.send(message).ok();
+    }
+
+    /// Subscribe to messages received from now on as a stream, which complements `Wechaty::on_message`.
+    ///
+    /// Messages are only published once the bot subscribes to message events, via `Wechaty::on_message` or
+    /// `Wechaty::message_feed`. A feed that falls behind by more than 256 messages skips the oldest ones.
+    pub fn message_feed(&self) -> impl Stream<Item = Message<T>> {
+        debug!("message_feed()");
+        BroadcastStream::new(self.message_feed_.subscribe()).filter_map(|message| {
+            future::ready(match message {
+                Ok(message) => Some(message),
+                Err(e) => {
+                    warn!("Message feed is lagging behind: {}", e);
+                    None
+                }
+            })
+        })
+    }
+
+    /// Replace the clock, the bot is regarded as started at the current time of the new clock.
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.started_at_.lock().unwrap() = clock.now();
+        *self.clock_.lock().unwrap() = clock;
+    }
+
+    /// Get the current time from the clock, see `Wechaty::with_clock`.
+    pub(crate) fn now(&self) -> SystemTime {
+        self.clock_.lock().unwrap().now()
+    }
+
+    /// Get the time elapsed since the given time by the clock, which is zero if the clock has gone backwards.
+    pub(crate) fn elapsed(&self, since: SystemTime) -> Duration {
+        self.now().duration_since(since).unwrap_or_default()
+    }
+
+    pub(crate) fn set_room_echo_window(&self, window: Duration) {
+        *self.room_echo_window_.lock().unwrap() = window;
+    }
+
+    /// Expect the gateway to echo back the topic the bot set for a room, see `Wechaty::room_echo_window`.
+    pub(crate) fn expect_room_echo(&self, room_id: String, topic: String) {
+        let now = self.now();
+        self.room_echoes_.lock().unwrap().insert(room_id, (topic, now));
+    }
+
+    /// Stop expecting the echo of a topic that could not be set, unless it has already been matched or replaced.
+    pub(crate) fn cancel_room_echo(&self, room_id: &str, topic: &str) {
+        let mut room_echoes = self.room_echoes_.lock().unwrap();
+        if matches!(room_echoes.get(room_id), Some((expected, _)) if expected == topic) {
+            room_echoes.remove(room_id);
+        }
+    }
+
+    /// Check whether a room topic event is the echo of a topic the bot set, each expected echo is matched once.
+    ///
+    /// Only an event carrying the very topic the bot set matches, so a different topic set by someone else within
+    /// the window is still delivered.
+    pub(crate) fn is_room_echo(&self, room_id: &str, new_topic: &str) -> bool {
+        let window = *self.room_echo_window_.lock().unwrap();
+        let mut room_echoes = self.room_echoes_.lock().unwrap();
+        match room_echoes.get(room_id) {
+            Some((topic, changed_at)) if topic == new_topic => {
+                let is_echo = self.elapsed(*changed_at) < window;
+                room_echoes.remove(room_id);
+                is_echo
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn set_lazy_entities(&self, enabled: bool) {
+        self.lazy_entities_.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Check whether entities are handed to event handlers without being synced, see `Wechaty::lazy_entities`.
+    pub(crate) fn lazy_entities(&self) -> bool {
+        self.lazy_entities_.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_ignore_self_messages(&self, enabled: bool) {
+        self.ignore_self_messages_.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Check whether a message should be kept from message handlers, see `Wechaty::ignore_self_messages`.
+    pub(crate) fn is_ignored_self_message(&self, message: &Message<T>) -> bool {
+        if !self.ignore_self_messages_.load(Ordering::SeqCst) {
+            return false;
+        }
+        let from_id = message.from().map(|from| from.id());
+        from_id.is_some() && from_id == self.id()
+    }
+
+    pub(crate) fn set_verbose_contact_identity(&self, enabled: bool) {
+        self.verbose_contact_identity_.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Check whether contacts are displayed with their verbose identity, see `Wechaty::verbose_contact_identity`.
+    pub(crate) fn verbose_contact_identity(&self) -> bool {
+        self.verbose_contact_identity_.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_cancelled(&self, cancelled: bool) {
+        self.cancelled_.store(cancelled, Ordering::SeqCst);
+    }
+
+    /// Check whether the bot is stopping, see `Wechaty::stop`, `warmup` gives up as soon as it notices it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled_.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_track_unread(&self, enabled: bool) {
+        self.track_unread_.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Count a received message as unread in its conversation, unless unread tracking is disabled or the message
+    /// is sent by the bot itself.
+    pub(crate) fn count_unread(&self, message: &Message<T>) {
+        if !self.track_unread_.load(Ordering::SeqCst) {
+            return;
+        }
+        let from_id = message.from().map(|from| from.id());
+        if from_id.is_some() && from_id == self.id() {
+            return;
+        }
+        if let Some(conversation_id) = message.conversation_id() {
+            *self.unread_.lock().unwrap().entry(conversation_id).or_default() += 1;
+        }
+    }
+
+    /// Get the number of unread messages in a conversation, see `Wechaty::track_unread`.
+    pub fn unread_count(&self, conversation_id: &str) -> usize {
+        debug!("unread_count(conversation_id = {})", conversation_id);
+        self.unread_
+            .lock()
+            .unwrap()
+            .get(conversation_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the number of unread messages in all conversations, see `Wechaty::track_unread`.
+    pub fn unread_total(&self) -> usize {
+        debug!("unread_total()");
+        self.unread_.lock().unwrap().values().sum()
+    }
+
+    /// Reset the unread counter of a conversation.
+    pub fn mark_read(&self, conversation_id: &str) {
+        debug!("mark_read(conversation_id = {})", conversation_id);
+        self.unread_.lock().unwrap().remove(conversation_id);
+    }
+
+    pub(crate) fn set_reconcile_room_members(&self, enabled: bool) {
+        self.reconcile_room_members_.store(enabled, Ordering::SeqCst);
+    }
+
+    pub(crate) fn reconciles_room_members(&self) -> bool {
+        self.reconcile_room_members_.load(Ordering::SeqCst)
+    }
+
+    /// Re-fetch the member lists of the cached rooms, and diff them against the cached member ids.
+    ///
+    /// The cached member ids are updated, and a join and a leave event are synthesized for the members that
+    /// joined and left each room since it was cached. The inviter and the remover are unknown, so they are empty.
+    pub(crate) async fn reconcile_room_members(&self) -> (Vec<EventRoomJoinPayload>, Vec<EventRoomLeavePayload>) {
+        debug!("reconcile_room_members()");
+        let room_id_list: Vec<String> = self.rooms().keys().cloned().collect();
+        let timestamp = self
+            .now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut join_list = vec![];
+        let mut leave_list = vec![];
+        for room_id in room_id_list {
+            let member_id_list = match self.puppet_.room_member_list(room_id.clone()).await {
+                Ok(member_id_list) => member_id_list,
+                Err(e) => {
+                    warn!("Failed to reconcile members of room {}: {}", room_id, e);
+                    continue;
+                }
+            };
+            let cached_member_id_list = match self.rooms().get_mut(&room_id) {
+                Some(payload) => std::mem::replace(&mut payload.member_id_list, member_id_list.clone()),
+                None => continue,
+            };
+            let (invitee_id_list, removee_id_list) = room_member_diff(
+                &cached_member_id_list.into_iter().collect(),
+                &member_id_list.into_iter().collect(),
+            );
+            if !invitee_id_list.is_empty() {
+                join_list.push(EventRoomJoinPayload {
+                    invitee_id_list,
+                    inviter_id: String::new(),
+                    room_id: room_id.clone(),
+                    timestamp,
+                });
+            }
+            if !removee_id_list.is_empty() {
+                leave_list.push(EventRoomLeavePayload {
+                    removee_id_list,
+                    remover_id: String::new(),
+                    room_id,
+                    timestamp,
+                });
+            }
+        }
+        (join_list, leave_list)
+    }
+
+    pub(crate) fn set_restricted_contact_types(&self, contact_types: Vec<ContactType>) {
+        *self.restricted_contact_types_.lock().unwrap() = contact_types;
+    }
+
+    /// Check whether messages cannot be sent to contacts of the given type without force.
+    pub(crate) fn is_restricted_contact_type(&self, contact_type: &ContactType) -> bool {
+        self.restricted_contact_types_.lock().unwrap().contains(contact_type)
+    }
+
+    pub(crate) fn set_track_contact_history(&self, enabled: bool) {
+        self.track_contact_history_.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            self.contact_history_.lock().unwrap().clear();
+        }
+    }
+
+    /// Update a contact in the contact store.
+    ///
+    /// If contact history tracking is enabled, the old name and alias are recorded when they are changed.
+    pub(crate) fn update_contact(&self, contact_id: String, payload: ContactPayload) {
+        let old_payload = self.contacts().insert(contact_id.clone(), payload.clone());
+        if !self.track_contact_history_.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Some(old_payload) = old_payload {
+            let mut contact_history = self.contact_history_.lock().unwrap();
+            let history = contact_history.entry(contact_id).or_default();
+            if old_payload.name != payload.name {
+                history.previous_name = Some(old_payload.name);
+            }
+            if old_payload.alias != payload.alias {
+                history.previous_alias = Some(old_payload.alias);
+            }
+        }
+    }
+
+    pub(crate) fn previous_name(&self, contact_id: &str) -> Option<String> {
+        self.contact_history_
+            .lock()
+            .unwrap()
+            .get(contact_id)
+            .and_then(|history| history.previous_name.clone())
+    }
+
+    pub(crate) fn previous_alias(&self, contact_id: &str) -> Option<String> {
+        self.contact_history_
+            .lock()
+            .unwrap()
+            .get(contact_id)
+            .and_then(|history| history.previous_alias.clone())
+    }
+
     pub(crate) fn id(&self) -> Option<String> {
         self.id_.clone()
     }
 
     pub(crate) fn set_id(&mut self, id: String) {
         self.id_ = Some(id);
+        let now = self.now();
+        *self.logged_in_at_.lock().unwrap() = Some(now);
     }
 
     pub(crate) fn clear_id(&mut self) {
         self.id_ = None;
+        self.self_qr_code_.lock().unwrap().take();
+        self.logged_in_at_.lock().unwrap().take();
+    }
+
+    /// Get the time elapsed since the bot was created.
+    pub fn uptime(&self) -> Duration {
+        debug!("uptime()");
+        let started_at = *self.started_at_.lock().unwrap();
+        self.elapsed(started_at)
+    }
+
+    /// Get the time elapsed since the bot logged in, which is `None` if it is not logged in.
+    pub fn session_duration(&self) -> Option<Duration> {
+        debug!("session_duration()");
+        let logged_in_at = *self.logged_in_at_.lock().unwrap();
+        logged_in_at.map(|logged_in_at| self.elapsed(logged_in_at))
+    }
+
+    /// Get the QR code of the logged in account, which is cached for `SELF_QR_CODE_TTL` unless `force` is set.
+    pub(crate) async fn self_qr_code(&self, force: bool) -> Result<String, WechatyError> {
+        debug!("self_qr_code(force = {})", force);
+        if !force {
+            if let Some((qr_code, fetched_at)) = &*self.self_qr_code_.lock().unwrap() {
+                if self.elapsed(*fetched_at) < SELF_QR_CODE_TTL {
+                    return Ok(qr_code.clone());
+                }
+            }
+        }
+        match self.puppet_.contact_self_qr_code().await {
+            Ok(qr_code) => {
+                let now = self.now();
+                *self.self_qr_code_.lock().unwrap() = Some((qr_code.clone(), now));
+                Ok(qr_code)
+            }
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Get the avatar of a contact fetched within `AVATAR_TTL`, if any.
+    fn cached_avatar(&self, contact_id: &str) -> Option<FileBox> {
+        let cached = self.avatars_.lock().unwrap().get(contact_id).cloned();
+        match cached {
+            Some((avatar, fetched_at)) if self.elapsed(fetched_at) < AVATAR_TTL => Some(avatar),
+            _ => None,
+        }
+    }
+
+    /// Get the avatar of a contact, which is cached for `AVATAR_TTL` until the contact is invalidated.
+    pub(crate) async fn contact_avatar(&self, contact_id: String) -> Result<FileBox, WechatyError> {
+        debug!("contact_avatar(contact_id = {})", contact_id);
+        if let Some(avatar) = self.cached_avatar(&contact_id) {
+            return Ok(avatar);
+        }
+        match self.puppet_.contact_avatar(contact_id.clone()).await {
+            Ok(avatar) => {
+                let now = self.now();
+                self.avatars_.lock().unwrap().insert(contact_id, (avatar.clone(), now));
+                Ok(avatar)
+            }
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    pub(crate) fn set_avatar_prefetch_interval(&self, interval: Duration) {
+        debug!("set_avatar_prefetch_interval(interval = {:?})", interval);
+        *self.avatar_prefetch_interval_.lock().unwrap() = interval;
+    }
+
+    /// Fetch the avatars of contacts into the avatar cache in the background, so that `IntoContact::avatar` hits
+    /// the cache later.
+    ///
+    /// Avatars are fetched one at a time, at most one per prefetch interval, see `Wechaty::avatar_prefetch_interval`.
+    /// Contacts whose avatar is cached or already queued are skipped, and the rest of the queue is dropped if the
+    /// puppet reports a rate limit.
+    pub fn prefetch_avatars(&self, contact_ids: Vec<String>) {
+        debug!("prefetch_avatars(contact_ids = {:?})", contact_ids);
+        let mut prefetch = self.avatar_prefetch_.lock().unwrap();
+        for contact_id in contact_ids {
+            if self.cached_avatar(&contact_id).is_none() && !prefetch.queue.contains(&contact_id) {
+                prefetch.queue.push_back(contact_id);
+            }
+        }
+        if prefetch.running || prefetch.queue.is_empty() {
+            return;
+        }
+        prefetch.running = true;
+        let ctx = self.clone();
+        actix_rt::spawn(async move {
+            loop {
+                let contact_id = {
+                    let mut prefetch = ctx.avatar_prefetch_.lock().unwrap();
+                    match prefetch.queue.pop_front() {
+                        Some(contact_id) => contact_id,
+                        None => {
+                            prefetch.running = false;
+                            break;
+                        }
+                    }
+                };
+                if ctx.cached_avatar(&contact_id).is_some() {
+                    continue;
+                }
+                match ctx.contact_avatar(contact_id.clone()).await {
+                    Ok(_) => {}
+                    Err(WechatyError::Puppet(e @ PuppetError::RateLimited(_))) => {
+                        warn!("Stop prefetching avatars, reason: {}", e);
+                        ctx.avatar_prefetch_.lock().unwrap().queue.clear();
+                    }
+                    Err(e) => error!("Failed to prefetch avatar of {}, reason: {}", contact_id, e),
+                }
+                let interval = *ctx.avatar_prefetch_interval_.lock().unwrap();
+                let clock = ctx.clock_.lock().unwrap().clone();
+                clock.sleep(interval).await;
+            }
+        });
+    }
+
+    pub(crate) fn set_outbox_path(&self, path: PathBuf) {
+        debug!("set_outbox_path(path = {:?})", path);
+        self.outbox_.lock().unwrap().set_path(path);
+    }
+
+    pub(crate) async fn outbox_push(&self, conversation_id: String, text: String) -> Result<u64, WechatyError> {
+        debug!("outbox_push(conversation_id = {}, text = {})", conversation_id, text);
+        self.with_outbox(move |outbox| outbox.push(conversation_id, text)).await
+    }
+
+    pub(crate) async fn outbox_remove(&self, id: u64) -> Result<(), WechatyError> {
+        debug!("outbox_remove(id = {})", id);
+        self.with_outbox(move |outbox| outbox.remove(id)).await
+    }
+
+    pub(crate) fn outbox_release(&self, id: u64) {
+        debug!("outbox_release(id = {})", id);
+        self.outbox_.lock().unwrap().release(id);
+    }
+
+    /// Run an operation that may read or write the outbox file on the blocking thread pool.
+    ///
+    /// The outbox is only locked while it is changed, and the file is written from a snapshot afterwards, so that
+    /// `outbox_release` never waits for the file. Items added by the operation are released if they cannot be saved.
+    async fn with_outbox<F, R>(&self, f: F) -> Result<R, WechatyError>
+    where
+        F: FnOnce(&mut Outbox) -> io::Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let outbox = self.outbox_.clone();
+        let task = move || {
+            let (result, snapshot) = {
+                let mut outbox = outbox.lock().unwrap();
+                let result = f(&mut outbox);
+                (result, outbox.snapshot())
+            };
+            if let Some(snapshot) = snapshot {
+                if let Err(e) = snapshot.save() {
+                    let mut outbox = outbox.lock().unwrap();
+                    snapshot.pushed.iter().for_each(|id| outbox.release(*id));
+                    return Err(e);
+                }
+            }
+            result
+        };
+        match tokio::task::spawn_blocking(task).await {
+            Ok(result) => Ok(result?),
+            Err(e) => Err(WechatyError::Io(io::Error::other(e))),
+        }
+    }
+
+    /// Retry the texts left in the outbox by `Talkable::send_text_reliable`, e.g. after a restart, and return the
+    /// number of texts sent.
+    ///
+    /// Texts are sent in the order they were queued, and flushing stops at the first text that fails to be sent,
+    /// which stays in the outbox. Texts being sent by `send_text_reliable` or a concurrent flush are skipped. A text
+    /// may be sent twice if the bot crashed after sending it but before taking it out of the outbox.
+    pub async fn flush_outbox(&self) -> Result<usize, WechatyError> {
+        debug!("flush_outbox()");
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        let claimed = self.with_outbox(|outbox| outbox.claim()).await?;
+        let mut count = 0;
+        let mut items = claimed.into_iter();
+        while let Some(item) = items.next() {
+            let id = item.id;
+            let result = send_text_unchecked(
+                self.clone(),
+                item.conversation_id.clone(),
+                item.conversation_id,
+                item.text,
+            )
+            .await;
+            if let Err(e) = result {
+                self.outbox_release(id);
+                items.for_each(|item| self.outbox_release(item.id));
+                return Err(e);
+            }
+            if let Err(e) = self.outbox_remove(id).await {
+                items.for_each(|item| self.outbox_release(item.id));
+                return Err(e);
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Classify an id as a room or a contact with the room id pattern of the puppet.
+    pub fn parse_conversation_id(&self, id: &str) -> Option<ConversationId> {
+        self.puppet_.parse_conversation_id(id)
+    }
+
+    /// Number of messages waiting or being sent, e.g. for a status command, see `Puppet::send_queue_depth`.
+    pub fn send_queue_depth(&self) -> usize {
+        self.puppet_.send_queue_depth()
+    }
+
+    /// Check whether the underlying puppet supports a feature.
+    pub fn supports(&self, feature: PuppetFeature) -> bool {
+        debug!("supports(feature = {:?})", feature);
+        self.puppet_.capabilities().supports(feature)
     }
 
     pub(crate) fn is_logged_in(&self) -> bool {
         self.id_.is_some()
     }
 
+    /// Drop a payload from both the context store and the puppet cache, so that it is loaded from the puppet
+    /// next time.
+    ///
+    /// For room members, `id` is the room id.
+    pub async fn invalidate(&self, payload_type: PayloadType, id: String) -> Result<(), WechatyError> {
+        debug!("invalidate(payload_type = {:?}, id = {})", payload_type, id);
+        match payload_type {
+            PayloadType::Contact => {
+                self.contacts().remove(&id);
+                self.avatars_.lock().unwrap().remove(&id);
+                // The contact may be mentioned in any message.
+                self.mention_names().clear();
+            }
+            PayloadType::Friendship => {
+                self.friendships().remove(&id);
+            }
+            PayloadType::Message => {
+                self.messages().remove(&id);
+                self.mention_names().pop(&id);
+            }
+            PayloadType::Room => {
+                self.rooms().remove(&id);
+            }
+            PayloadType::RoomMember => {
+                self.mention_names().clear();
+            }
+            PayloadType::Unknown => {}
+        }
+        match self.puppet_.dirty_payload(payload_type, id).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Empty every store of the context and every cache of the puppet, so that all payloads are loaded from the
+    /// puppet again, e.g. for a `/refresh` admin command.
+    ///
+    /// Only cached data is dropped, unread counts, contact history and the outbox are kept.
+    pub fn clear_all_stores(&self) {
+        debug!("clear_all_stores()");
+        self.contacts().clear();
+        self.friendships().clear();
+        self.messages().clear();
+        self.mention_names().clear();
+        self.rooms().clear();
+        self.room_invitations().clear();
+        self.avatars_.lock().unwrap().clear();
+        *self.self_qr_code_.lock().unwrap() = None;
+        self.puppet_.clear_all_caches();
+    }
+
+    /// Invalidate the payload of an entity and drop the payload held by the entity.
+    ///
+    /// Always use this or `invalidate` instead of `Puppet::dirty_payload`, otherwise the context store keeps
+    /// serving the stale payload.
+    pub async fn refresh<Payload>(&self, entity: &mut Entity<T, Payload>) -> Result<(), WechatyError>
+    where
+        Payload: EntityPayload + std::fmt::Debug + Clone,
+    {
+        debug!("refresh(id = {})", entity.id());
+        match self.invalidate(Payload::payload_type(), entity.id()).await {
+            Ok(_) => {
+                entity.set_payload(None);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Load a contact.
     ///
     /// Use contact store first, if the contact cannot be found in the local store,
@@ -100,7 +750,31 @@ where
         }
     }
 
-    /// Batch load contacts with a default batch size of 16.
+    /// Check whether a contact exists, without loading its payload if it is not cached and the puppet can tell
+    /// cheaply.
+    ///
+    /// Otherwise the payload is loaded, and `PuppetError::NotFound` means the contact does not exist.
+    pub async fn contact_exists(&self, contact_id: String) -> Result<bool, WechatyError> {
+        debug!("contact_exists(contact_id = {})", contact_id);
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        if self.contacts().contains_key(&contact_id) {
+            return Ok(true);
+        }
+        match self.puppet_.contact_exists(contact_id.clone()).await {
+            Err(PuppetError::Unsupported(_)) => {}
+            Ok(exists) => return Ok(exists),
+            Err(e) => return Err(WechatyError::from(e)),
+        }
+        match self.puppet_.contact_payload(contact_id).await {
+            Ok(_) => Ok(true),
+            Err(PuppetError::NotFound(_)) => Ok(false),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Batch load contacts with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     ///
     /// Reference: [Batch execution of futures in the tokio runtime](https://users.rust-lang.org/t/batch-execution-of-futures-in-the-tokio-runtime-or-max-number-of-active-futures-at-a-time/47659).
     ///
@@ -112,7 +786,7 @@ where
         let mut contact_list = vec![];
         let mut stream = tokio_stream::iter(contact_id_list)
             .map(|contact_id| self.contact_load(contact_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.puppet_.batch_size());
         while let Some(result) = stream.next().await {
             if let Ok(contact) = result {
                 contact_list.push(contact);
@@ -161,7 +835,7 @@ where
             Some(query) => query,
             None => ContactQueryFilter::default(),
         };
-        match self.puppet().contact_search(query, None).await {
+        match self.puppet_.contact_search(query, None).await {
             Ok(contact_id_list) => Ok(self.contact_load_batch(contact_id_list).await),
             Err(e) => Err(WechatyError::from(e)),
         }
@@ -173,12 +847,64 @@ where
         if !self.is_logged_in() {
             return Err(WechatyError::NotLoggedIn);
         }
-        match self.puppet().contact_search_by_string(query_str, None).await {
+        match self.puppet_.contact_search_by_string(query_str, None).await {
             Ok(contact_id_list) => Ok(self.contact_load_batch(contact_id_list).await),
             Err(e) => Err(WechatyError::from(e)),
         }
     }
 
+    /// Find the first contact whose name or alias matches the regex pattern
+    pub async fn contact_find_by_regex(&self, pattern: &str) -> Result<Option<Contact<T>>, WechatyError> {
+        debug!("contact_find_by_regex(pattern = {})", pattern);
+        match self.contact_find_all_by_regex(pattern).await {
+            Ok(contact_list) => Ok(contact_list.into_iter().next()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Find all contacts whose name or alias matches the regex pattern
+    pub async fn contact_find_all_by_regex(&self, pattern: &str) -> Result<Vec<Contact<T>>, WechatyError> {
+        debug!("contact_find_all_by_regex(pattern = {})", pattern);
+        let regex = Self::compile_regex(pattern)?;
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        // The filters of a query must all match, so search the names and the aliases separately.
+        let name_query = ContactQueryFilter {
+            name_regex: Some(regex.clone()),
+            ..Default::default()
+        };
+        let alias_query = ContactQueryFilter {
+            alias_regex: Some(regex),
+            ..Default::default()
+        };
+        let mut contact_id_list = match self.puppet_.contact_search(name_query, None).await {
+            Ok(contact_id_list) => contact_id_list,
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        match self.puppet_.contact_search(alias_query, None).await {
+            Ok(alias_id_list) => {
+                for contact_id in alias_id_list {
+                    if !contact_id_list.contains(&contact_id) {
+                        contact_id_list.push(contact_id);
+                    }
+                }
+            }
+            Err(e) => return Err(WechatyError::from(e)),
+        }
+        Ok(self.contact_load_batch(contact_id_list).await)
+    }
+
+    fn compile_regex(pattern: &str) -> Result<Regex, WechatyError> {
+        match Regex::new(pattern) {
+            Ok(regex) => Ok(regex),
+            Err(e) => Err(WechatyError::InvalidOperation(format!(
+                "Invalid regex {}: {}",
+                pattern, e
+            ))),
+        }
+    }
+
     /// Load a message.
     ///
     /// Use message store first, if the message cannot be found in the local store,
@@ -198,13 +924,13 @@ where
         }
     }
 
-    /// Batch load messages with a default batch size of 16.
+    /// Batch load messages with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     pub(crate) async fn message_load_batch(&self, message_id_list: Vec<String>) -> Vec<Message<T>> {
         debug!("message_load_batch(message_id_list = {:?})", message_id_list);
         let mut message_list = vec![];
         let mut stream = tokio_stream::iter(message_id_list)
             .map(|message_id| self.message_load(message_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.puppet_.batch_size());
         while let Some(result) = stream.next().await {
             if let Ok(message) = result {
                 message_list.push(message);
@@ -237,7 +963,7 @@ where
         if !self.is_logged_in() {
             return Err(WechatyError::NotLoggedIn);
         }
-        match self.puppet().message_search(query).await {
+        match self.puppet_.message_search(query).await {
             Ok(message_id_list) => Ok(self.message_load_batch(message_id_list).await),
             Err(e) => Err(WechatyError::from(e)),
         }
@@ -265,13 +991,51 @@ where
         }
     }
 
-    /// Batch load rooms with a default batch size of 16.
+    /// Check whether a room exists, like `contact_exists`.
+    pub async fn room_exists(&self, room_id: String) -> Result<bool, WechatyError> {
+        debug!("room_exists(room_id = {})", room_id);
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        if self.rooms().contains_key(&room_id) {
+            return Ok(true);
+        }
+        match self.puppet_.room_exists(room_id.clone()).await {
+            Err(PuppetError::Unsupported(_)) => {}
+            Ok(exists) => return Ok(exists),
+            Err(e) => return Err(WechatyError::from(e)),
+        }
+        match self.puppet_.room_payload(room_id).await {
+            Ok(_) => Ok(true),
+            Err(PuppetError::NotFound(_)) => Ok(false),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Join a room by its QR code or invitation url, which must be an http or https url.
+    ///
+    /// This complements `RoomInvitation::accept` for gateways that allow joining by url.
+    pub async fn room_join_by_url(&self, url: String) -> Result<Room<T>, WechatyError> {
+        debug!("room_join_by_url(url = {})", url);
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        if !is_room_url(&url) {
+            return Err(WechatyError::InvalidOperation(format!("Invalid room url: {}", url)));
+        }
+        match self.puppet().room_join_by_url(url).await {
+            Ok(room_id) => self.room_load(room_id).await,
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Batch load rooms with a default batch size of 16, which can be overridden by `WECHATY_BATCH_SIZE`.
     pub(crate) async fn room_load_batch(&self, room_id_list: Vec<String>) -> Vec<Room<T>> {
         debug!("room_load_batch(room_id_list = {:?})", room_id_list);
         let mut room_list = vec![];
         let mut stream = tokio_stream::iter(room_id_list)
             .map(|room_id| self.room_load(room_id))
-            .buffer_unordered(16);
+            .buffer_unordered(self.puppet_.batch_size());
         while let Some(result) = stream.next().await {
             if let Ok(room) = result {
                 room_list.push(room);
@@ -331,19 +1095,104 @@ where
         if !self.is_logged_in() {
             return Err(WechatyError::NotLoggedIn);
         }
-        match self.puppet().room_search(query).await {
+        match self.puppet_.room_search(query).await {
             Ok(room_id_list) => Ok(self.room_load_batch(room_id_list).await),
             Err(e) => Err(WechatyError::from(e)),
         }
     }
 
+    /// Find the first room whose topic matches the regex pattern
+    pub async fn room_find_by_regex(&self, pattern: &str) -> Result<Option<Room<T>>, WechatyError> {
+        debug!("room_find_by_regex(pattern = {})", pattern);
+        match self.room_find_all_by_regex(pattern).await {
+            Ok(room_list) => Ok(room_list.into_iter().next()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Find all rooms whose topic matches the regex pattern
+    pub async fn room_find_all_by_regex(&self, pattern: &str) -> Result<Vec<Room<T>>, WechatyError> {
+        debug!("room_find_all_by_regex(pattern = {})", pattern);
+        let regex = Self::compile_regex(pattern)?;
+        self.room_find_all(RoomQueryFilter {
+            topic_regex: Some(regex),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Load all contacts and rooms into the caches, so that later lookups do not wait for the puppet.
+    ///
+    /// Call it from `on_login` or `on_ready` to warm the caches instead of finding all contacts. Payloads are
+    /// loaded in batches like `contact_find_all`, and each one is cached as soon as it is loaded, so dropping the
+    /// future halfway keeps what has been loaded. Returns the numbers of contacts and rooms loaded, or
+    /// `WechatyError::Cancelled` if the bot is stopped halfway.
+    pub async fn warmup(&self) -> Result<(usize, usize), WechatyError> {
+        debug!("warmup()");
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        self.check_cancelled()?;
+        let contact_count = match self.puppet_.contact_list().await {
+            Ok(contact_id_list) => {
+                self.warmup_batch(contact_id_list, |contact_id| self.contact_load(contact_id))
+                    .await?
+            }
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        self.check_cancelled()?;
+        let room_count = match self.puppet_.room_list().await {
+            Ok(room_id_list) => {
+                self.warmup_batch(room_id_list, |room_id| self.room_load(room_id))
+                    .await?
+            }
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        Ok((contact_count, room_count))
+    }
+
+    /// Load entities in batches for `warmup`, stopping with `WechatyError::Cancelled` once the bot is stopped.
+    ///
+    /// Unlike `contact_load_batch` and `room_load_batch`, which back the public finders and always run to the end,
+    /// this is the only loader that honours the cancellation flag. No load is started once the bot is stopped, and
+    /// the loads in flight are dropped as soon as one of them finishes.
+    async fn warmup_batch<F, Fut, E>(&self, id_list: Vec<String>, load: F) -> Result<usize, WechatyError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<E, WechatyError>>,
+    {
+        let mut count = 0;
+        let mut stream = tokio_stream::iter(id_list)
+            .take_while(|_| future::ready(!self.is_cancelled()))
+            .map(load)
+            .buffer_unordered(self.puppet_.batch_size());
+        while let Some(result) = stream.next().await {
+            self.check_cancelled()?;
+            if result.is_ok() {
+                count += 1;
+            }
+        }
+        // The id stream also ends early if the bot is stopped before the last load is started.
+        self.check_cancelled()?;
+        Ok(count)
+    }
+
+    /// Return `WechatyError::Cancelled` if the bot has been stopped, see `warmup`.
+    fn check_cancelled(&self) -> Result<(), WechatyError> {
+        if self.is_cancelled() {
+            debug!("warmup() cancelled");
+            Err(WechatyError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Load a friendship.
     ///
     /// Use friendship store first, if the friendship cannot be found in the local store,
     /// try to fetch from the puppet instead.
-    #[allow(dead_code)]
-    pub(crate) async fn friendship_load(&self, friendship_id: String) -> Result<Friendship<T>, WechatyError> {
-        debug!("friendship_load(friendship_id = {})", friendship_id);
+    pub async fn friendship(&self, friendship_id: String) -> Result<Friendship<T>, WechatyError> {
+        debug!("friendship(friendship_id = {})", friendship_id);
         if !self.is_logged_in() {
             return Err(WechatyError::NotLoggedIn);
         }
@@ -360,6 +1209,26 @@ where
         }
     }
 
+    /// Manually set the payload of a friendship, mainly used for seeding friendships in tests.
+    pub async fn friendship_payload_set(
+        &self,
+        friendship_id: String,
+        payload: FriendshipPayload,
+    ) -> Result<(), WechatyError> {
+        debug!("friendship_payload_set(friendship_id = {})", friendship_id);
+        match self
+            .puppet_
+            .friendship_payload_set(friendship_id.clone(), payload.clone())
+            .await
+        {
+            Ok(_) => {
+                self.friendships().insert(friendship_id, payload);
+                Ok(())
+            }
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
     /// Add friendship with contact.
     pub async fn friendship_add(&self, contact: Contact<T>, hello: Option<String>) -> Result<(), WechatyError> {
         debug!("friendship_add(contact = {}, hello = {:?}", contact, hello);
@@ -374,7 +1243,7 @@ where
 
     /// Search a friendship.
     ///
-    /// First search by phone, then search by weixin.
+    /// First search by phone, then search by weixin. Empty or blank fields are treated as missing.
     pub async fn friendship_search(
         &self,
         query: FriendshipSearchQueryFilter,
@@ -383,12 +1252,16 @@ where
         if !self.is_logged_in() {
             return Err(WechatyError::NotLoggedIn);
         }
+        let query = FriendshipSearchQueryFilter {
+            phone: query.phone.filter(|phone| !phone.trim().is_empty()),
+            weixin: query.weixin.filter(|weixin| !weixin.trim().is_empty()),
+        };
         if query.phone.is_none() && query.weixin.is_none() {
             return Err(WechatyError::InvalidOperation(
                 "Must specify either phone or weixin".to_owned(),
             ));
         }
-        match self.puppet().friendship_search(query).await {
+        match self.puppet_.friendship_search(query).await {
             Ok(Some(contact_id)) => {
                 let mut contact = Contact::new(contact_id, self.clone(), None);
                 contact.sync().await.unwrap_or_default();
@@ -399,6 +1272,89 @@ where
         }
     }
 
+    /// Load the favorites of the bot, favorites that fail to load are left out.
+    pub async fn favorite_list(&self) -> Result<Vec<Favorite<T>>, WechatyError> {
+        debug!("favorite_list()");
+        if !self.is_logged_in() {
+            return Err(WechatyError::NotLoggedIn);
+        }
+        match self.puppet().favorite_list().await {
+            Ok(favorite_id_list) => {
+                let mut favorite_list = vec![];
+                for favorite_id in favorite_id_list {
+                    let mut favorite = Favorite::new(favorite_id, self.clone(), None);
+                    if favorite.ready().await.is_ok() {
+                        favorite_list.push(favorite);
+                    }
+                }
+                Ok(favorite_list)
+            }
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Get a tag by id.
+    pub fn tag(&self, tag_id: String) -> Tag<T> {
+        debug!("tag(tag_id = {})", tag_id);
+        Tag::new(tag_id, self.clone())
+    }
+
+    /// Add a tag to contacts, return the result for each contact.
+    ///
+    /// Contacts are tagged concurrently with the batch size, a failure does not stop the others.
+    pub async fn tag_add_batch(
+        &self,
+        tag_id: String,
+        contacts: Vec<Contact<T>>,
+    ) -> Vec<(Contact<T>, Result<(), WechatyError>)> {
+        debug!("tag_add_batch(tag_id = {}, contacts = {:?})", tag_id, contacts);
+        let puppet = self.puppet();
+        let puppet = &puppet;
+        let tag_id = &tag_id;
+        tokio_stream::iter(contacts)
+            .map(|contact| async move {
+                let result = match puppet.tag_contact_add(tag_id.clone(), contact.id()).await {
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        error!("Failed to add tag {} to {}, reason: {}", tag_id, contact, e);
+                        Err(WechatyError::from(e))
+                    }
+                };
+                (contact, result)
+            })
+            .buffer_unordered(self.puppet_.batch_size())
+            .collect()
+            .await
+    }
+
+    /// Remove a tag from contacts, return the result for each contact.
+    ///
+    /// Contacts are untagged concurrently with the batch size, a failure does not stop the others.
+    pub async fn tag_remove_batch(
+        &self,
+        tag_id: String,
+        contacts: Vec<Contact<T>>,
+    ) -> Vec<(Contact<T>, Result<(), WechatyError>)> {
+        debug!("tag_remove_batch(tag_id = {}, contacts = {:?})", tag_id, contacts);
+        let puppet = self.puppet();
+        let puppet = &puppet;
+        let tag_id = &tag_id;
+        tokio_stream::iter(contacts)
+            .map(|contact| async move {
+                let result = match puppet.tag_contact_remove(tag_id.clone(), contact.id()).await {
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        error!("Failed to remove tag {} from {}, reason: {}", tag_id, contact, e);
+                        Err(WechatyError::from(e))
+                    }
+                };
+                (contact, result)
+            })
+            .buffer_unordered(self.puppet_.batch_size())
+            .collect()
+            .await
+    }
+
     /// Logout current account.
     pub async fn logout(&self) -> Result<(), WechatyError> {
         debug!("logout()");
@@ -411,3 +1367,482 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::{fixtures, FriendshipType};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::{ContactSelf, MockClock, Talkable};
+
+    #[actix_rt::test]
+    async fn clearing_stores_refetches_payloads() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        let contact = ctx.contact_load("clear-alice".to_owned()).await.unwrap();
+        assert_eq!(contact.name().as_deref(), Some("clear-alice"));
+
+        let mut payload = contact.payload().unwrap();
+        payload.name = "Alice".to_owned();
+        mock.add_contact(payload);
+        let contact = ctx.contact_load("clear-alice".to_owned()).await.unwrap();
+        assert_eq!(contact.name().as_deref(), Some("clear-alice"));
+
+        ctx.clear_all_stores();
+        assert!(ctx.contacts().is_empty());
+        assert!(!ctx
+            .puppet()
+            .is_payload_cached(PayloadType::Contact, "clear-alice".to_owned()));
+        let contact = ctx.contact_load("clear-alice".to_owned()).await.unwrap();
+        assert_eq!(contact.name().as_deref(), Some("Alice"));
+    }
+
+    #[actix_rt::test]
+    async fn can_check_existence() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        mock.set_room_members("exists-room".to_owned(), vec!["exists-alice".to_owned()]);
+        assert!(matches!(
+            ctx.contact_exists("exists-alice".to_owned()).await,
+            Err(WechatyError::NotLoggedIn)
+        ));
+
+        ctx.set_id("self".to_owned());
+        assert!(ctx.contact_exists("exists-alice".to_owned()).await.unwrap());
+        assert!(!ctx.contact_exists("exists-nobody".to_owned()).await.unwrap());
+        assert!(ctx.room_exists("exists-room".to_owned()).await.unwrap());
+        assert!(!ctx.room_exists("exists-nowhere".to_owned()).await.unwrap());
+        // Checking does not load the payloads.
+        assert!(!ctx.contacts().contains_key("exists-alice"));
+        assert!(!ctx.rooms().contains_key("exists-room"));
+    }
+
+    #[actix_rt::test]
+    async fn existence_falls_back_to_loading_payloads() {
+        let mock = PuppetMock::default()
+            .with_unsupported("contact_exists")
+            .with_unsupported("room_exists");
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        assert!(ctx.contact_exists("alice".to_owned()).await.unwrap());
+        assert!(!ctx.contact_exists("missing-alice".to_owned()).await.unwrap());
+        assert!(ctx.room_exists("room".to_owned()).await.unwrap());
+        assert!(!ctx.room_exists("missing-room".to_owned()).await.unwrap());
+        assert_eq!(mock.call_count("room_raw_payload"), 2);
+    }
+
+    #[actix_rt::test]
+    async fn warmup_populates_caches() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        mock.set_room_members(
+            "warmup-room".to_owned(),
+            vec!["warmup-alice".to_owned(), "warmup-bob".to_owned()],
+        );
+        assert!(matches!(ctx.warmup().await, Err(WechatyError::NotLoggedIn)));
+
+        ctx.set_id("self".to_owned());
+        let (contact_count, room_count) = ctx.warmup().await.unwrap();
+        assert!(contact_count >= 2);
+        assert!(room_count >= 1);
+        assert!(ctx.contacts().contains_key("warmup-alice"));
+        assert!(ctx.contacts().contains_key("warmup-bob"));
+        assert_eq!(
+            ctx.rooms()
+                .get("warmup-room")
+                .map(|payload| payload.member_id_list.clone()),
+            Some(vec!["warmup-alice".to_owned(), "warmup-bob".to_owned()])
+        );
+    }
+
+    fn contact_payload(name: &str, alias: &str) -> ContactPayload {
+        ContactPayload {
+            alias: alias.to_owned(),
+            ..fixtures::contact("contact", name)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn contact_history_records_previous_name() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_track_contact_history(true);
+        ctx.update_contact("contact".to_owned(), contact_payload("Alice", "alias"));
+        ctx.update_contact("contact".to_owned(), contact_payload("Bob", "alias"));
+
+        let contact = Contact::new("contact".to_owned(), ctx, None);
+        assert_eq!(contact.name(), Some("Bob".to_owned()));
+        assert_eq!(contact.previous_name(), Some("Alice".to_owned()));
+        assert_eq!(contact.previous_alias(), None);
+    }
+
+    #[actix_rt::test]
+    async fn contact_history_is_disabled_by_default() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.update_contact("contact".to_owned(), contact_payload("Alice", "alias"));
+        ctx.update_contact("contact".to_owned(), contact_payload("Bob", "alias"));
+
+        assert_eq!(ctx.previous_name("contact"), None);
+    }
+
+    #[actix_rt::test]
+    async fn can_load_seeded_friendship() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        ctx.friendship_payload_set(
+            "friendship".to_owned(),
+            FriendshipPayload {
+                hello: "hello".to_owned(),
+                ..fixtures::friendship("friendship", "")
+            },
+        )
+        .await
+        .unwrap();
+
+        let friendship = ctx.friendship("friendship".to_owned()).await.unwrap();
+        assert_eq!(friendship.id(), "friendship");
+        assert_eq!(friendship.payload().unwrap().hello, "hello");
+        assert_eq!(friendship.friendship_type(), Some(FriendshipType::Receive));
+    }
+
+    #[actix_rt::test]
+    async fn friendship_search_rejects_missing_and_blank_queries() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        let blank = FriendshipSearchQueryFilter {
+            phone: Some(String::new()),
+            weixin: Some("  ".to_owned()),
+        };
+        for query in [FriendshipSearchQueryFilter::default(), blank] {
+            assert!(matches!(
+                ctx.friendship_search(query).await,
+                Err(WechatyError::InvalidOperation(_))
+            ));
+        }
+
+        let query = FriendshipSearchQueryFilter {
+            phone: Some(" ".to_owned()),
+            weixin: Some("search-alice".to_owned()),
+        };
+        let contact = ctx.friendship_search(query).await.unwrap().unwrap();
+        assert_eq!(contact.id(), "search-alice");
+    }
+
+    #[actix_rt::test]
+    async fn batch_load_respects_batch_size() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()).with_batch_size(2));
+        assert_eq!(ctx.puppet().batch_size(), 2);
+
+        let contact_id_list: Vec<String> = (0..5).map(|i| format!("contact-{}", i)).collect();
+        for contact_id in contact_id_list.iter() {
+            ctx.update_contact(contact_id.clone(), contact_payload("name", "alias"));
+        }
+        let contact_list = ctx.contact_load_batch(contact_id_list.clone()).await;
+        let mut loaded_id_list: Vec<String> = contact_list.iter().map(|contact| contact.id()).collect();
+        loaded_id_list.sort();
+        assert_eq!(loaded_id_list, contact_id_list);
+    }
+
+    #[actix_rt::test]
+    async fn tag_add_batch_reports_partial_failure() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        // The mock rejects empty contact ids.
+        let contacts = ["alice", "", "bob"]
+            .iter()
+            .map(|id| Contact::new(id.to_string(), ctx.clone(), Some(contact_payload(id, ""))))
+            .collect();
+
+        let mut results = ctx.tag("tag".to_owned()).add_contacts(contacts).await;
+        results.sort_by_key(|(contact, _)| contact.id());
+        let results: Vec<(String, bool)> = results
+            .into_iter()
+            .map(|(contact, result)| (contact.id(), result.is_ok()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("".to_owned(), false),
+                ("alice".to_owned(), true),
+                ("bob".to_owned(), true),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn self_qr_code_is_cached_until_refreshed_or_logout() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        let contact_self = ContactSelf::new("self".to_owned(), ctx.clone(), None);
+        assert_eq!(contact_self.qrcode().await.unwrap(), "qr-self");
+        assert_eq!(contact_self.qrcode().await.unwrap(), "qr-self");
+        assert_eq!(mock.call_count("contact_self_qr_code"), 1);
+
+        assert_eq!(contact_self.qrcode_refresh().await.unwrap(), "qr-self");
+        assert_eq!(mock.call_count("contact_self_qr_code"), 2);
+
+        ctx.clear_id();
+        assert!(ctx.self_qr_code_.lock().unwrap().is_none());
+    }
+
+    #[actix_rt::test]
+    async fn can_track_uptime_and_session_duration() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let clock = Arc::new(MockClock::new(start));
+        ctx.set_clock(clock.clone());
+        assert_eq!(ctx.uptime(), Duration::ZERO);
+        assert_eq!(ctx.session_duration(), None);
+
+        clock.set(start + Duration::from_secs(60));
+        ctx.set_id("self".to_owned());
+        clock.set(start + Duration::from_secs(90));
+        assert_eq!(ctx.uptime(), Duration::from_secs(90));
+        assert_eq!(ctx.session_duration(), Some(Duration::from_secs(30)));
+
+        ctx.clear_id();
+        assert_eq!(ctx.uptime(), Duration::from_secs(90));
+        assert_eq!(ctx.session_duration(), None);
+    }
+
+    #[actix_rt::test]
+    async fn prefetched_avatars_land_in_cache_at_bounded_rate() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH));
+        ctx.set_clock(clock.clone());
+        let interval = Duration::from_secs(60);
+        ctx.set_avatar_prefetch_interval(interval);
+        let contact_ids: Vec<String> = (0..4).map(|i| format!("prefetch-{}", i)).collect();
+        let cached = || contact_ids.iter().filter(|id| ctx.cached_avatar(id).is_some()).count();
+
+        ctx.prefetch_avatars(contact_ids.clone());
+        ctx.prefetch_avatars(contact_ids.clone());
+        // The first avatar is fetched right away, each of the others waits for the clock to move on by an interval.
+        for fetched in 1..=contact_ids.len() {
+            for _ in 0..100 {
+                actix_rt::task::yield_now().await;
+            }
+            assert_eq!(cached(), fetched);
+            clock.advance(interval);
+        }
+        assert_eq!(mock.call_count("contact_avatar"), contact_ids.len());
+        assert_eq!(ctx.cached_avatar("prefetch-0").unwrap().name(), "prefetch-0.jpg");
+    }
+
+    #[actix_rt::test]
+    async fn avatar_is_cached_until_expired_or_invalidated() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let clock = Arc::new(MockClock::new(SystemTime::now()));
+        ctx.set_clock(clock.clone());
+        let contact = Contact::new("alice".to_owned(), ctx.clone(), None);
+        assert_eq!(contact.avatar().await.unwrap().name(), "alice.jpg");
+        assert_eq!(contact.avatar().await.unwrap().name(), "alice.jpg");
+        assert_eq!(mock.call_count("contact_avatar"), 1);
+
+        clock.advance(AVATAR_TTL);
+        assert!(ctx.cached_avatar("alice").is_none());
+        contact.avatar().await.unwrap();
+        assert_eq!(mock.call_count("contact_avatar"), 2);
+
+        assert!(ctx.cached_avatar("alice").is_some());
+        ctx.invalidate(PayloadType::Contact, "alice".to_owned()).await.unwrap();
+        assert!(ctx.cached_avatar("alice").is_none());
+        contact.avatar().await.unwrap();
+        assert_eq!(mock.call_count("contact_avatar"), 3);
+    }
+
+    #[actix_rt::test]
+    async fn can_find_contacts_by_name_or_alias_regex() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        mock.set_room_members(
+            "regex-room".to_owned(),
+            vec![
+                "regex-alice".to_owned(),
+                "regex-alan".to_owned(),
+                "regex-bob".to_owned(),
+            ],
+        );
+        ctx.puppet()
+            .contact_alias_set("regex-bob".to_owned(), "regex-albert".to_owned())
+            .await
+            .unwrap();
+        ctx.set_id("self".to_owned());
+
+        let mut id_list: Vec<String> = ctx
+            .contact_find_all_by_regex("^regex-al")
+            .await
+            .unwrap()
+            .iter()
+            .map(|contact| contact.id())
+            .collect();
+        id_list.sort();
+        assert_eq!(id_list, vec!["regex-alan", "regex-alice", "regex-bob"]);
+        assert!(ctx.contact_find_by_regex("^regex-carol").await.unwrap().is_none());
+        assert!(matches!(
+            ctx.contact_find_all_by_regex("regex-(").await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            ctx.room_find_all_by_regex("[").await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn outbox_is_retried_after_a_crash() {
+        let path = std::env::temp_dir().join(format!("wechaty-outbox-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // The gateway outlives the bot, so the restarted bots share the mock.
+        let mock = PuppetMock::default();
+
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        ctx.set_outbox_path(path.clone());
+        let contact = Contact::new("outbox-alice".to_owned(), ctx.clone(), None);
+        contact.send_text_reliable("sent".to_owned()).await.unwrap();
+        drop(ctx);
+
+        // The gateway rejects the text and the bot crashes before retrying it.
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone().with_unsupported("message_send_text")));
+        ctx.set_id("self".to_owned());
+        ctx.set_outbox_path(path.clone());
+        let contact = Contact::new("outbox-alice".to_owned(), ctx.clone(), None);
+        assert!(contact.send_text_reliable("pending".to_owned()).await.is_err());
+        assert!(ctx.flush_outbox().await.is_err());
+        drop(ctx);
+        assert_eq!(mock.sent_texts("outbox-alice"), vec!["sent"]);
+
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_outbox_path(path.clone());
+        assert!(matches!(ctx.flush_outbox().await, Err(WechatyError::NotLoggedIn)));
+        ctx.set_id("self".to_owned());
+        assert_eq!(ctx.flush_outbox().await.unwrap(), 1);
+        assert_eq!(mock.sent_texts("outbox-alice"), vec!["sent", "pending"]);
+        assert_eq!(ctx.flush_outbox().await.unwrap(), 0);
+
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        ctx.set_outbox_path(path.clone());
+        assert_eq!(ctx.flush_outbox().await.unwrap(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn outbox_items_are_released_if_saving_fails() {
+        let dir = std::env::temp_dir().join(format!("wechaty-outbox-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        ctx.set_outbox_path(dir.join("outbox.json"));
+        // Left over from a previous run.
+        for text in ["first", "second"] {
+            let id = ctx
+                .outbox_push("outbox-carol".to_owned(), text.to_owned())
+                .await
+                .unwrap();
+            ctx.outbox_release(id);
+        }
+
+        // Taking the first text out of the outbox cannot be saved.
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(ctx.flush_outbox().await, Err(WechatyError::Io(_))));
+        assert_eq!(mock.sent_texts("outbox-carol"), vec!["first"]);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(ctx.flush_outbox().await.unwrap(), 1);
+        assert_eq!(mock.sent_texts("outbox-carol"), vec!["first", "second"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn outbox_items_are_claimed_once() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        // Left over from a previous run.
+        let id = ctx
+            .outbox_push("outbox-bob".to_owned(), "once".to_owned())
+            .await
+            .unwrap();
+        ctx.outbox_release(id);
+
+        let (first, second) = futures::join!(ctx.flush_outbox(), ctx.flush_outbox());
+        assert_eq!(first.unwrap() + second.unwrap(), 1);
+        assert_eq!(mock.sent_texts("outbox-bob"), vec!["once"]);
+    }
+
+    #[actix_rt::test]
+    async fn can_join_room_by_url() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        let room = ctx
+            .room_join_by_url("https://weixin.qq.com/g/rust".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(room.id(), "room-rust");
+
+        for url in [
+            "weixin.qq.com/g/rust",
+            "https:///g/rust",
+            "https://weixin.qq.com/g/ rust",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    ctx.room_join_by_url(url.to_owned()).await,
+                    Err(WechatyError::InvalidOperation(_))
+                ),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn refresh_clears_context_and_puppet_caches() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.friendship_payload_set(
+            "friendship".to_owned(),
+            FriendshipPayload {
+                hello: "hello".to_owned(),
+                ..fixtures::friendship("friendship", "")
+            },
+        )
+        .await
+        .unwrap();
+        let mut friendship = Friendship::new("friendship".to_owned(), ctx.clone(), None);
+        assert!(friendship.payload().is_some());
+        assert!(ctx
+            .puppet()
+            .is_payload_cached(PayloadType::Friendship, "friendship".to_owned()));
+
+        ctx.refresh(&mut friendship).await.unwrap();
+        assert!(friendship.payload().is_none());
+        assert!(!ctx.friendships().contains_key("friendship"));
+        assert!(!ctx
+            .puppet()
+            .is_payload_cached(PayloadType::Friendship, "friendship".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn stale_contact_is_not_served_after_set_alias() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()).with_dry_run(true));
+        let mut stale = contact_payload("Stale", "");
+        stale.id = "alice".to_owned();
+        ctx.update_contact("alice".to_owned(), stale);
+
+        let mut contact = ctx.contact_load("alice".to_owned()).await.unwrap();
+        assert_eq!(contact.name(), Some("Stale".to_owned()));
+        contact.set_alias("alias".to_owned()).await.unwrap();
+
+        // The mock names contacts after their ids.
+        assert_eq!(contact.name(), Some("alice".to_owned()));
+        let contact = ctx.contact_load("alice".to_owned()).await.unwrap();
+        assert_eq!(contact.name(), Some("alice".to_owned()));
+    }
+}
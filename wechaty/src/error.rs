@@ -1,4 +1,4 @@
-use std::{error, fmt};
+use std::{error, fmt, io};
 
 use wechaty_puppet::PuppetError;
 
@@ -8,6 +8,18 @@ pub enum WechatyError {
     Maybe(String),
     NotLoggedIn,
     NoPayload,
+    Io(io::Error),
+    Cancelled,
+}
+
+impl WechatyError {
+    /// Check whether the failed operation may succeed if retried later, see `PuppetError::is_retryable`.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WechatyError::Puppet(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Debug for WechatyError {
@@ -19,11 +31,19 @@ impl fmt::Debug for WechatyError {
 impl fmt::Display for WechatyError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            WechatyError::Puppet(e @ PuppetError::RateLimited(_)) => {
+                write!(fmt, "Puppet error: {}, please back off before retrying", e)
+            }
+            WechatyError::Puppet(e @ PuppetError::AccountBanned(_)) => {
+                write!(fmt, "Puppet error: {}, the account needs manual attention", e)
+            }
             WechatyError::Puppet(e) => write!(fmt, "Puppet error: {}", e),
             WechatyError::InvalidOperation(op) => write!(fmt, "Invalid operation: {}", op),
             WechatyError::Maybe(maybe) => write!(fmt, "An error may have occurred: {}", maybe),
             WechatyError::NotLoggedIn => write!(fmt, "User is not logged in"),
             WechatyError::NoPayload => write!(fmt, "Operation cannot be done because the current entity does not have payload due to an unknown previous issue"),
+            WechatyError::Io(e) => write!(fmt, "IO error: {}", e),
+            WechatyError::Cancelled => write!(fmt, "Operation cancelled because the bot is stopping"),
         }
     }
 }
@@ -34,4 +54,37 @@ impl From<PuppetError> for WechatyError {
     }
 }
 
+impl From<io::Error> for WechatyError {
+    fn from(e: io::Error) -> Self {
+        WechatyError::Io(e)
+    }
+}
+
 impl error::Error for WechatyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_classify_retryable_errors() {
+        let cases = [
+            (WechatyError::from(PuppetError::Network("timeout".to_owned())), true),
+            (
+                WechatyError::from(PuppetError::RateLimited("too frequent".to_owned())),
+                true,
+            ),
+            (WechatyError::from(PuppetError::InvalidToken), false),
+            (
+                WechatyError::InvalidOperation("send to official account".to_owned()),
+                false,
+            ),
+            (WechatyError::Maybe("unknown".to_owned()), false),
+            (WechatyError::NotLoggedIn, false),
+            (WechatyError::NoPayload, false),
+        ];
+        for (error, retryable) in cases {
+            assert_eq!(error.is_retryable(), retryable, "{}", error);
+        }
+    }
+}
@@ -1,22 +1,34 @@
+mod clock;
+mod cluster;
 mod context;
 mod error;
+mod outbox;
 mod payload;
 mod traits;
 mod user;
 mod wechaty;
 
 pub use actix_rt as wechaty_rt;
-pub use wechaty_puppet::{MessageType, PuppetOptions};
+pub use wechaty_puppet::{
+    ConnectionState, ConversationId, ForwardPath, JsonCasing, MessageCategory, MessageRecord, MessageType,
+    PuppetCapabilities, PuppetFeature, PuppetOptions, StreamErrorAction, StreamErrorPolicy,
+};
 
+pub use crate::clock::{Clock, MockClock, SystemClock};
+pub use crate::cluster::WechatyCluster;
 pub use crate::context::WechatyContext;
 pub use crate::error::WechatyError;
 pub use crate::payload::*;
 pub use crate::traits::contact::IntoContact;
-pub use crate::traits::event_listener::EventListener;
+#[cfg(not(feature = "test-util"))]
 pub(crate) use crate::traits::event_listener::EventListenerInner;
+#[cfg(feature = "test-util")]
+pub use crate::traits::event_listener::{EventHarness, EventListenerInner};
+pub use crate::traits::event_listener::{EventListener, MessageMatchOptions};
 pub use crate::traits::talkable::Talkable;
 pub use crate::user::contact::Contact;
 pub use crate::user::contact_self::ContactSelf;
+pub use crate::user::conversation::Conversation;
 pub(crate) use crate::user::entity::Entity;
 pub use crate::user::favorite::Favorite;
 pub use crate::user::friendship::Friendship;
@@ -26,7 +38,8 @@ pub use crate::user::message::Message;
 pub use crate::user::mini_program::MiniProgram;
 pub use crate::user::moment::Moment;
 pub use crate::user::money::Money;
-pub use crate::user::room::Room;
+pub use crate::user::participant::Participant;
+pub use crate::user::room::{room_member_diff, Room, RoomDetails};
 pub use crate::user::room_invitation::RoomInvitation;
 pub use crate::user::tag::Tag;
 pub use crate::user::url_link::UrlLink;
@@ -34,16 +47,22 @@ pub use crate::wechaty::Wechaty;
 
 pub mod prelude {
     pub use actix_rt as wechaty_rt;
-    pub use wechaty_puppet::{MessageType, PuppetOptions};
+    pub use wechaty_puppet::{
+        ConnectionState, ConversationId, ForwardPath, JsonCasing, MessageCategory, MessageRecord, MessageType,
+        PuppetCapabilities, PuppetFeature, PuppetOptions, StreamErrorAction, StreamErrorPolicy,
+    };
 
+    pub use crate::clock::{Clock, MockClock, SystemClock};
+    pub use crate::cluster::WechatyCluster;
     pub use crate::context::WechatyContext;
     pub use crate::error::WechatyError;
     pub use crate::payload::*;
     pub use crate::traits::contact::IntoContact;
-    pub use crate::traits::event_listener::EventListener;
+    pub use crate::traits::event_listener::{EventListener, MessageMatchOptions};
     pub use crate::traits::talkable::Talkable;
     pub use crate::user::contact::Contact;
     pub use crate::user::contact_self::ContactSelf;
+    pub use crate::user::conversation::Conversation;
     pub use crate::user::favorite::Favorite;
     pub use crate::user::friendship::Friendship;
     pub use crate::user::image::Image;
@@ -52,7 +71,8 @@ pub mod prelude {
     pub use crate::user::mini_program::MiniProgram;
     pub use crate::user::moment::Moment;
     pub use crate::user::money::Money;
-    pub use crate::user::room::Room;
+    pub use crate::user::participant::Participant;
+    pub use crate::user::room::{room_member_diff, Room, RoomDetails};
     pub use crate::user::room_invitation::RoomInvitation;
     pub use crate::user::tag::Tag;
     pub use crate::user::url_link::UrlLink;
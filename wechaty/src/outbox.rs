@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// A text waiting to be sent, see `Talkable::send_text_reliable`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OutboxItem {
+    pub(crate) id: u64,
+    pub(crate) conversation_id: String,
+    pub(crate) text: String,
+}
+
+/// Texts that have not been confirmed as sent, persisted to a file if a path is set.
+///
+/// The items are loaded from the file the first time they are needed. Whenever an item is added or removed, a
+/// snapshot of the items is taken with `snapshot` and written to the file without holding the outbox. Items being
+/// sent are marked in flight, so that they are not claimed twice by concurrent flushes.
+#[derive(Default)]
+pub(crate) struct Outbox {
+    path: Option<PathBuf>,
+    items: Option<Vec<OutboxItem>>,
+    in_flight: HashSet<u64>,
+    /// Id of the next item, which only goes up so that a stale claim never matches a newer item.
+    next_id: u64,
+    /// Number of changes made so far, which orders the snapshots.
+    generation: u64,
+    /// Whether the items have changed since the last snapshot.
+    dirty: bool,
+    /// Items added since the last snapshot, released if the snapshot cannot be saved.
+    pushed: Vec<u64>,
+    /// Generation of the last snapshot written to the file, shared with the snapshots being saved.
+    saved_generation: Arc<Mutex<u64>>,
+}
+
+impl Outbox {
+    pub(crate) fn set_path(&mut self, path: PathBuf) {
+        self.path = Some(path);
+        self.items = None;
+        self.in_flight.clear();
+        self.dirty = false;
+        self.pushed.clear();
+        self.saved_generation = Default::default();
+    }
+
+    /// Add an item, which is in flight until it is removed or released.
+    pub(crate) fn push(&mut self, conversation_id: String, text: String) -> io::Result<u64> {
+        let id = self.next_id;
+        self.items()?.push(OutboxItem {
+            id,
+            conversation_id,
+            text,
+        });
+        self.next_id += 1;
+        self.in_flight.insert(id);
+        self.pushed.push(id);
+        self.dirty = true;
+        Ok(id)
+    }
+
+    pub(crate) fn remove(&mut self, id: u64) -> io::Result<()> {
+        self.items()?.retain(|item| item.id != id);
+        self.in_flight.remove(&id);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Put an item that failed to be sent back, so that it can be claimed again.
+    pub(crate) fn release(&mut self, id: u64) {
+        self.in_flight.remove(&id);
+    }
+
+    /// Take the items that are not in flight and mark them in flight.
+    pub(crate) fn claim(&mut self) -> io::Result<Vec<OutboxItem>> {
+        let items = self.items()?.clone();
+        let claimed: Vec<OutboxItem> = items
+            .into_iter()
+            .filter(|item| !self.in_flight.contains(&item.id))
+            .collect();
+        self.in_flight.extend(claimed.iter().map(|item| item.id));
+        Ok(claimed)
+    }
+
+    /// Take a snapshot of the items to save if they have changed and a path is set.
+    pub(crate) fn snapshot(&mut self) -> Option<OutboxSnapshot> {
+        if !mem::take(&mut self.dirty) {
+            return None;
+        }
+        let pushed = mem::take(&mut self.pushed);
+        self.generation += 1;
+        match (&self.path, &self.items) {
+            (Some(path), Some(items)) => Some(OutboxSnapshot {
+                path: path.clone(),
+                items: items.clone(),
+                pushed,
+                generation: self.generation,
+                saved_generation: self.saved_generation.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn items(&mut self) -> io::Result<&mut Vec<OutboxItem>> {
+        if self.items.is_none() {
+            let items: Vec<OutboxItem> = match &self.path {
+                Some(path) if path.exists() => serde_json::from_str(&fs::read_to_string(path)?)?,
+                _ => vec![],
+            };
+            let next_id = items.iter().map(|item| item.id + 1).max().unwrap_or_default();
+            self.next_id = self.next_id.max(next_id);
+            self.items = Some(items);
+        }
+        Ok(self.items.get_or_insert_with(Vec::new))
+    }
+}
+
+/// The items of an outbox at some point, to be written to its file, see `Outbox::snapshot`.
+pub(crate) struct OutboxSnapshot {
+    path: PathBuf,
+    items: Vec<OutboxItem>,
+    pub(crate) pushed: Vec<u64>,
+    generation: u64,
+    saved_generation: Arc<Mutex<u64>>,
+}
+
+impl OutboxSnapshot {
+    /// Write to a temporary file first, so that a crash while saving does not corrupt the outbox.
+    ///
+    /// Nothing is written if a later snapshot has already been saved, which includes the changes of this one.
+    pub(crate) fn save(&self) -> io::Result<()> {
+        let mut saved_generation = self.saved_generation.lock().unwrap();
+        if *saved_generation >= self.generation {
+            return Ok(());
+        }
+        let temp_path = temp_path(&self.path);
+        fs::write(&temp_path, serde_json::to_string(&self.items)?)?;
+        fs::rename(temp_path, &self.path)?;
+        *saved_generation = self.generation;
+        Ok(())
+    }
+}
+
+/// Append a suffix instead of replacing the extension, which would be the file itself for a path ending in `.tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = OsString::from(path.as_os_str());
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_path_never_is_the_outbox_itself() {
+        assert_eq!(temp_path(Path::new("outbox.json")), PathBuf::from("outbox.json.tmp"));
+        assert_eq!(temp_path(Path::new("outbox.tmp")), PathBuf::from("outbox.tmp.tmp"));
+    }
+
+    #[test]
+    fn ids_are_not_reused_once_the_outbox_is_empty() {
+        let mut outbox = Outbox::default();
+        let first = outbox.push("alice".to_owned(), "first".to_owned()).unwrap();
+        outbox.remove(first).unwrap();
+        let second = outbox.push("alice".to_owned(), "second".to_owned()).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn stale_snapshots_are_not_saved() {
+        // A path ending in `.tmp` must not be mistaken for its temporary file either.
+        let path = std::env::temp_dir().join(format!("wechaty-outbox-snapshot-{}.tmp", std::process::id()));
+        let mut outbox = Outbox::default();
+        outbox.set_path(path.clone());
+        outbox.push("alice".to_owned(), "first".to_owned()).unwrap();
+        let stale = outbox.snapshot().unwrap();
+        outbox.push("alice".to_owned(), "second".to_owned()).unwrap();
+        outbox.snapshot().unwrap().save().unwrap();
+
+        // The first snapshot is saved last, e.g. by a slower thread.
+        stale.save().unwrap();
+        let items: Vec<OutboxItem> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(items.len(), 2);
+        fs::remove_file(path).unwrap();
+    }
+}
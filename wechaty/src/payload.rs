@@ -1,10 +1,14 @@
 use wechaty_puppet::{
-    EventDongPayload, EventErrorPayload, EventHeartbeatPayload, EventReadyPayload, EventResetPayload, EventScanPayload,
-    PuppetImpl,
+    EventConnectionChangePayload, EventDirtyPayload, EventDongPayload, EventErrorPayload, EventHeartbeatPayload,
+    EventReadyPayload, EventResetPayload, EventScanPayload, PuppetImpl,
 };
 
 use crate::user::contact_self::ContactSelf;
-use crate::{Contact, Friendship, Message, Room, RoomInvitation};
+use crate::{Contact, Friendship, Message, Room, RoomInvitation, WechatyError};
+
+pub type ConnectionChangePayload = EventConnectionChangePayload;
+
+pub type DirtyPayload = EventDirtyPayload;
 
 pub type DongPayload = EventDongPayload;
 
@@ -43,6 +47,10 @@ where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
 {
     pub message: Message<T>,
+    /// The room of the message, already loaded, `None` for direct messages.
+    pub room: Option<Room<T>>,
+    /// The sender of the message, already loaded.
+    pub from: Option<Contact<T>>,
 }
 
 pub type ScanPayload = EventScanPayload;
@@ -70,6 +78,24 @@ where
     pub timestamp: u64,
 }
 
+impl<T> RoomJoinPayload<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    /// Welcome every invitee in the room, see `Room::welcome`.
+    pub async fn welcome_all(&self, template: &str) -> Result<Vec<Message<T>>, WechatyError> {
+        let mut message_list = vec![];
+        for invitee in self.invitee_list.iter() {
+            match self.room.welcome(template, invitee).await {
+                Ok(Some(message)) => message_list.push(message),
+                Ok(None) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(message_list)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RoomLeavePayload<T>
 where
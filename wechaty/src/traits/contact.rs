@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use log::{debug, error};
-use wechaty_puppet::{ContactGender, ContactPayload, PayloadType, PuppetImpl};
+use wechaty_puppet::{ContactGender, ContactPayload, ContactType, FileBox, PayloadType, PuppetImpl};
 
 use crate::{Talkable, WechatyError};
 
@@ -23,16 +23,16 @@ where
             Ok(())
         } else {
             let id = self.id();
-            let mut puppet = self.ctx().puppet();
+            let puppet = self.ctx().puppet();
             if force_sync {
-                if let Err(e) = puppet.dirty_payload(PayloadType::Contact, id.clone()).await {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Contact, id.clone()).await {
                     error!("Error occurred while syncing contact {}: {}", id, e);
-                    return Err(WechatyError::from(e));
+                    return Err(e);
                 }
             }
             match puppet.contact_payload(id.clone()).await {
                 Ok(payload) => {
-                    self.ctx().contacts().insert(id, payload.clone());
+                    self.ctx().update_contact(id, payload.clone());
                     self.set_payload(Some(payload));
                     Ok(())
                 }
@@ -54,6 +54,11 @@ where
         self.payload().as_ref().map(|payload| payload.name.clone())
     }
 
+    fn contact_type(&self) -> Option<ContactType> {
+        debug!("contact.contact_type(id = {})", self.id());
+        self.payload().as_ref().map(|payload| payload.contact_type.clone())
+    }
+
     fn gender(&self) -> Option<ContactGender> {
         debug!("contact.gender(id = {})", self.id());
         self.payload().as_ref().map(|payload| payload.gender.clone())
@@ -84,9 +89,59 @@ where
         self.payload().as_ref().map(|payload| payload.alias.clone())
     }
 
+    /// The name of the contact before it was last changed.
+    ///
+    /// Always `None` unless contact history tracking is enabled by `Wechaty::track_contact_history`.
+    fn previous_name(&self) -> Option<String> {
+        debug!("contact.previous_name(id = {})", self.id());
+        self.ctx().previous_name(&self.id())
+    }
+
+    /// The alias of the contact before it was last changed.
+    ///
+    /// Always `None` unless contact history tracking is enabled by `Wechaty::track_contact_history`.
+    fn previous_alias(&self) -> Option<String> {
+        debug!("contact.previous_alias(id = {})", self.id());
+        self.ctx().previous_alias(&self.id())
+    }
+
+    /// Set the alias of the contact, a mismatch in the readback is only logged, see `set_alias_verified`.
     async fn set_alias(&mut self, new_alias: String) -> Result<(), WechatyError> {
         debug!("contact.set_alias(id = {}, new_alias = {})", self.id(), new_alias);
-        let mut puppet = self.ctx().puppet();
+        match self.set_alias_verified(new_alias).await {
+            Ok(_) | Err(WechatyError::Maybe(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Clear the alias of the contact and read it back.
+    ///
+    /// Gateways take an empty alias as clearing it, if the readback still has an alias `WechatyError::Maybe` is
+    /// returned, as the gateway may not have applied the change yet.
+    async fn clear_alias(&mut self) -> Result<(), WechatyError> {
+        debug!("contact.clear_alias(id = {})", self.id());
+        match self.set_alias_verified(String::new()).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(WechatyError::Maybe(format!(
+                "alias of {} is cleared but still has {:?}",
+                self.identity(),
+                self.alias().unwrap_or_default()
+            ))),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Set the alias of the contact and read it back, return whether the readback matches the new alias.
+    ///
+    /// Some gateways silently ignore alias changes, which can be detected with this. If the alias is set but
+    /// cannot be read back, `WechatyError::Maybe` is returned.
+    async fn set_alias_verified(&mut self, new_alias: String) -> Result<bool, WechatyError> {
+        debug!(
+            "contact.set_alias_verified(id = {}, new_alias = {})",
+            self.id(),
+            new_alias
+        );
+        let puppet = self.ctx().puppet();
         let id = self.id();
         match puppet.contact_alias_set(id.clone(), new_alias.clone()).await {
             Err(e) => {
@@ -94,24 +149,165 @@ where
                 Err(WechatyError::from(e))
             }
             Ok(_) => {
-                if let Err(e) = puppet.dirty_payload(PayloadType::Contact, id.clone()).await {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Contact, id.clone()).await {
                     error!("Failed to dirty payload for {}, reason: {}", self.identity(), e);
                 }
                 match puppet.contact_payload(id.clone()).await {
                     Ok(payload) => {
-                        if payload.alias != new_alias {
+                        let verified = payload.alias == new_alias;
+                        if !verified {
                             error!("Payload is not correctly set.");
                         }
+                        self.ctx().update_contact(id, payload.clone());
+                        self.set_payload(Some(payload));
+                        Ok(verified)
                     }
                     Err(e) => {
                         error!("Failed to verify payload for {}, reason: {}", self.identity(), e);
+                        Err(WechatyError::Maybe(format!(
+                            "alias of {} is set but cannot be verified: {}",
+                            self.identity(),
+                            e
+                        )))
                     }
-                };
+                }
+            }
+        }
+    }
+
+    /// Get the avatar of the contact, which is cached for a few minutes to avoid downloading it again.
+    async fn avatar(&self) -> Result<FileBox, WechatyError> {
+        debug!("contact.avatar(id = {})", self.id());
+        self.ctx().contact_avatar(self.id()).await
+    }
+
+    /// Get the description the bot wrote for the contact.
+    fn description(&self) -> Option<String> {
+        debug!("contact.description(id = {})", self.id());
+        self.payload().as_ref().map(|payload| payload.description.clone())
+    }
+
+    /// Get the corporation of the contact, which is the corporation remark if the bot has set one.
+    fn corporation(&self) -> Option<String> {
+        debug!("contact.corporation(id = {})", self.id());
+        self.payload().as_ref().map(|payload| payload.corporation.clone())
+    }
+
+    /// Set the description of the contact, an empty description clears it.
+    async fn set_description(&mut self, description: String) -> Result<(), WechatyError> {
+        debug!(
+            "contact.set_description(id = {}, description = {})",
+            self.id(),
+            description
+        );
+        let description = if description.is_empty() {
+            None
+        } else {
+            Some(description)
+        };
+        match self
+            .ctx()
+            .puppet()
+            .contact_description_set(self.id(), description)
+            .await
+        {
+            Err(e) => {
+                error!("Failed to set description for {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+            Ok(_) => {
+                if let Err(e) = self.sync().await {
+                    error!("Failed to verify payload for {}, reason: {}", self.identity(), e);
+                }
                 Ok(())
             }
         }
     }
 
+    /// Set the corporation remark of the contact, an empty remark clears it.
+    async fn set_corporation_remark(&mut self, corporation_remark: String) -> Result<(), WechatyError> {
+        debug!(
+            "contact.set_corporation_remark(id = {}, corporation_remark = {})",
+            self.id(),
+            corporation_remark
+        );
+        let corporation_remark = if corporation_remark.is_empty() {
+            None
+        } else {
+            Some(corporation_remark)
+        };
+        match self
+            .ctx()
+            .puppet()
+            .contact_corporation_remark_set(self.id(), corporation_remark)
+            .await
+        {
+            Err(e) => {
+                error!(
+                    "Failed to set corporation remark for {}, reason: {}",
+                    self.identity(),
+                    e
+                );
+                Err(WechatyError::from(e))
+            }
+            Ok(_) => {
+                if let Err(e) = self.sync().await {
+                    error!("Failed to verify payload for {}, reason: {}", self.identity(), e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Block the contact, which is not supported by all gateways.
+    async fn block(&self) -> Result<(), WechatyError> {
+        debug!("contact.block(id = {})", self.id());
+        if self.is_self() {
+            return Err(WechatyError::InvalidOperation("block the bot itself".to_owned()));
+        }
+        match self.ctx().puppet().contact_block(self.id()).await {
+            Err(e) => {
+                error!("Failed to block {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+            Ok(_) => {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Contact, self.id()).await {
+                    error!("Failed to dirty payload for {}, reason: {}", self.identity(), e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Unblock the contact, which is not supported by all gateways.
+    async fn unblock(&self) -> Result<(), WechatyError> {
+        debug!("contact.unblock(id = {})", self.id());
+        if self.is_self() {
+            return Err(WechatyError::InvalidOperation("unblock the bot itself".to_owned()));
+        }
+        match self.ctx().puppet().contact_unblock(self.id()).await {
+            Err(e) => {
+                error!("Failed to unblock {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+            Ok(_) => {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Contact, self.id()).await {
+                    error!("Failed to dirty payload for {}, reason: {}", self.identity(), e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Check whether the contact is blocked, which is not supported by all gateways.
+    async fn is_blocked(&self) -> Result<bool, WechatyError> {
+        debug!("contact.is_blocked(id = {})", self.id());
+        match self.ctx().puppet().contact_is_blocked(self.id()).await {
+            Ok(blocked) => Ok(blocked),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
     /// Check if current contact is the bot self.
     fn is_self(&self) -> bool {
         debug!("contact.is_self(id = {})", self.id());
@@ -1,22 +1,33 @@
-use std::cell::RefCell;
 use std::future::Future;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use actix::{Actor, ActorFutureExt, AtomicResponse, Context, Handler, Recipient, WrapFuture};
-use log::{error, info};
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, AtomicResponse, Context, Handler, Message as ActixMessage,
+    Recipient, WrapFuture,
+};
+use log::{debug, error, info, warn};
+use regex::{Regex, RegexBuilder};
 use wechaty_puppet::{
-    AsyncFnPtr, EventDongPayload, EventErrorPayload, EventFriendshipPayload, EventHeartbeatPayload, EventLoginPayload,
-    EventLogoutPayload, EventMessagePayload, EventReadyPayload, EventResetPayload, EventRoomInvitePayload,
-    EventRoomJoinPayload, EventRoomLeavePayload, EventRoomTopicPayload, EventScanPayload, IntoAsyncFnPtr, PayloadType,
-    Puppet, PuppetEvent, PuppetImpl, Subscribe,
+    AsyncFnPtr, ConnectionState, EventConnectionChangePayload, EventDirtyPayload, EventDongPayload, EventErrorPayload,
+    EventFriendshipPayload, EventHeartbeatPayload, EventLoginPayload, EventLogoutPayload, EventMessagePayload,
+    EventName, EventReadyPayload, EventResetPayload, EventRoomInvitePayload, EventRoomJoinPayload,
+    EventRoomLeavePayload, EventRoomTopicPayload, EventScanPayload, IntoAsyncFnPtr, PayloadType, Puppet, PuppetEvent,
+    PuppetImpl, Subscribe,
 };
 
 use crate::{
-    Contact, ContactSelf, DongPayload, ErrorPayload, Friendship, FriendshipPayload, HeartbeatPayload, IntoContact,
-    LoginPayload, LogoutPayload, Message, MessagePayload, ReadyPayload, ResetPayload, Room, RoomInvitation,
-    RoomInvitePayload, RoomJoinPayload, RoomLeavePayload, RoomTopicPayload, ScanPayload, WechatyContext,
+    ConnectionChangePayload, Contact, ContactSelf, DirtyPayload, DongPayload, ErrorPayload, Friendship,
+    FriendshipPayload, HeartbeatPayload, IntoContact, LoginPayload, LogoutPayload, Message, MessagePayload,
+    ReadyPayload, ResetPayload, Room, RoomInvitation, RoomInvitePayload, RoomJoinPayload, RoomLeavePayload,
+    RoomTopicPayload, ScanPayload, WechatyContext,
 };
 
+/// Register handlers for puppet events.
+///
+/// Events are handled one at a time in the order they arrive: all handlers of an event run to completion, in the
+/// order they were registered, before any handler of the next event starts. A slow handler therefore holds up the
+/// events after it, so long-running work should be spawned rather than awaited in the handler.
 pub trait EventListener<T>
 where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
@@ -33,24 +44,71 @@ where
         handler: AsyncFnPtr<Payload, WechatyContext<T>, ()>,
         limit: Option<usize>,
         handlers: HandlersPtr<T, Payload>,
-        event_name: &'static str,
+        event_name: EventName,
     ) -> (&mut Self, usize) {
         if let Err(e) = self.get_puppet().get_subscribe_addr().do_send(Subscribe {
             addr: self.get_addr(),
             name: self.get_name(),
             event_name,
         }) {
-            error!("{} failed to subscribe to event {}: {}", self.get_name(), event_name, e);
+            error!(
+                "{} failed to subscribe to event {}: {}",
+                self.get_name(),
+                event_name.as_str(),
+                e
+            );
         }
-        let counter = handlers.borrow().len();
         let limit = match limit {
             Some(limit) => limit,
             None => usize::MAX,
         };
-        handlers.borrow_mut().push((handler, limit));
+        let mut handlers = handlers.lock().unwrap();
+        let counter = handlers.len();
+        handlers.push((Some(handler), limit));
         (self, counter)
     }
 
+    fn on_connection_change<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: IntoAsyncFnPtr<ConnectionChangePayload, WechatyContext<T>, ()>,
+    {
+        self.on_connection_change_with_handle(handler, None);
+        self
+    }
+
+    fn on_connection_change_with_handle<F>(&mut self, handler: F, limit: Option<usize>) -> usize
+    where
+        F: IntoAsyncFnPtr<ConnectionChangePayload, WechatyContext<T>, ()>,
+    {
+        let connection_change_handlers = self.get_listener().connection_change_handlers.clone();
+        self.on_event_with_handle(
+            handler.into(),
+            limit,
+            connection_change_handlers,
+            EventName::ConnectionChange,
+        )
+        .1
+    }
+
+    /// Register a handler for dirty events, which the puppet emits when a payload has changed remotely. The cached
+    /// entity is invalidated before the handlers run, so reloading it in a handler fetches the fresh payload.
+    fn on_dirty<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: IntoAsyncFnPtr<DirtyPayload, WechatyContext<T>, ()>,
+    {
+        self.on_dirty_with_handle(handler, None);
+        self
+    }
+
+    fn on_dirty_with_handle<F>(&mut self, handler: F, limit: Option<usize>) -> usize
+    where
+        F: IntoAsyncFnPtr<DirtyPayload, WechatyContext<T>, ()>,
+    {
+        let dirty_handlers = self.get_listener().dirty_handlers.clone();
+        self.on_event_with_handle(handler.into(), limit, dirty_handlers, EventName::Dirty)
+            .1
+    }
+
     fn on_dong<F>(&mut self, handler: F) -> &mut Self
     where
         F: IntoAsyncFnPtr<DongPayload, WechatyContext<T>, ()>,
@@ -64,7 +122,7 @@ where
         F: IntoAsyncFnPtr<DongPayload, WechatyContext<T>, ()>,
     {
         let dong_handlers = self.get_listener().dong_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, dong_handlers, "dong")
+        self.on_event_with_handle(handler.into(), limit, dong_handlers, EventName::Dong)
             .1
     }
 
@@ -81,7 +139,7 @@ where
         F: IntoAsyncFnPtr<ErrorPayload, WechatyContext<T>, ()>,
     {
         let error_handlers = self.get_listener().error_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, error_handlers, "error")
+        self.on_event_with_handle(handler.into(), limit, error_handlers, EventName::Error)
             .1
     }
 
@@ -98,7 +156,7 @@ where
         F: IntoAsyncFnPtr<FriendshipPayload<T>, WechatyContext<T>, ()>,
     {
         let friendship_handlers = self.get_listener().friendship_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, friendship_handlers, "friendship")
+        self.on_event_with_handle(handler.into(), limit, friendship_handlers, EventName::Friendship)
             .1
     }
 
@@ -115,7 +173,7 @@ where
         F: IntoAsyncFnPtr<HeartbeatPayload, WechatyContext<T>, ()>,
     {
         let heartbeat_handlers = self.get_listener().heartbeat_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, heartbeat_handlers, "heartbeat")
+        self.on_event_with_handle(handler.into(), limit, heartbeat_handlers, EventName::Heartbeat)
             .1
     }
 
@@ -132,7 +190,7 @@ where
         F: IntoAsyncFnPtr<LoginPayload<T>, WechatyContext<T>, ()>,
     {
         let login_handlers = self.get_listener().login_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, login_handlers, "login")
+        self.on_event_with_handle(handler.into(), limit, login_handlers, EventName::Login)
             .1
     }
 
@@ -149,7 +207,7 @@ where
         F: IntoAsyncFnPtr<LogoutPayload<T>, WechatyContext<T>, ()>,
     {
         let logout_handlers = self.get_listener().logout_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, logout_handlers, "logout")
+        self.on_event_with_handle(handler.into(), limit, logout_handlers, EventName::Logout)
             .1
     }
 
@@ -166,10 +224,53 @@ where
         F: IntoAsyncFnPtr<MessagePayload<T>, WechatyContext<T>, ()>,
     {
         let message_handlers = self.get_listener().message_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, message_handlers, "message")
+        self.on_event_with_handle(handler.into(), limit, message_handlers, EventName::Message)
             .1
     }
 
+    /// Handle the messages whose text matches a pattern, see `on_message_matching_with_options`.
+    fn on_message_matching<F>(&mut self, pattern: Regex, handler: F) -> &mut Self
+    where
+        F: IntoAsyncFnPtr<MessagePayload<T>, WechatyContext<T>, ()>,
+    {
+        self.on_message_matching_with_options(pattern, handler, MessageMatchOptions::default());
+        self
+    }
+
+    /// Handle the messages whose text matches a pattern, other messages are filtered out before the handler.
+    ///
+    /// The limit in the options only counts the matched messages.
+    fn on_message_matching_with_options<F>(&mut self, pattern: Regex, handler: F, options: MessageMatchOptions) -> usize
+    where
+        F: IntoAsyncFnPtr<MessagePayload<T>, WechatyContext<T>, ()>,
+    {
+        let pattern = options.build_pattern(pattern);
+        let handler = handler.into();
+        let remaining = AtomicUsize::new(options.limit.unwrap_or(usize::MAX));
+        let skip_self = options.skip_self;
+        self.on_message_with_handle(
+            move |payload: MessagePayload<T>, ctx: WechatyContext<T>| {
+                let is_self = payload.from.as_ref().map(|from| from.id()) == ctx.id() && ctx.id().is_some();
+                let is_match = !(skip_self && is_self)
+                    && payload.message.text().is_some_and(|text| pattern.is_match(&text))
+                    && remaining
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| remaining.checked_sub(1))
+                        .is_ok();
+                let future = if is_match {
+                    Some(handler.call(payload, ctx))
+                } else {
+                    None
+                };
+                async move {
+                    if let Some(future) = future {
+                        future.await
+                    }
+                }
+            },
+            None,
+        )
+    }
+
     fn on_ready<F>(&mut self, handler: F) -> &mut Self
     where
         F: IntoAsyncFnPtr<ReadyPayload, WechatyContext<T>, ()>,
@@ -183,7 +284,7 @@ where
         F: IntoAsyncFnPtr<ReadyPayload, WechatyContext<T>, ()>,
     {
         let ready_handlers = self.get_listener().ready_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, ready_handlers, "ready")
+        self.on_event_with_handle(handler.into(), limit, ready_handlers, EventName::Ready)
             .1
     }
 
@@ -200,7 +301,7 @@ where
         F: IntoAsyncFnPtr<ResetPayload, WechatyContext<T>, ()>,
     {
         let reset_handlers = self.get_listener().reset_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, reset_handlers, "reset")
+        self.on_event_with_handle(handler.into(), limit, reset_handlers, EventName::Reset)
             .1
     }
 
@@ -217,7 +318,7 @@ where
         F: IntoAsyncFnPtr<RoomInvitePayload<T>, WechatyContext<T>, ()>,
     {
         let room_invite_handlers = self.get_listener().room_invite_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, room_invite_handlers, "room-invite")
+        self.on_event_with_handle(handler.into(), limit, room_invite_handlers, EventName::RoomInvite)
             .1
     }
 
@@ -234,7 +335,7 @@ where
         F: IntoAsyncFnPtr<RoomJoinPayload<T>, WechatyContext<T>, ()>,
     {
         let room_join_handlers = self.get_listener().room_join_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, room_join_handlers, "room-join")
+        self.on_event_with_handle(handler.into(), limit, room_join_handlers, EventName::RoomJoin)
             .1
     }
 
@@ -251,7 +352,7 @@ where
         F: IntoAsyncFnPtr<RoomLeavePayload<T>, WechatyContext<T>, ()>,
     {
         let room_leave_handlers = self.get_listener().room_leave_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, room_leave_handlers, "room-leave")
+        self.on_event_with_handle(handler.into(), limit, room_leave_handlers, EventName::RoomLeave)
             .1
     }
 
@@ -268,7 +369,7 @@ where
         F: IntoAsyncFnPtr<RoomTopicPayload<T>, WechatyContext<T>, ()>,
     {
         let room_topic_handlers = self.get_listener().room_topic_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, room_topic_handlers, "room-topic")
+        self.on_event_with_handle(handler.into(), limit, room_topic_handlers, EventName::RoomTopic)
             .1
     }
 
@@ -285,12 +386,52 @@ where
         F: IntoAsyncFnPtr<ScanPayload, WechatyContext<T>, ()>,
     {
         let scan_handlers = self.get_listener().scan_handlers.clone();
-        self.on_event_with_handle(handler.into(), limit, scan_handlers, "scan")
+        self.on_event_with_handle(handler.into(), limit, scan_handlers, EventName::Scan)
             .1
     }
 }
 
-type HandlersPtr<T, Payload> = Rc<RefCell<Vec<(AsyncFnPtr<Payload, WechatyContext<T>, ()>, usize)>>>;
+/// Options of `EventListener::on_message_matching_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct MessageMatchOptions {
+    /// Match the pattern ignoring case.
+    pub case_insensitive: bool,
+    /// Only match the pattern as whole words, e.g. `ding` does not match `dingdong`.
+    pub whole_word: bool,
+    /// Skip the messages sent by the bot itself.
+    pub skip_self: bool,
+    /// The number of matched messages to handle, unlimited if `None`.
+    pub limit: Option<usize>,
+}
+
+impl MessageMatchOptions {
+    fn build_pattern(&self, pattern: Regex) -> Regex {
+        if !self.case_insensitive && !self.whole_word {
+            return pattern;
+        }
+        let source = if self.whole_word {
+            format!(r"\b(?:{})\b", pattern.as_str())
+        } else {
+            pattern.as_str().to_owned()
+        };
+        match RegexBuilder::new(&source)
+            .case_insensitive(self.case_insensitive)
+            .build()
+        {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                error!("Failed to apply options to pattern {}: {}", pattern, e);
+                pattern
+            }
+        }
+    }
+}
+
+/// Handlers of an event with their remaining limits.
+///
+/// A handler is taken out of its slot while it runs, so that no lock is held across user code, which may
+/// register more handlers.
+type HandlersPtr<T, Payload> = Arc<Mutex<Vec<(Option<AsyncFnPtr<Payload, WechatyContext<T>, ()>>, usize)>>>;
 
 #[derive(Clone)]
 pub struct EventListenerInner<T>
@@ -299,6 +440,8 @@ where
 {
     name: String,
     ctx: WechatyContext<T>,
+    connection_change_handlers: HandlersPtr<T, ConnectionChangePayload>,
+    dirty_handlers: HandlersPtr<T, DirtyPayload>,
     dong_handlers: HandlersPtr<T, DongPayload>,
     error_handlers: HandlersPtr<T, ErrorPayload>,
     friendship_handlers: HandlersPtr<T, FriendshipPayload<T>>,
@@ -330,15 +473,122 @@ where
     }
 }
 
+/// A listener driven by hand, see `EventListenerInner::test_harness`.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Clone)]
+pub struct EventHarness<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    puppet: Puppet<T>,
+    listener: EventListenerInner<T>,
+    addr: Addr<EventListenerInner<T>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl<T> EventHarness<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    /// Handle an event, returning once all of its handlers have finished.
+    pub async fn inject(&self, event: PuppetEvent) {
+        if let Err(e) = self.addr.send(event).await {
+            error!("Failed to inject event: {}", e);
+        }
+    }
+
+    /// Inject events one after another, e.g. a session recorded with `EventRecorder` and read with `read_events`.
+    pub async fn replay(&self, events: Vec<PuppetEvent>) {
+        for event in events {
+            self.inject(event).await;
+        }
+    }
+
+    /// Wait until the events queued by the listener itself have been handled, e.g. the room join and room leave
+    /// events synthesized by `Wechaty::reconcile_room_members`, which `inject` does not wait for.
+    pub async fn flush(&self) {
+        if let Err(e) = self.addr.send(Flush).await {
+            error!("Failed to flush events: {}", e);
+        }
+    }
+}
+
+/// Do nothing, which returns once the messages queued before it have been handled, see `EventHarness::flush`.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(ActixMessage)]
+#[rtype("()")]
+struct Flush;
+
+#[cfg(any(test, feature = "test-util"))]
+impl<T> Handler<Flush> for EventListenerInner<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    type Result = ();
+
+    fn handle(&mut self, _msg: Flush, _ctx: &mut Context<Self>) -> Self::Result {}
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl<T> EventListener<T> for EventHarness<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn get_listener(&self) -> &EventListenerInner<T> {
+        &self.listener
+    }
+
+    fn get_puppet(&self) -> Puppet<T> {
+        self.puppet.clone()
+    }
+
+    fn get_addr(&self) -> Recipient<PuppetEvent> {
+        self.addr.clone().recipient()
+    }
+}
+
+/// Stop the listener actor, see the `Drop` implementation of `Wechaty`.
+#[derive(ActixMessage)]
+#[rtype("()")]
+pub(crate) struct StopListener;
+
+impl<T> Handler<StopListener> for EventListenerInner<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    type Result = ();
+
+    fn handle(&mut self, _msg: StopListener, ctx: &mut Context<Self>) -> Self::Result {
+        ctx.stop();
+    }
+}
+
 impl<T> Handler<PuppetEvent> for EventListenerInner<T>
 where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
 {
+    // An atomic response keeps the listener from handling the next event until the handlers of this one finish,
+    // which is what guarantees the ordering documented on `EventListener`.
     type Result = AtomicResponse<Self, ()>;
 
-    fn handle(&mut self, msg: PuppetEvent, _ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: PuppetEvent, ctx: &mut Context<Self>) -> Self::Result {
         info!("{} receives puppet event: {:?}", self.name.clone(), msg);
         match msg {
+            PuppetEvent::ConnectionChange(payload) => {
+                let reconcile = payload.state == ConnectionState::Connected && self.ctx.reconciles_room_members();
+                let addr = ctx.address();
+                AtomicResponse::new(Box::pin(
+                    async {}
+                        .into_actor(self)
+                        .then(move |_, this, _| this.trigger_connection_change_handlers(payload).into_actor(this))
+                        .then(move |_, this, _| this.reconcile_room_members(reconcile, addr).into_actor(this)),
+                ))
+            }
+            PuppetEvent::Dirty(payload) => AtomicResponse::new(Box::pin(
+                async {}
+                    .into_actor(self)
+                    .then(move |_, this, _| this.trigger_dirty_handlers(payload).into_actor(this)),
+            )),
             PuppetEvent::Dong(payload) => AtomicResponse::new(Box::pin(
                 async {}
                     .into_actor(self)
@@ -415,7 +665,6 @@ where
                     .into_actor(self)
                     .then(move |_, this, _| this.trigger_scan_handlers(payload).into_actor(this)),
             )),
-            _ => AtomicResponse::new(Box::pin(async {}.into_actor(self))),
         }
     }
 }
@@ -428,23 +677,44 @@ where
         Self {
             name,
             ctx,
-            dong_handlers: Rc::new(RefCell::new(vec![])),
-            error_handlers: Rc::new(RefCell::new(vec![])),
-            friendship_handlers: Rc::new(RefCell::new(vec![])),
-            heartbeat_handlers: Rc::new(RefCell::new(vec![])),
-            login_handlers: Rc::new(RefCell::new(vec![])),
-            logout_handlers: Rc::new(RefCell::new(vec![])),
-            message_handlers: Rc::new(RefCell::new(vec![])),
-            ready_handlers: Rc::new(RefCell::new(vec![])),
-            reset_handlers: Rc::new(RefCell::new(vec![])),
-            room_invite_handlers: Rc::new(RefCell::new(vec![])),
-            room_join_handlers: Rc::new(RefCell::new(vec![])),
-            room_leave_handlers: Rc::new(RefCell::new(vec![])),
-            room_topic_handlers: Rc::new(RefCell::new(vec![])),
-            scan_handlers: Rc::new(RefCell::new(vec![])),
+            connection_change_handlers: Arc::new(Mutex::new(vec![])),
+            dirty_handlers: Arc::new(Mutex::new(vec![])),
+            dong_handlers: Arc::new(Mutex::new(vec![])),
+            error_handlers: Arc::new(Mutex::new(vec![])),
+            friendship_handlers: Arc::new(Mutex::new(vec![])),
+            heartbeat_handlers: Arc::new(Mutex::new(vec![])),
+            login_handlers: Arc::new(Mutex::new(vec![])),
+            logout_handlers: Arc::new(Mutex::new(vec![])),
+            message_handlers: Arc::new(Mutex::new(vec![])),
+            ready_handlers: Arc::new(Mutex::new(vec![])),
+            reset_handlers: Arc::new(Mutex::new(vec![])),
+            room_invite_handlers: Arc::new(Mutex::new(vec![])),
+            room_join_handlers: Arc::new(Mutex::new(vec![])),
+            room_leave_handlers: Arc::new(Mutex::new(vec![])),
+            room_topic_handlers: Arc::new(Mutex::new(vec![])),
+            scan_handlers: Arc::new(Mutex::new(vec![])),
         }
     }
 
+    pub(crate) fn ctx(&self) -> WechatyContext<T> {
+        self.ctx.clone()
+    }
+
+    /// Create a listener whose events are injected by hand instead of coming from the puppet.
+    ///
+    /// Register handlers on the returned harness as on a bot, then `EventHarness::inject` an event and await it,
+    /// which returns once all handlers of the event have finished.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn test_harness(ctx: WechatyContext<T>) -> (Self, EventHarness<T>) {
+        let listener = Self::new("EventHarness".to_owned(), ctx);
+        let harness = EventHarness {
+            puppet: listener.ctx().puppet(),
+            listener: listener.clone(),
+            addr: listener.clone().start(),
+        };
+        (listener, harness)
+    }
+
     async fn trigger_handlers<Payload: Clone + 'static>(
         ctx: WechatyContext<T>,
         payload: Payload,
@@ -452,16 +722,82 @@ where
     ) where
         T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
     {
-        let len = handlers.borrow_mut().len();
+        // Handlers registered while triggering are not run for this event.
+        let len = handlers.lock().unwrap().len();
         for i in 0..len {
-            let mut handler = &mut handlers.borrow_mut()[i];
-            if handler.1 > 0 {
-                handler.0.run(payload.clone(), ctx.clone()).await;
-                handler.1 -= 1;
+            let handler = {
+                let mut handlers = handlers.lock().unwrap();
+                let (handler, limit) = &mut handlers[i];
+                if *limit > 0 {
+                    *limit -= 1;
+                    handler.take()
+                } else {
+                    None
+                }
+            };
+            if let Some(handler) = handler {
+                handler.run(payload.clone(), ctx.clone()).await;
+                handlers.lock().unwrap()[i].0 = Some(handler);
             }
         }
     }
 
+    /// Load the contacts of an event, which are left un-synced if entities are lazy.
+    async fn load_contacts(ctx: &WechatyContext<T>, contact_id_list: Vec<String>) -> Vec<Contact<T>> {
+        if ctx.lazy_entities() {
+            contact_id_list
+                .into_iter()
+                .map(|contact_id| Contact::new(contact_id, ctx.clone(), None))
+                .collect()
+        } else {
+            ctx.contact_load_batch(contact_id_list).await
+        }
+    }
+
+    fn trigger_connection_change_handlers(
+        &mut self,
+        payload: EventConnectionChangePayload,
+    ) -> impl Future<Output = ()> + 'static {
+        let ctx = self.ctx.clone();
+        let handlers = self.connection_change_handlers.clone();
+        async move { EventListenerInner::<T>::trigger_handlers(ctx, payload, handlers).await }
+    }
+
+    /// Synthesize room join and room leave events for the members that changed while disconnected, see
+    /// `Wechaty::reconcile_room_members`.
+    fn reconcile_room_members(&mut self, reconcile: bool, addr: Addr<Self>) -> impl Future<Output = ()> + 'static {
+        let ctx = self.ctx.clone();
+        async move {
+            if !reconcile {
+                return;
+            }
+            let (join_list, leave_list) = ctx.reconcile_room_members().await;
+            for payload in join_list {
+                addr.do_send(PuppetEvent::RoomJoin(payload));
+            }
+            for payload in leave_list {
+                addr.do_send(PuppetEvent::RoomLeave(payload));
+            }
+        }
+    }
+
+    fn trigger_dirty_handlers(&mut self, payload: EventDirtyPayload) -> impl Future<Output = ()> + 'static {
+        let ctx = self.ctx.clone();
+        let handlers = self.dirty_handlers.clone();
+        async move {
+            if let Err(e) = ctx
+                .invalidate(payload.payload_type.clone(), payload.payload_id.clone())
+                .await
+            {
+                warn!(
+                    "Failed to invalidate {:?} {}: {}",
+                    payload.payload_type, payload.payload_id, e
+                );
+            }
+            EventListenerInner::<T>::trigger_handlers(ctx, payload, handlers).await
+        }
+    }
+
     fn trigger_dong_handlers(&mut self, payload: EventDongPayload) -> impl Future<Output = ()> + 'static {
         let ctx = self.ctx.clone();
         let handlers = self.dong_handlers.clone();
@@ -479,7 +815,9 @@ where
         let mut friendship = Friendship::new(payload.friendship_id, ctx.clone(), None);
         let handlers = self.friendship_handlers.clone();
         async move {
-            friendship.ready().await.unwrap_or_default();
+            if !ctx.lazy_entities() {
+                friendship.ready().await.unwrap_or_default();
+            }
             EventListenerInner::<T>::trigger_handlers(ctx, FriendshipPayload { friendship }, handlers).await
         }
     }
@@ -523,8 +861,17 @@ where
         let mut message = Message::new(payload.message_id, ctx.clone(), None);
         let handlers = self.message_handlers.clone();
         async move {
-            message.ready().await.unwrap_or_default();
-            EventListenerInner::<T>::trigger_handlers(ctx, MessagePayload { message }, handlers).await
+            if !ctx.lazy_entities() {
+                message.ready().await.unwrap_or_default();
+            }
+            ctx.count_unread(&message);
+            ctx.publish_message(message.clone());
+            if ctx.is_ignored_self_message(&message) {
+                return;
+            }
+            let room = message.room();
+            let from = message.from();
+            EventListenerInner::<T>::trigger_handlers(ctx, MessagePayload { message, room, from }, handlers).await
         }
     }
 
@@ -545,7 +892,9 @@ where
         let ctx = self.ctx.clone();
         let handlers = self.room_invite_handlers.clone();
         async move {
-            room_invitation.ready().await.unwrap_or_default();
+            if !ctx.lazy_entities() {
+                room_invitation.ready().await.unwrap_or_default();
+            }
             EventListenerInner::<T>::trigger_handlers(ctx, RoomInvitePayload { room_invitation }, handlers).await
         }
     }
@@ -556,9 +905,11 @@ where
         let mut room = Room::new(payload.room_id.clone(), ctx.clone(), None);
         let mut inviter = Contact::new(payload.inviter_id.clone(), ctx.clone(), None);
         async move {
-            room.sync().await.unwrap_or_default();
-            inviter.sync().await.unwrap_or_default();
-            let invitee_list = ctx.contact_load_batch(payload.invitee_id_list).await;
+            if !ctx.lazy_entities() {
+                room.sync().await.unwrap_or_default();
+                inviter.sync().await.unwrap_or_default();
+            }
+            let invitee_list = EventListenerInner::<T>::load_contacts(&ctx, payload.invitee_id_list).await;
             EventListenerInner::<T>::trigger_handlers(
                 ctx,
                 RoomJoinPayload {
@@ -579,9 +930,11 @@ where
         let mut room = Room::new(payload.room_id.clone(), ctx.clone(), None);
         let mut remover = Contact::new(payload.remover_id.clone(), ctx.clone(), None);
         async move {
-            room.sync().await.unwrap_or_default();
-            remover.sync().await.unwrap_or_default();
-            let removee_list = ctx.contact_load_batch(payload.removee_id_list.clone()).await;
+            if !ctx.lazy_entities() {
+                room.sync().await.unwrap_or_default();
+                remover.sync().await.unwrap_or_default();
+            }
+            let removee_list = EventListenerInner::<T>::load_contacts(&ctx, payload.removee_id_list.clone()).await;
             EventListenerInner::<T>::trigger_handlers(
                 ctx.clone(),
                 RoomLeavePayload {
@@ -595,12 +948,10 @@ where
             .await;
             let self_id = ctx.id().unwrap();
             if payload.removee_id_list.contains(&self_id) {
-                ctx.puppet()
-                    .dirty_payload(PayloadType::Room, payload.room_id.clone())
+                ctx.invalidate(PayloadType::Room, payload.room_id.clone())
                     .await
                     .unwrap_or_default();
-                ctx.puppet()
-                    .dirty_payload(PayloadType::RoomMember, payload.room_id)
+                ctx.invalidate(PayloadType::RoomMember, payload.room_id)
                     .await
                     .unwrap_or_default();
             }
@@ -613,8 +964,14 @@ where
         let mut room = Room::new(payload.room_id.clone(), ctx.clone(), None);
         let mut changer = Contact::new(payload.changer_id.clone(), ctx.clone(), None);
         async move {
-            room.sync().await.unwrap_or_default();
-            changer.sync().await.unwrap_or_default();
+            if ctx.is_room_echo(&payload.room_id, &payload.new_topic) {
+                debug!("Ignore room topic event echoed back for room {}", payload.room_id);
+                return;
+            }
+            if !ctx.lazy_entities() {
+                room.sync().await.unwrap_or_default();
+                changer.sync().await.unwrap_or_default();
+            }
             EventListenerInner::<T>::trigger_handlers(
                 ctx,
                 RoomTopicPayload {
@@ -636,3 +993,513 @@ where
         async move { EventListenerInner::<T>::trigger_handlers(ctx, payload, handlers).await }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
+    use futures::StreamExt;
+    use wechaty_puppet::{fixtures, ScanStatus};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::{MockClock, Wechaty};
+
+    fn message_event(message_id: &str) -> PuppetEvent {
+        PuppetEvent::Message(EventMessagePayload {
+            message_id: message_id.to_owned(),
+        })
+    }
+
+    fn login_event() -> PuppetEvent {
+        PuppetEvent::Login(EventLoginPayload {
+            contact_id: "self".to_owned(),
+        })
+    }
+
+    #[actix_rt::test]
+    async fn message_handlers_get_room_and_sender() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.messages().insert(
+            "message".to_owned(),
+            wechaty_puppet::MessagePayload {
+                room_id: "room".to_owned(),
+                ..fixtures::message("message", "alice", "hello")
+            },
+        );
+        ctx.rooms()
+            .insert("room".to_owned(), fixtures::room("room", "Rust", &["alice"]));
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let received = Arc::new(Mutex::new(None));
+        let received_clone = received.clone();
+        harness.on_message(move |payload: MessagePayload<PuppetMock>, _ctx| {
+            let received = received_clone.clone();
+            async move {
+                let room = payload.room.map(|room| (room.id(), room.topic()));
+                let from = payload.from.map(|from| from.id());
+                *received.lock().unwrap() = Some((room, from));
+            }
+        });
+
+        harness.inject(message_event("message")).await;
+        assert_eq!(
+            received.lock().unwrap().clone(),
+            Some((
+                Some(("room".to_owned(), Some("Rust".to_owned()))),
+                Some("alice".to_owned())
+            ))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn recorded_events_can_be_replayed() {
+        let path = std::env::temp_dir().join(format!("wechaty-recording-{}.jsonl", std::process::id()));
+        let puppet = Puppet::new(PuppetMock::default());
+        wechaty_puppet::EventRecorder::start(&puppet, &path).unwrap();
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        for data in ["a", "b"] {
+            puppet
+                .self_addr()
+                .do_send(PuppetEvent::Dong(EventDongPayload { data: data.to_owned() }))
+                .unwrap();
+        }
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+
+        let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+        let events = wechaty_puppet::read_events(file).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let (_listener, mut harness) = EventListenerInner::test_harness(WechatyContext::new(puppet));
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        harness.on_dong(move |payload: DongPayload, _ctx| {
+            let received = received_clone.clone();
+            async move {
+                received.lock().unwrap().push(payload.data);
+            }
+        });
+        harness.replay(events).await;
+        assert_eq!(*received.lock().unwrap(), vec!["a", "b"]);
+    }
+
+    #[actix_rt::test]
+    async fn can_inject_events_with_test_harness() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        harness.on_scan(move |payload: ScanPayload, _ctx| {
+            let received = received_clone.clone();
+            async move {
+                actix_rt::time::sleep(Duration::from_millis(50)).await;
+                received.lock().unwrap().push(payload.qrcode);
+            }
+        });
+
+        harness
+            .inject(PuppetEvent::Scan(EventScanPayload {
+                status: ScanStatus::Waiting,
+                qrcode: Some("qrcode".to_owned()),
+                data: None,
+            }))
+            .await;
+        assert_eq!(*received.lock().unwrap(), vec![Some("qrcode".to_owned())]);
+    }
+
+    #[actix_rt::test]
+    async fn dirty_events_invalidate_the_cache_before_handlers_run() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.messages().insert(
+            "dirty-message".to_owned(),
+            wechaty_puppet::MessagePayload {
+                to_id: "bob".to_owned(),
+                ..fixtures::message("dirty-message", "alice", "stale")
+            },
+        );
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx.clone());
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        harness.on_dirty(move |payload: DirtyPayload, ctx: WechatyContext<PuppetMock>| {
+            let received = received_clone.clone();
+            async move {
+                let cached = ctx.messages().contains_key(&payload.payload_id);
+                received.lock().unwrap().push((payload.payload_id, cached));
+            }
+        });
+
+        harness
+            .inject(PuppetEvent::Dirty(EventDirtyPayload {
+                payload_type: PayloadType::Message,
+                payload_id: "dirty-message".to_owned(),
+            }))
+            .await;
+        assert_eq!(*received.lock().unwrap(), vec![("dirty-message".to_owned(), false)]);
+        assert!(!ctx.messages().contains_key("dirty-message"));
+    }
+
+    #[actix_rt::test]
+    async fn handler_can_register_another_handler() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.messages()
+            .insert("message".to_owned(), fixtures::message("message", "", "hello"));
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let registering_harness = Arc::new(Mutex::new(harness.clone()));
+        let fired = Arc::new(Mutex::new(vec![]));
+        let fired_clone = fired.clone();
+        harness.on_message_with_handle(
+            move |_payload: MessagePayload<PuppetMock>, _ctx| {
+                let harness = registering_harness.clone();
+                let fired = fired_clone.clone();
+                async move {
+                    fired.lock().unwrap().push("outer");
+                    let fired_clone = fired.clone();
+                    harness
+                        .lock()
+                        .unwrap()
+                        .on_message(move |_payload: MessagePayload<PuppetMock>, _ctx| {
+                            let fired = fired_clone.clone();
+                            async move {
+                                fired.lock().unwrap().push("inner");
+                            }
+                        });
+                }
+            },
+            Some(1),
+        );
+
+        for _ in 0..2 {
+            harness.inject(message_event("message")).await;
+        }
+        assert_eq!(*fired.lock().unwrap(), vec!["outer", "inner"]);
+    }
+
+    #[actix_rt::test]
+    async fn room_members_are_reconciled_on_connect() {
+        let mock = PuppetMock::default();
+        let mut bot = Wechaty::new(Puppet::new(mock.clone()));
+        bot.reconcile_room_members(true);
+        let ctx = bot.ctx();
+        ctx.rooms().insert(
+            "reconciled-room".to_owned(),
+            fixtures::room("reconciled-room", "Rust", &["alice", "bob"]),
+        );
+        mock.set_room_members("reconciled-room".to_owned(), vec!["bob".to_owned(), "carol".to_owned()]);
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx.clone());
+        let joined = Arc::new(Mutex::new(vec![]));
+        let left = Arc::new(Mutex::new(vec![]));
+        let joined_clone = joined.clone();
+        harness.on_room_join(move |payload: RoomJoinPayload<PuppetMock>, _ctx| {
+            let joined = joined_clone.clone();
+            async move {
+                joined
+                    .lock()
+                    .unwrap()
+                    .extend(payload.invitee_list.iter().map(|contact| contact.id()));
+            }
+        });
+        let left_clone = left.clone();
+        harness.on_room_leave(move |payload: RoomLeavePayload<PuppetMock>, _ctx| {
+            let left = left_clone.clone();
+            async move {
+                left.lock()
+                    .unwrap()
+                    .extend(payload.removee_list.iter().map(|contact| contact.id()));
+            }
+        });
+
+        harness.inject(login_event()).await;
+        harness
+            .inject(PuppetEvent::ConnectionChange(EventConnectionChangePayload {
+                state: ConnectionState::Connected,
+            }))
+            .await;
+        harness.flush().await;
+        assert_eq!(*joined.lock().unwrap(), vec!["carol".to_owned()]);
+        assert_eq!(*left.lock().unwrap(), vec!["alice".to_owned()]);
+        assert_eq!(
+            ctx.rooms().get("reconciled-room").unwrap().member_id_list,
+            vec!["bob".to_owned(), "carol".to_owned()]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn lazy_entities_are_not_synced_until_ready() {
+        let mut bot = Wechaty::new(Puppet::new(PuppetMock::default()));
+        bot.lazy_entities(true);
+        let (_listener, mut harness) = EventListenerInner::test_harness(bot.ctx());
+        let loaded = Arc::new(Mutex::new(vec![]));
+        let loaded_clone = loaded.clone();
+        harness.on_message(
+            move |payload: MessagePayload<PuppetMock>, ctx: WechatyContext<PuppetMock>| {
+                let loaded = loaded_clone.clone();
+                async move {
+                    let mut message = payload.message;
+                    let is_cached = || {
+                        ctx.puppet()
+                            .cached_messages()
+                            .iter()
+                            .any(|payload| payload.id == "lazy-message")
+                    };
+                    loaded.lock().unwrap().push((message.text().is_some(), is_cached()));
+                    message.ready().await.unwrap();
+                    loaded.lock().unwrap().push((message.text().is_some(), is_cached()));
+                }
+            },
+        );
+
+        harness.inject(message_event("lazy-message")).await;
+        assert_eq!(*loaded.lock().unwrap(), vec![(false, false), (true, true)]);
+    }
+
+    #[actix_rt::test]
+    async fn message_matching_handlers_only_get_matched_messages() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let messages = [
+            ("from-self", "self", "ding"),
+            ("upper-case", "alice", "DING"),
+            ("part-of-word", "alice", "dingdong"),
+            ("unrelated", "alice", "hello"),
+            ("in-sentence", "alice", "say ding please"),
+            ("over-limit", "alice", "ding"),
+        ];
+        for (id, from_id, text) in messages.iter() {
+            ctx.messages()
+                .insert(id.to_string(), fixtures::message(id, from_id, text));
+        }
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let matched = Arc::new(Mutex::new(vec![]));
+        let matched_clone = matched.clone();
+        harness.on_message_matching_with_options(
+            Regex::new("ding").unwrap(),
+            move |payload: MessagePayload<PuppetMock>, _ctx| {
+                let matched = matched_clone.clone();
+                async move {
+                    matched.lock().unwrap().push(payload.message.id());
+                }
+            },
+            MessageMatchOptions {
+                case_insensitive: true,
+                whole_word: true,
+                skip_self: true,
+                limit: Some(2),
+            },
+        );
+
+        harness.inject(login_event()).await;
+        for (id, _, _) in messages.iter() {
+            harness.inject(message_event(id)).await;
+        }
+        assert_eq!(*matched.lock().unwrap(), vec!["upper-case", "in-sentence"]);
+    }
+
+    #[actix_rt::test]
+    async fn handlers_of_an_event_finish_before_the_next_event() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let log = Arc::new(Mutex::new(vec![]));
+        for handler in ["first", "second"] {
+            let log = log.clone();
+            harness.on_dong(move |payload: DongPayload, _ctx| {
+                let log = log.clone();
+                async move {
+                    log.lock().unwrap().push(format!("{} starts {}", handler, payload.data));
+                    actix_rt::time::sleep(Duration::from_millis(50)).await;
+                    log.lock().unwrap().push(format!("{} ends {}", handler, payload.data));
+                }
+            });
+        }
+
+        // Both events are queued before the handlers of the first one finish.
+        let dong_event = |data: &str| PuppetEvent::Dong(EventDongPayload { data: data.to_owned() });
+        futures::join!(harness.inject(dong_event("a")), harness.inject(dong_event("b")));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first starts a",
+                "first ends a",
+                "second starts a",
+                "second ends a",
+                "first starts b",
+                "first ends b",
+                "second starts b",
+                "second ends b",
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn self_messages_can_be_ignored() {
+        let mut bot = Wechaty::new(Puppet::new(PuppetMock::default()));
+        bot.ignore_self_messages(true);
+        let ctx = bot.ctx();
+        for (id, from_id) in [("from-self", "self"), ("from-alice", "alice")] {
+            ctx.messages().insert(id.to_owned(), fixtures::message(id, from_id, id));
+        }
+        let (_listener, mut harness) = EventListenerInner::test_harness(ctx);
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        harness.on_message(move |payload: MessagePayload<PuppetMock>, _ctx| {
+            let received = received_clone.clone();
+            async move {
+                received.lock().unwrap().push(payload.message.id());
+            }
+        });
+
+        harness.inject(login_event()).await;
+        for id in ["from-self", "from-alice"] {
+            harness.inject(message_event(id)).await;
+        }
+        assert_eq!(*received.lock().unwrap(), vec!["from-alice"]);
+    }
+
+    #[actix_rt::test]
+    async fn unread_messages_are_counted_per_conversation() {
+        let mut bot = Wechaty::new(Puppet::new(PuppetMock::default()));
+        bot.track_unread(true);
+        let ctx = bot.ctx();
+        let messages = [
+            ("room-1", "alice", "self", "room"),
+            ("room-2", "bob", "", "room"),
+            ("room-3", "alice", "", "room"),
+            ("direct-1", "bob", "self", ""),
+            ("direct-2", "bob", "self", ""),
+            ("from-self", "self", "bob", ""),
+        ];
+        for (id, from_id, to_id, room_id) in messages.iter() {
+            ctx.messages().insert(
+                id.to_string(),
+                wechaty_puppet::MessagePayload {
+                    room_id: room_id.to_string(),
+                    to_id: to_id.to_string(),
+                    ..fixtures::message(id, from_id, id)
+                },
+            );
+        }
+        let (_listener, harness) = EventListenerInner::test_harness(ctx.clone());
+
+        harness.inject(login_event()).await;
+        for (id, _, _, _) in messages.iter() {
+            harness.inject(message_event(id)).await;
+        }
+        assert_eq!(ctx.unread_count("room"), 3);
+        assert_eq!(ctx.unread_count("bob"), 2);
+        assert_eq!(ctx.unread_count("alice"), 0);
+        assert_eq!(ctx.unread_total(), 5);
+        ctx.mark_read("room");
+        assert_eq!(ctx.unread_count("room"), 0);
+        assert_eq!(ctx.unread_total(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn message_feed_receives_messages() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        for id in ["first", "second"] {
+            ctx.messages().insert(id.to_owned(), fixtures::message(id, "", id));
+        }
+        let mut feed = Box::pin(ctx.message_feed());
+        let (_listener, harness) = EventListenerInner::test_harness(ctx);
+
+        for id in ["first", "second"] {
+            harness.inject(message_event(id)).await;
+        }
+        assert_eq!(feed.next().await.unwrap().text(), Some("first".to_owned()));
+        assert_eq!(feed.next().await.unwrap().text(), Some("second".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn self_initiated_topic_change_is_suppressed() {
+        let mut bot = Wechaty::new(Puppet::new(PuppetMock::default()).with_dry_run(true));
+        let clock = Arc::new(MockClock::new(SystemTime::now()));
+        bot.with_clock(clock.clone());
+        let (_listener, mut harness) = EventListenerInner::test_harness(bot.ctx());
+        let fired = Arc::new(Mutex::new(0));
+        let fired_clone = fired.clone();
+        harness.on_room_topic(move |_payload: RoomTopicPayload<PuppetMock>, _ctx| {
+            let fired = fired_clone.clone();
+            async move {
+                *fired.lock().unwrap() += 1;
+            }
+        });
+        let topic_event = |changer_id: &str, new_topic: &str| {
+            PuppetEvent::RoomTopic(EventRoomTopicPayload {
+                changer_id: changer_id.to_owned(),
+                new_topic: new_topic.to_owned(),
+                old_topic: String::new(),
+                room_id: "room".to_owned(),
+                timestamp: 0,
+            })
+        };
+
+        let mut room = Room::new("room".to_owned(), bot.ctx(), None);
+        room.set_topic("Rust".to_owned()).await.unwrap();
+        // Someone else changing the topic before the echo arrives is still delivered.
+        harness.inject(topic_event("other", "Go")).await;
+        assert_eq!(*fired.lock().unwrap(), 1);
+
+        clock.advance(Duration::from_secs(2));
+        harness.inject(topic_event("self", "Rust")).await;
+        assert_eq!(*fired.lock().unwrap(), 1);
+
+        harness.inject(topic_event("self", "Rust")).await;
+        assert_eq!(*fired.lock().unwrap(), 2);
+
+        // Setting the announcement does not swallow the next topic change.
+        room.set_announce("Welcome".to_owned()).await.unwrap();
+        harness.inject(topic_event("other", "Rust")).await;
+        assert_eq!(*fired.lock().unwrap(), 3);
+
+        // An echo arriving after the window is delivered.
+        room.set_topic("Rust".to_owned()).await.unwrap();
+        clock.advance(Duration::from_secs(3));
+        harness.inject(topic_event("self", "Rust")).await;
+        assert_eq!(*fired.lock().unwrap(), 4);
+
+        bot.room_echo_window(Duration::ZERO);
+        room.set_topic("Rust".to_owned()).await.unwrap();
+        harness.inject(topic_event("self", "Rust")).await;
+        assert_eq!(*fired.lock().unwrap(), 5);
+    }
+
+    #[actix_rt::test]
+    async fn topic_echo_arriving_before_the_call_returns_is_suppressed() {
+        let mock = PuppetMock::default().with_latency(Duration::from_millis(20));
+        let bot = Wechaty::new(Puppet::new(mock));
+        let (_listener, mut harness) = EventListenerInner::test_harness(bot.ctx());
+        let fired = Arc::new(Mutex::new(0));
+        let fired_clone = fired.clone();
+        harness.on_room_topic(move |_payload: RoomTopicPayload<PuppetMock>, _ctx| {
+            let fired = fired_clone.clone();
+            async move {
+                *fired.lock().unwrap() += 1;
+            }
+        });
+        let echo = PuppetEvent::RoomTopic(EventRoomTopicPayload {
+            changer_id: "self".to_owned(),
+            new_topic: "Rust".to_owned(),
+            old_topic: String::new(),
+            room_id: "room".to_owned(),
+            timestamp: 0,
+        });
+
+        // The echo is handled while the gateway is still setting the topic.
+        let mut room = Room::new("room".to_owned(), bot.ctx(), None);
+        let (result, _) = futures::join!(room.set_topic("Rust".to_owned()), harness.inject(echo.clone()));
+        result.unwrap();
+        assert_eq!(*fired.lock().unwrap(), 0);
+
+        // The echo of a topic that failed to be set is not expected.
+        let bot = Wechaty::new(Puppet::new(PuppetMock::default().with_unsupported("room_topic_set")));
+        let (_listener, mut harness) = EventListenerInner::test_harness(bot.ctx());
+        let fired_clone = fired.clone();
+        harness.on_room_topic(move |_payload: RoomTopicPayload<PuppetMock>, _ctx| {
+            let fired = fired_clone.clone();
+            async move {
+                *fired.lock().unwrap() += 1;
+            }
+        });
+        let mut room = Room::new("room".to_owned(), bot.ctx(), None);
+        assert!(room.set_topic("Rust".to_owned()).await.is_err());
+        harness.inject(echo).await;
+        assert_eq!(*fired.lock().unwrap(), 1);
+    }
+}
@@ -7,7 +7,7 @@ use wechaty_puppet::PuppetImpl;
 
 use crate::{Message, WechatyContext, WechatyError};
 
-async fn message_load<T>(
+pub(crate) async fn message_load<T>(
     ctx: WechatyContext<T>,
     message_id: String,
     identity: String,
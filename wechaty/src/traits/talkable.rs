@@ -1,10 +1,35 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use futures::StreamExt;
 use log::{debug, error};
 use wechaty_puppet::{FileBox, MiniProgramPayload, PuppetImpl, UrlLinkPayload};
 
 use super::message_load;
 use crate::{Message, WechatyContext, WechatyError};
 
+/// Send a text to a conversation without checking `Talkable::check_send`.
+pub(crate) async fn send_text_unchecked<T>(
+    ctx: WechatyContext<T>,
+    conversation_id: String,
+    identity: String,
+    text: String,
+) -> Result<Option<Message<T>>, WechatyError>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    let puppet = ctx.puppet();
+    let message_id = match puppet.message_send_text(conversation_id, text, vec![]).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            error!("Message has been sent to {} but cannot get message id", identity);
+            return Ok(None);
+        }
+        Err(e) => return Err(WechatyError::from(e)),
+    };
+    message_load(ctx, message_id, identity).await
+}
+
 #[async_trait]
 pub trait Talkable<T>
 where
@@ -14,25 +39,78 @@ where
     fn ctx(&self) -> WechatyContext<T>;
     fn identity(&self) -> String;
 
+    /// Check whether messages can be sent to this conversation, which is checked before sending anything.
+    fn check_send(&self) -> Result<(), WechatyError> {
+        Ok(())
+    }
+
     async fn send_text(&self, text: String) -> Result<Option<Message<T>>, WechatyError> {
         debug!("talkable.send_text(id = {}, text = {})", self.id(), text);
+        self.check_send()?;
+        send_text_unchecked(self.ctx(), self.id(), self.identity(), text).await
+    }
+
+    /// Send a text with at-least-once delivery: the text is put in the outbox before sending and taken out once
+    /// sent, so that a text which fails to be sent, or is interrupted by a crash, can be retried with
+    /// `WechatyContext::flush_outbox`, see `Wechaty::outbox`.
+    async fn send_text_reliable(&self, text: String) -> Result<Option<Message<T>>, WechatyError> {
+        debug!("talkable.send_text_reliable(id = {}, text = {})", self.id(), text);
+        self.check_send()?;
+        let ctx = self.ctx();
+        let item_id = ctx.outbox_push(self.id(), text.clone()).await?;
+        match send_text_unchecked(ctx.clone(), self.id(), self.identity(), text).await {
+            Ok(message) => {
+                ctx.outbox_remove(item_id).await?;
+                Ok(message)
+            }
+            Err(e) => {
+                ctx.outbox_release(item_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Send a text, then wait for the next message received in this conversation, which is `None` if nothing
+    /// arrives within the timeout.
+    ///
+    /// Messages sent by the bot itself are skipped. Replies are only seen when the bot is subscribed to message
+    /// events, via `Wechaty::on_message` or `Wechaty::message_feed`.
+    async fn ask(&self, text: String, timeout: Duration) -> Result<Option<Message<T>>, WechatyError> {
+        debug!(
+            "talkable.ask(id = {}, text = {}, timeout = {:?})",
+            self.id(),
+            text,
+            timeout
+        );
         let ctx = self.ctx();
-        let puppet = ctx.puppet();
         let conversation_id = self.id();
-        let message_id = match puppet.message_send_text(conversation_id, text, vec![]).await {
-            Ok(Some(id)) => id,
-            Ok(None) => {
-                error!("Message has been sent to {} but cannot get message id", self.identity());
-                return Ok(None);
+        // Subscribe before sending, so that a quick reply is not missed.
+        let mut feed = Box::pin(ctx.message_feed());
+        self.send_text(text).await?;
+        let reply = async move {
+            while let Some(mut message) = feed.next().await {
+                if !message.is_ready() {
+                    message.ready().await.unwrap_or_default();
+                }
+                let from_id = message.from().map(|from| from.id());
+                if from_id.is_some() && from_id == ctx.id() {
+                    continue;
+                }
+                if message.conversation_id().as_deref() == Some(conversation_id.as_str()) {
+                    return Some(message);
+                }
             }
-            Err(e) => return Err(WechatyError::from(e)),
+            None
         };
-        let identity = self.identity();
-        message_load(ctx, message_id, identity).await
+        match actix_rt::time::timeout(timeout, reply).await {
+            Ok(reply) => Ok(reply),
+            Err(_) => Ok(None),
+        }
     }
 
     async fn send_contact(&self, contact_id: String) -> Result<Option<Message<T>>, WechatyError> {
         debug!("talkable.send_contact(id = {}, contact_id = {})", self.id(), contact_id);
+        self.check_send()?;
         let ctx = self.ctx();
         let puppet = ctx.puppet();
         let conversation_id = self.id();
@@ -50,6 +128,7 @@ where
 
     async fn send_file(&self, file: FileBox) -> Result<Option<Message<T>>, WechatyError> {
         debug!("talkable.send_file(id = {})", self.id());
+        self.check_send()?;
         let ctx = self.ctx();
         let puppet = ctx.puppet();
         let conversation_id = self.id();
@@ -65,12 +144,19 @@ where
         message_load(ctx, message_id, identity).await
     }
 
+    /// Send a file under the given name, an empty name falls back to one derived from the file.
+    async fn send_file_as(&self, file: FileBox, filename: String) -> Result<Option<Message<T>>, WechatyError> {
+        debug!("talkable.send_file_as(id = {}, filename = {})", self.id(), filename);
+        self.send_file(file.with_name(filename)).await
+    }
+
     async fn send_mini_program(&self, mini_program: MiniProgramPayload) -> Result<Option<Message<T>>, WechatyError> {
         debug!(
             "talkable.send_mini_program(id = {}, mini_program = {:?}",
             self.id(),
             mini_program
         );
+        self.check_send()?;
         let ctx = self.ctx();
         let puppet = ctx.puppet();
         let conversation_id = self.id();
@@ -88,6 +174,7 @@ where
 
     async fn send_url(&self, url: UrlLinkPayload) -> Result<Option<Message<T>>, WechatyError> {
         debug!("talkable.send_url(id = {}, url = {:?})", self.id(), url);
+        self.check_send()?;
         let ctx = self.ctx();
         let puppet = ctx.puppet();
         let conversation_id = self.id();
@@ -1,10 +1,11 @@
 use std::fmt;
 
 use log::{debug, trace};
-use wechaty_puppet::{ContactPayload, PuppetImpl};
+use wechaty_puppet::{ContactPayload, ContactType, PuppetImpl};
 
+use crate::traits::talkable::send_text_unchecked;
 use crate::user::entity::Entity;
-use crate::{IntoContact, Talkable, WechatyContext};
+use crate::{IntoContact, Message, Talkable, WechatyContext, WechatyError};
 
 pub type Contact<T> = Entity<T, ContactPayload>;
 
@@ -24,6 +25,33 @@ where
             payload_: payload,
         }
     }
+
+    /// Send a text to the contact, set `force` to send even if the contact type is restricted, see
+    /// `Wechaty::restrict_sending_to`.
+    pub async fn send(&self, text: String, force: bool) -> Result<Option<Message<T>>, WechatyError> {
+        debug!("Contact.send(id = {}, text = {}, force = {})", self.id_, text, force);
+        if !force {
+            self.check_send()?;
+        }
+        send_text_unchecked(self.ctx(), self.id(), self.identity(), text).await
+    }
+
+    /// The identity followed by the last 4 characters of the id and the contact type unless it is an individual,
+    /// e.g. `Alice#a1b2 (official)`, which tells apart contacts sharing a name.
+    pub fn identity_verbose(&self) -> String {
+        trace!("Contact.identity_verbose(id = {})", self.id_);
+        let suffix: String = {
+            let mut suffix: Vec<char> = self.id_.chars().rev().take(4).collect();
+            suffix.reverse();
+            suffix.into_iter().collect()
+        };
+        let marker = match self.contact_type() {
+            Some(ContactType::Official) => " (official)",
+            Some(ContactType::Corporation) => " (corporation)",
+            _ => "",
+        };
+        format!("{}#{}{}", self.identity(), suffix, marker)
+    }
 }
 
 impl<T> Talkable<T> for Contact<T>
@@ -40,6 +68,19 @@ where
         self.ctx_.clone()
     }
 
+    fn check_send(&self) -> Result<(), WechatyError> {
+        match self.contact_type() {
+            Some(contact_type) if self.ctx_.is_restricted_contact_type(&contact_type) => {
+                Err(WechatyError::InvalidOperation(format!(
+                    "Cannot send messages to {} of type {:?}, use Contact::send with force instead",
+                    self.identity(),
+                    contact_type
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn identity(&self) -> String {
         trace!("Contact.identity(id = {})", self.id_);
         match self.payload() {
@@ -88,6 +129,154 @@ where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
 {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, "{}", self.identity())
+        if self.ctx_.verbose_contact_identity() {
+            write!(fmt, "{}", self.identity_verbose())
+        } else {
+            write!(fmt, "{}", self.identity())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wechaty_puppet::{fixtures, ContactType, EventMessagePayload, MessagePayload, Puppet, PuppetEvent};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::EventListenerInner;
+
+    fn official_account(ctx: WechatyContext<PuppetMock>) -> Contact<PuppetMock> {
+        Contact::new(
+            "gh_official".to_owned(),
+            ctx,
+            Some(ContactPayload {
+                contact_type: ContactType::Official,
+                friend: false,
+                ..fixtures::contact("gh_official", "Official")
+            }),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn can_restrict_sending_to_official_accounts() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()).with_dry_run(true));
+        let contact = official_account(ctx.clone());
+
+        assert!(matches!(
+            contact.send_text("ding".to_owned()).await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            contact.send("ding".to_owned(), false).await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+        assert!(matches!(contact.send("ding".to_owned(), true).await, Ok(Some(_))));
+
+        ctx.set_restricted_contact_types(vec![]);
+        assert!(matches!(contact.send_text("ding".to_owned()).await, Ok(Some(_))));
+    }
+
+    #[actix_rt::test]
+    async fn verbose_identity_tells_apart_contacts_sharing_a_name() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let contact = official_account(ctx.clone());
+        assert_eq!(contact.identity(), "Official");
+        assert_eq!(contact.identity_verbose(), "Official#cial (official)");
+        assert_eq!(contact.to_string(), "Official");
+
+        ctx.set_verbose_contact_identity(true);
+        assert_eq!(contact.to_string(), "Official#cial (official)");
+        let loading = Contact::new("ab".to_owned(), ctx, None);
+        assert_eq!(loading.to_string(), "loading...#ab");
+    }
+
+    #[actix_rt::test]
+    async fn description_and_corporation_remark_round_trip() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let mut contact = Contact::new("colleague".to_owned(), ctx, None);
+
+        contact.set_description("Met at RustConf".to_owned()).await.unwrap();
+        contact.set_corporation_remark("Wechaty".to_owned()).await.unwrap();
+        assert_eq!(contact.description(), Some("Met at RustConf".to_owned()));
+        assert_eq!(contact.corporation(), Some("Wechaty".to_owned()));
+
+        contact.set_description(String::new()).await.unwrap();
+        assert_eq!(contact.description(), Some(String::new()));
+        assert_eq!(contact.corporation(), Some("Wechaty".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn alias_that_does_not_round_trip_is_not_verified() {
+        // The alias change never reaches the gateway in dry run, so it does not round-trip.
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()).with_dry_run(true));
+        let mut contact = Contact::new("ignored-alias".to_owned(), ctx, None);
+        assert!(!contact.set_alias_verified("Alice".to_owned()).await.unwrap());
+        assert_eq!(contact.alias(), Some(String::new()));
+        assert!(contact.set_alias("Alice".to_owned()).await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn can_set_and_clear_alias() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let mut contact = Contact::new("aliased-contact".to_owned(), ctx, None);
+
+        assert!(contact.set_alias_verified("Alice".to_owned()).await.unwrap());
+        assert_eq!(contact.alias(), Some("Alice".to_owned()));
+        contact.clear_alias().await.unwrap();
+        assert_eq!(contact.alias(), Some(String::new()));
+    }
+
+    #[actix_rt::test]
+    async fn ask_resolves_with_the_next_message_in_the_conversation() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()).with_dry_run(true));
+        ctx.set_id("self".to_owned());
+        for (id, from_id, to_id) in [("other", "bob", "self"), ("reply", "alice", "self")] {
+            ctx.messages().insert(
+                id.to_owned(),
+                MessagePayload {
+                    to_id: to_id.to_owned(),
+                    ..fixtures::message(id, from_id, id)
+                },
+            );
+        }
+        let (_listener, harness) = EventListenerInner::test_harness(ctx.clone());
+
+        // The question subscribes to the messages before it is sent, so the replies can arrive right away.
+        let alice = Contact::new("alice".to_owned(), ctx.clone(), None);
+        let (reply, _) = futures::join!(alice.ask("How are you?".to_owned(), Duration::from_secs(1)), async {
+            for id in ["other", "reply"] {
+                harness
+                    .inject(PuppetEvent::Message(EventMessagePayload {
+                        message_id: id.to_owned(),
+                    }))
+                    .await;
+            }
+        });
+        assert_eq!(reply.unwrap().map(|message| message.id()), Some("reply".to_owned()));
+
+        let bob = Contact::new("bob".to_owned(), ctx, None);
+        assert!(bob
+            .ask("Anyone?".to_owned(), Duration::from_millis(100))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[actix_rt::test]
+    async fn can_block_and_unblock() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        let contact = Contact::new("blocked-contact".to_owned(), ctx.clone(), None);
+
+        assert!(!contact.is_blocked().await.unwrap());
+        contact.block().await.unwrap();
+        assert!(contact.is_blocked().await.unwrap());
+        contact.unblock().await.unwrap();
+        assert!(!contact.is_blocked().await.unwrap());
+
+        let bot = Contact::new("self".to_owned(), ctx, None);
+        assert!(matches!(bot.block().await, Err(WechatyError::InvalidOperation(_))));
     }
 }
@@ -95,17 +95,25 @@ where
         }
     }
 
+    /// Get the QR code of the logged in account, which is cached for a short time and invalidated on logout.
     pub async fn qrcode(&self) -> Result<String, WechatyError> {
         debug!("Contact_self.qrcode()");
 
         if !self.is_self() {
             Err(WechatyError::NotLoggedIn)
         } else {
-            let puppet = self.ctx().puppet();
-            match puppet.contact_self_qr_code().await {
-                Ok(qrcode) => Ok(qrcode),
-                Err(e) => Err(WechatyError::from(e)),
-            }
+            self.ctx().self_qr_code(false).await
+        }
+    }
+
+    /// Fetch the QR code of the logged in account from the puppet, bypassing the cache, e.g. after it is reset.
+    pub async fn qrcode_refresh(&self) -> Result<String, WechatyError> {
+        debug!("Contact_self.qrcode_refresh()");
+
+        if !self.is_self() {
+            Err(WechatyError::NotLoggedIn)
+        } else {
+            self.ctx().self_qr_code(true).await
         }
     }
 }
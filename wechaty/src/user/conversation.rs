@@ -0,0 +1,69 @@
+use std::fmt;
+
+use wechaty_puppet::PuppetImpl;
+
+use crate::{Contact, Room, Talkable, WechatyContext, WechatyError};
+
+/// Where a message is sent to, either a room or a contact.
+#[derive(Clone)]
+pub enum Conversation<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    Room(Room<T>),
+    Contact(Contact<T>),
+}
+
+impl<T> Conversation<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    pub fn is_room(&self) -> bool {
+        matches!(self, Conversation::Room(_))
+    }
+}
+
+impl<T> Talkable<T> for Conversation<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn id(&self) -> String {
+        match self {
+            Conversation::Room(room) => room.id(),
+            Conversation::Contact(contact) => contact.id(),
+        }
+    }
+
+    fn ctx(&self) -> WechatyContext<T> {
+        match self {
+            Conversation::Room(room) => room.ctx(),
+            Conversation::Contact(contact) => contact.ctx(),
+        }
+    }
+
+    fn identity(&self) -> String {
+        match self {
+            Conversation::Room(room) => room.identity(),
+            Conversation::Contact(contact) => contact.identity(),
+        }
+    }
+
+    fn check_send(&self) -> Result<(), WechatyError> {
+        match self {
+            Conversation::Room(room) => room.check_send(),
+            Conversation::Contact(contact) => contact.check_send(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Conversation<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conversation::Room(room) => write!(fmt, "Conversation({:?})", room),
+            Conversation::Contact(contact) => write!(fmt, "Conversation({:?})", contact),
+        }
+    }
+}
@@ -2,9 +2,10 @@ use std::any;
 use std::fmt::Debug;
 
 use log::trace;
-use wechaty_puppet::PuppetImpl;
+use serde::Serialize;
+use wechaty_puppet::{ContactPayload, FriendshipPayload, MessagePayload, PayloadType, PuppetImpl, RoomPayload};
 
-use crate::WechatyContext;
+use crate::{WechatyContext, WechatyError};
 
 #[derive(Clone)]
 pub struct Entity<T, Payload>
@@ -68,3 +69,108 @@ where
         self.payload_ = payload;
     }
 }
+
+impl<T, Payload> Entity<T, Payload>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+    Payload: Debug + Clone + Serialize,
+{
+    /// Get the entity's payload as pretty json, which tells more than `Display` when debugging.
+    pub fn to_json(&self) -> Result<String, WechatyError> {
+        trace!("{}.to_json(id = {})", Entity::<T, Payload>::type_name(), self.id_);
+        match &self.payload_ {
+            Some(payload) => match serde_json::to_string_pretty(payload) {
+                Ok(json) => Ok(json),
+                Err(e) => Err(WechatyError::InvalidOperation(format!(
+                    "Failed to serialize {}: {}",
+                    self.id_, e
+                ))),
+            },
+            None => Err(WechatyError::NoPayload),
+        }
+    }
+}
+
+/// Payload of an entity which is cached by both the context and the puppet.
+pub trait EntityPayload {
+    fn payload_type() -> PayloadType;
+}
+
+impl EntityPayload for ContactPayload {
+    fn payload_type() -> PayloadType {
+        PayloadType::Contact
+    }
+}
+
+impl EntityPayload for FriendshipPayload {
+    fn payload_type() -> PayloadType {
+        PayloadType::Friendship
+    }
+}
+
+impl EntityPayload for MessagePayload {
+    fn payload_type() -> PayloadType {
+        PayloadType::Message
+    }
+}
+
+impl EntityPayload for RoomPayload {
+    fn payload_type() -> PayloadType {
+        PayloadType::Room
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::Puppet;
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::{Contact, Message, Room};
+
+    #[actix_rt::test]
+    async fn can_dump_payloads_as_json() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+
+        let contact = ctx.contact_load("json-contact".to_owned()).await.unwrap();
+        let json = contact.to_json().unwrap();
+        for key in ["\"id\": \"json-contact\"", "\"name\"", "\"alias\"", "\"contact_type\""] {
+            assert!(json.contains(key), "{} not in {}", key, json);
+        }
+
+        let room = ctx.room_load("json-room".to_owned()).await.unwrap();
+        let json = room.to_json().unwrap();
+        for key in ["\"id\": \"json-room\"", "\"topic\"", "\"member_id_list\""] {
+            assert!(json.contains(key), "{} not in {}", key, json);
+        }
+
+        let message = ctx.message_load("json-message".to_owned()).await.unwrap();
+        let json = message.to_json().unwrap();
+        for key in [
+            "\"id\": \"json-message\"",
+            "\"text\"",
+            "\"message_type\"",
+            "\"from_id\"",
+        ] {
+            assert!(json.contains(key), "{} not in {}", key, json);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn unready_entities_have_no_json() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        assert!(matches!(
+            Contact::new("contact".to_owned(), ctx.clone(), None).to_json(),
+            Err(WechatyError::NoPayload)
+        ));
+        assert!(matches!(
+            Room::new("room".to_owned(), ctx.clone(), None).to_json(),
+            Err(WechatyError::NoPayload)
+        ));
+        assert!(matches!(
+            Message::new("message".to_owned(), ctx, None).to_json(),
+            Err(WechatyError::NoPayload)
+        ));
+    }
+}
@@ -1,2 +1,107 @@
-#[derive(Clone, Debug)]
-pub struct Favorite {}
+use std::fmt;
+
+use log::{debug, error};
+use wechaty_puppet::{FavoritePayload, MessageType, PuppetImpl};
+
+use crate::{Entity, Message, WechatyContext, WechatyError};
+
+pub type Favorite<T> = Entity<T, FavoritePayload>;
+
+impl<T> Favorite<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    pub(crate) fn new(id: String, ctx: WechatyContext<T>, payload: Option<FavoritePayload>) -> Self {
+        debug!("create favorite {}", id);
+        Self {
+            id_: id,
+            ctx_: ctx,
+            payload_: payload,
+        }
+    }
+
+    pub(crate) async fn ready(&mut self) -> Result<(), WechatyError> {
+        debug!("Favorite.ready(id = {})", self.id_);
+        if self.is_ready() {
+            Ok(())
+        } else {
+            match self.ctx_.puppet().favorite_payload(self.id()).await {
+                Ok(payload) => {
+                    self.payload_ = Some(payload);
+                    Ok(())
+                }
+                Err(e) => {
+                    error!("Error occurred while syncing favorite {}: {}", self.id_, e);
+                    Err(WechatyError::from(e))
+                }
+            }
+        }
+    }
+
+    /// Get the id of the starred message.
+    pub fn message_id(&self) -> Option<String> {
+        debug!("Favorite.message_id(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.message_id.clone())
+    }
+
+    /// Get the type of the starred message.
+    pub fn message_type(&self) -> Option<MessageType> {
+        debug!("Favorite.message_type(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.message_type.clone())
+    }
+
+    /// Get the text of the starred message.
+    pub fn text(&self) -> Option<String> {
+        debug!("Favorite.text(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.text.clone())
+    }
+
+    /// Get the timestamp of the starred message.
+    pub fn timestamp(&self) -> Option<u64> {
+        debug!("Favorite.timestamp(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.timestamp)
+    }
+
+    /// Load the starred message.
+    pub async fn message(&self) -> Result<Message<T>, WechatyError> {
+        debug!("Favorite.message(id = {})", self.id_);
+        match self.message_id() {
+            Some(message_id) => self.ctx_.message_load(message_id).await,
+            None => Err(WechatyError::NoPayload),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Favorite<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Favorite({})", self.id_)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::Puppet;
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn can_add_favorite() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let message = Message::new("favorite-message".to_owned(), ctx.clone(), None);
+        let favorite = message.favorite().await.unwrap();
+        assert_eq!(favorite.id(), "favorite-favorite-message");
+        assert_eq!(favorite.message_id(), Some("favorite-message".to_owned()));
+        assert_eq!(favorite.message_type(), Some(MessageType::Text));
+        assert_eq!(favorite.message().await.unwrap().id(), "favorite-message");
+
+        ctx.set_id("self".to_owned());
+        let favorite_list = ctx.favorite_list().await.unwrap();
+        assert!(favorite_list
+            .iter()
+            .any(|favorite| favorite.id() == "favorite-favorite-message"));
+    }
+}
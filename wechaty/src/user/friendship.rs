@@ -1,7 +1,7 @@
 use std::fmt;
 
 use log::{debug, error};
-use wechaty_puppet::{FriendshipPayload, FriendshipType, PuppetImpl};
+use wechaty_puppet::{FriendshipPayload, FriendshipSceneType, FriendshipType, PuppetImpl};
 
 use crate::{Contact, Entity, IntoContact, WechatyContext, WechatyError};
 
@@ -24,7 +24,8 @@ where
         }
     }
 
-    pub(crate) async fn ready(&mut self) -> Result<(), WechatyError> {
+    /// Load the payload of the friendship, which is needed by most accessors if it is not cached.
+    pub async fn ready(&mut self) -> Result<(), WechatyError> {
         debug!("Friendship.ready(id = {})", self.id_);
         if self.is_ready() {
             Ok(())
@@ -53,6 +54,30 @@ where
         self.payload_.as_ref().map(|payload| payload.friendship_type.clone())
     }
 
+    /// Get friendship's scene, i.e. where the friendship request comes from.
+    pub fn scene(&self) -> Option<FriendshipSceneType> {
+        debug!("Friendship.scene(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.scene.clone())
+    }
+
+    /// Get friendship's ticket.
+    pub fn ticket(&self) -> Option<String> {
+        debug!("Friendship.ticket(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.ticket.clone())
+    }
+
+    /// Get friendship's stranger.
+    pub fn stranger(&self) -> Option<String> {
+        debug!("Friendship.stranger(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.stranger.clone())
+    }
+
+    /// Check if the friendship request comes from a room.
+    pub fn is_from_room(&self) -> bool {
+        debug!("Friendship.is_from_room(id = {})", self.id_);
+        self.scene() == Some(FriendshipSceneType::Room)
+    }
+
     /// Get friendship's contact.
     pub fn contact(&self) -> Option<Contact<T>> {
         debug!("Friendship.contact(id = {})", self.id_);
@@ -125,3 +150,35 @@ where
         write!(fmt, "{}", friendship_info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::{fixtures, Puppet};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn can_read_friendship_scene() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        ctx.friendship_payload_set(
+            "friendship".to_owned(),
+            FriendshipPayload {
+                hello: "hello".to_owned(),
+                scene: FriendshipSceneType::Room,
+                stranger: "stranger".to_owned(),
+                ticket: "ticket".to_owned(),
+                ..fixtures::friendship("friendship", "")
+            },
+        )
+        .await
+        .unwrap();
+
+        let friendship = ctx.friendship("friendship".to_owned()).await.unwrap();
+        assert_eq!(friendship.scene(), Some(FriendshipSceneType::Room));
+        assert_eq!(friendship.ticket(), Some("ticket".to_owned()));
+        assert_eq!(friendship.stranger(), Some("stranger".to_owned()));
+        assert!(friendship.is_from_room());
+    }
+}
@@ -1,13 +1,40 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::time::SystemTime;
 
-use log::{debug, error, info};
-use wechaty_puppet::{FileBox, MessagePayload, MessageType, MiniProgramPayload, PuppetImpl, UrlLinkPayload};
+use log::{debug, error, info, warn};
+use wechaty_puppet::{
+    ConversationId, FileBox, MessagePayload, MessageRecord, MessageType, MiniProgramPayload, PuppetFeature, PuppetImpl,
+    UrlLinkPayload,
+};
 
-use crate::{Contact, Entity, IntoContact, Room, Talkable, WechatyContext, WechatyError};
+use crate::traits::message_load;
+use crate::user::room::MENTION_SEPARATOR;
+use crate::{
+    Contact, Conversation, Entity, Favorite, IntoContact, Money, Participant, Room, Talkable, WechatyContext,
+    WechatyError,
+};
 
 pub type Message<T> = Entity<T, MessagePayload>;
 
+/// Ids of the official accounts that WeChat uses to send system notifications.
+const SYSTEM_CONTACT_ID_LIST: [&str; 5] = ["weixin", "fmessage", "medianote", "floatbottle", "newsapp"];
+/// Maximum number of characters of the text shown when displaying a message.
+const TEXT_PREVIEW_LENGTH: usize = 70;
+
+/// Shorten a text for a single-line preview, collapsing whitespace and newlines to single spaces and appending
+/// an ellipsis when truncated.
+fn text_preview(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_chars {
+        text
+    } else {
+        let mut preview: String = text.chars().take(max_chars).collect();
+        preview.push('…');
+        preview
+    }
+}
+
 impl<T> Message<T>
 where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
@@ -41,6 +68,33 @@ where
         self.room().is_some()
     }
 
+    /// Check if the message is a system message, i.e. it has no sender or is sent by a system account.
+    pub fn is_system(&self) -> bool {
+        debug!("Message.is_system(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => payload.from_id.is_empty() || SYSTEM_CONTACT_ID_LIST.contains(&payload.from_id.as_str()),
+            None => false,
+        }
+    }
+
+    /// Check if the message is sent to a contact directly, rather than in a room.
+    pub fn is_direct_message(&self) -> bool {
+        debug!("Message.is_direct_message(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => payload.room_id.is_empty() && !self.is_system(),
+            None => false,
+        }
+    }
+
+    /// Check if the message is sent in a room by a member.
+    pub fn is_group_message(&self) -> bool {
+        debug!("Message.is_group_message(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => !payload.room_id.is_empty() && !self.is_system(),
+            None => false,
+        }
+    }
+
     /// Check if the message mentioned the user self.
     pub fn mentioned_self(&self) -> bool {
         debug!("Message.mentioned_self(id = {})", self.id_);
@@ -54,7 +108,49 @@ where
         }
     }
 
-    pub(crate) async fn ready(&mut self) -> Result<(), WechatyError> {
+    /// Check if the message mentioned a contact.
+    ///
+    /// Gateways that do not report the mentioned ids leave `mention_id_list` empty, in which case the text is searched
+    /// for a mention of the contact's name, which needs the contact to be ready.
+    pub fn mentions(&self, contact: &Contact<T>) -> bool {
+        debug!("Message.mentions(id = {}, contact = {})", self.id_, contact.id());
+        match &self.payload_ {
+            Some(payload) if !payload.mention_id_list.is_empty() => payload.mention_id_list.contains(&contact.id()),
+            Some(payload) => match contact.name() {
+                Some(name) if !name.is_empty() => {
+                    let mention = format!("@{}", name);
+                    payload.text.match_indices(&mention).any(|(i, _)| {
+                        match payload.text[i + mention.len()..].chars().next() {
+                            Some(c) => c == MENTION_SEPARATOR || c == ' ',
+                            None => true,
+                        }
+                    })
+                }
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Check if the message mentioned any contact, by the mentioned ids or by a mention in the text.
+    pub fn mentions_anyone(&self) -> bool {
+        debug!("Message.mentions_anyone(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => {
+                // A mention in the text is an `@` and a name followed by the mention separator.
+                let mut segments: Vec<&str> = payload.text.split(MENTION_SEPARATOR).collect();
+                segments.pop();
+                !payload.mention_id_list.is_empty()
+                    || segments
+                        .iter()
+                        .any(|segment| segment.rsplit_once('@').is_some_and(|(_, name)| !name.is_empty()))
+            }
+            None => false,
+        }
+    }
+
+    /// Load the payload of the message, which is needed by most accessors if it is not cached.
+    pub async fn ready(&mut self) -> Result<(), WechatyError> {
         debug!("Message.ready(id = {})", self.id_);
         if self.is_ready() {
             Ok(())
@@ -83,21 +179,52 @@ where
         }
     }
 
+    /// Get message's conversation.
+    ///
+    /// A room message belongs to the room, a direct message belongs to the other side, which is the receiver if
+    /// the message is sent by the bot. Besides `room_id`, the sender and the receiver are also checked against
+    /// the room id pattern of the puppet, as some puppets put the room id there.
+    pub fn conversation(&self) -> Option<ConversationId> {
+        debug!("Message.conversation(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) if !payload.room_id.is_empty() => Some(ConversationId::Room(payload.room_id.clone())),
+            Some(payload) => {
+                let from = self.ctx_.parse_conversation_id(&payload.from_id);
+                let to = self.ctx_.parse_conversation_id(&payload.to_id);
+                match (from, to) {
+                    (_, Some(to)) if to.is_room() => Some(to),
+                    (Some(from), _) if from.is_room() => Some(from),
+                    (Some(from), Some(to)) => {
+                        if self.ctx_.id().as_deref() == Some(from.id()) {
+                            Some(to)
+                        } else {
+                            Some(from)
+                        }
+                    }
+                    (from, to) => from.or(to),
+                }
+            }
+            None => None,
+        }
+    }
+
     /// Get message's conversation id.
     pub fn conversation_id(&self) -> Option<String> {
         debug!("Message.conversation_id(id = {})", self.id_);
-        if self.is_ready() {
-            let payload = self.payload().unwrap();
-            if !payload.room_id.is_empty() {
-                Some(payload.room_id)
-            } else if !payload.from_id.is_empty() {
-                Some(payload.from_id)
-            } else {
-                None
+        self.conversation().map(|conversation| conversation.id().to_owned())
+    }
+
+    /// Convert the message to a record for persistence, which is `None` if the message is not ready.
+    pub fn to_record(&self) -> Option<MessageRecord> {
+        debug!("Message.to_record(id = {})", self.id_);
+        let conversation_id = self.conversation_id();
+        self.payload_.clone().map(|payload| {
+            let mut record = MessageRecord::from(payload);
+            if let Some(conversation_id) = conversation_id {
+                record.conversation_id = conversation_id;
             }
-        } else {
-            None
-        }
+            record
+        })
     }
 
     /// Get message's sender.
@@ -116,11 +243,14 @@ where
     }
 
     /// Get message's receiver.
+    ///
+    /// Room messages are sent to the room rather than to a contact, so it is always `None` for them, use
+    /// `recipient` to get the room as well.
     pub fn to(&self) -> Option<Contact<T>> {
         debug!("Message.to(id = {})", self.id_);
         match &self.payload_ {
             Some(payload) => {
-                if !payload.to_id.is_empty() {
+                if payload.room_id.is_empty() && !payload.to_id.is_empty() {
                     Some(Contact::new(payload.to_id.clone(), self.ctx_.clone(), None))
                 } else {
                     None
@@ -130,6 +260,27 @@ where
         }
     }
 
+    /// Get message's sender like `from`, which is a `ContactSelf` if the bot sent the message.
+    pub fn sender(&self) -> Option<Participant<T>> {
+        debug!("Message.sender(id = {})", self.id_);
+        self.from().map(Participant::new)
+    }
+
+    /// Get message's receiver like `to`, which is a `ContactSelf` if the message is sent to the bot.
+    pub fn receiver(&self) -> Option<Participant<T>> {
+        debug!("Message.receiver(id = {})", self.id_);
+        self.to().map(Participant::new)
+    }
+
+    /// Get where the message is sent to, which is the room for room messages and the receiver otherwise.
+    pub fn recipient(&self) -> Option<Conversation<T>> {
+        debug!("Message.recipient(id = {})", self.id_);
+        match self.room() {
+            Some(room) => Some(Conversation::Room(room)),
+            None => self.to().map(Conversation::Contact),
+        }
+    }
+
     /// Get the room that the message belongs to.
     pub fn room(&self) -> Option<Room<T>> {
         debug!("Message.room(id = {})", self.id_);
@@ -145,6 +296,27 @@ where
         }
     }
 
+    /// Get message's room with its payload loaded, `room` may return a room without payload.
+    pub async fn load_room(&self) -> Result<Option<Room<T>>, WechatyError> {
+        debug!("Message.load_room(id = {})", self.id_);
+        match self.room() {
+            Some(mut room) => match room.ready(false).await {
+                Ok(_) => Ok(Some(room)),
+                Err(e) => Err(e),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Get the topic of message's room, if it is a room message.
+    pub async fn room_topic(&self) -> Result<Option<String>, WechatyError> {
+        debug!("Message.room_topic(id = {})", self.id_);
+        match self.load_room().await {
+            Ok(room) => Ok(room.and_then(|room| room.topic())),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Get message's timestamp.
     pub fn timestamp(&self) -> Option<u64> {
         debug!("Message.timestamp(id = {})", self.id_);
@@ -156,7 +328,8 @@ where
         debug!("Message.age(id = {})", self.id_);
         match &self.payload_ {
             Some(payload) => {
-                SystemTime::now()
+                self.ctx_
+                    .now()
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap()
                     .as_secs()
@@ -167,6 +340,12 @@ where
         }
     }
 
+    /// Check if the message is at most `max_age` seconds old.
+    pub fn is_fresh(&self, max_age: u64) -> bool {
+        debug!("Message.is_fresh(id = {}, max_age = {})", self.id_, max_age);
+        self.age() <= max_age
+    }
+
     /// Get the message type.
     pub fn message_type(&self) -> Option<MessageType> {
         debug!("Message.message_type(id = {})", self.id_);
@@ -179,6 +358,87 @@ where
         self.payload_.as_ref().map(|payload| payload.text.clone())
     }
 
+    /// Get the filename of the message's attachment, if any.
+    pub fn filename(&self) -> Option<String> {
+        debug!("Message.filename(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) if !payload.filename.is_empty() => Some(payload.filename.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get the url link card of the message, if it is a url message.
+    pub async fn to_url_link(&self) -> Result<UrlLinkPayload, WechatyError> {
+        debug!("Message.to_url_link(id = {})", self.id_);
+        match self.message_type() {
+            None => Err(WechatyError::NoPayload),
+            Some(MessageType::Url) => match self.ctx_.puppet().message_url(self.id()).await {
+                Ok(url_link) => Ok(url_link),
+                Err(e) => Err(WechatyError::from(e)),
+            },
+            Some(message_type) => Err(WechatyError::InvalidOperation(format!(
+                "Cannot get url link from a {:?} message",
+                message_type
+            ))),
+        }
+    }
+
+    /// Get the messages forwarded in a chat history message.
+    ///
+    /// Fails with `PuppetError::Unsupported` if the puppet cannot expand chat histories, in which case the text of
+    /// the message is all there is.
+    pub async fn chat_history(&self) -> Result<Vec<Message<T>>, WechatyError> {
+        debug!("Message.chat_history(id = {})", self.id_);
+        match self.message_type() {
+            None => Err(WechatyError::NoPayload),
+            Some(MessageType::ChatHistory) => match self.ctx_.puppet().message_chat_history(self.id()).await {
+                Ok(payload_list) => Ok(payload_list
+                    .into_iter()
+                    .map(|payload| Message::new(payload.id.clone(), self.ctx(), Some(payload)))
+                    .collect()),
+                Err(e) => {
+                    warn!("Failed to expand chat history {}, reason: {}", self, e);
+                    Err(WechatyError::from(e))
+                }
+            },
+            Some(message_type) => Err(WechatyError::InvalidOperation(format!(
+                "Cannot expand chat history of a {:?} message",
+                message_type
+            ))),
+        }
+    }
+
+    /// Star the message, return the new favorite.
+    pub async fn favorite(&self) -> Result<Favorite<T>, WechatyError> {
+        debug!("Message.favorite(id = {})", self.id_);
+        match self.ctx_.puppet().favorite_add(self.id()).await {
+            Ok(favorite_id) => {
+                let mut favorite = Favorite::new(favorite_id, self.ctx(), None);
+                favorite.ready().await?;
+                Ok(favorite)
+            }
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Get the amount, status and memo of a transfer or red envelope message.
+    pub async fn to_money(&self) -> Result<Money, WechatyError> {
+        debug!("Message.to_money(id = {})", self.id_);
+        match self.message_type() {
+            None => Err(WechatyError::NoPayload),
+            Some(MessageType::Transfer) | Some(MessageType::RedEnvelope) => {
+                match self.ctx_.puppet().message_money(self.id()).await {
+                    Ok(payload) => Ok(Money::new(payload)),
+                    Err(e) => Err(WechatyError::from(e)),
+                }
+            }
+            Some(message_type) => Err(WechatyError::InvalidOperation(format!(
+                "Cannot get money from a {:?} message",
+                message_type
+            ))),
+        }
+    }
+
     /// Get the trimmed version (no mentions) of the message's text content.
     pub async fn text_trimmed(&mut self) -> String {
         unimplemented!()
@@ -195,6 +455,56 @@ where
         }
     }
 
+    /// Get the display names of the mentioned contacts, in the order they are mentioned.
+    ///
+    /// In a room, the room alias of a member is preferred over their identity. The contacts and room members are
+    /// loaded in batches, and the names of the most recent messages are cached until the message, a contact or the
+    /// room members are invalidated.
+    pub async fn mention_names(&self) -> Vec<String> {
+        debug!("Message.mention_names(id = {})", self.id_);
+        if let Some(names) = self.ctx_.mention_names().get(&self.id_).cloned() {
+            return names;
+        }
+        let payload = match &self.payload_ {
+            Some(payload) => payload.clone(),
+            None => return vec![],
+        };
+        let mut names: HashMap<String, String> = self
+            .ctx_
+            .contact_load_batch(payload.mention_id_list.clone())
+            .await
+            .into_iter()
+            .map(|contact| (contact.id(), contact.identity()))
+            .collect();
+        if !payload.room_id.is_empty() {
+            match self
+                .ctx_
+                .puppet()
+                .room_member_payload_list(payload.room_id.clone(), payload.mention_id_list.clone())
+                .await
+            {
+                Ok(member_list) => {
+                    for member in member_list {
+                        if !member.room_alias.is_empty() {
+                            names.insert(member.id, member.room_alias);
+                        }
+                    }
+                }
+                Err(e) => error!(
+                    "Failed to load mentioned members of room {}, reason: {}",
+                    payload.room_id, e
+                ),
+            }
+        }
+        let mention_names: Vec<String> = payload
+            .mention_id_list
+            .iter()
+            .map(|id| names.get(id).cloned().unwrap_or_else(|| id.clone()))
+            .collect();
+        self.ctx_.mention_names().put(self.id_.clone(), mention_names.clone());
+        mention_names
+    }
+
     /// Forward the current message to a conversation (contact or room).
     pub async fn forward(&mut self, conversation_id: String) -> Result<Option<Message<T>>, WechatyError> {
         debug!("Message.forward(id = {}", self.id_);
@@ -234,6 +544,32 @@ where
         }
     }
 
+    /// Reply with a text quoting the current message, unlike `reply_text`, the current message is displayed
+    /// along with the reply.
+    ///
+    /// Return `PuppetError::Unsupported` if the puppet cannot quote messages.
+    pub async fn quote_reply(&self, text: String) -> Result<Option<Message<T>>, WechatyError> {
+        debug!("Message.quote_reply(id = {}, text = {})", self.id_, text);
+        let conversation_id = match self.conversation_id() {
+            Some(conversation_id) => conversation_id,
+            None => return Err(WechatyError::NoPayload),
+        };
+        let message_id = match self
+            .ctx_
+            .puppet()
+            .message_send_quote(conversation_id.clone(), text, self.id())
+            .await
+        {
+            Ok(Some(message_id)) => message_id,
+            Ok(None) => {
+                error!("Message has been sent to {} but cannot get message id", conversation_id);
+                return Ok(None);
+            }
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        message_load(self.ctx(), message_id, conversation_id).await
+    }
+
     pub async fn reply_contact(&mut self, contact_id: String) -> Result<Option<Message<T>>, WechatyError> {
         debug!("Message.reply_contact(id = {}, contact_id = {})", self.id_, contact_id);
         if !self.is_ready() {
@@ -248,6 +584,7 @@ where
 
     pub async fn reply_file(&mut self, file: FileBox) -> Result<Option<Message<T>>, WechatyError> {
         debug!("Message.reply_file(id = {})", self.id_);
+        self.check_reply_supported(PuppetFeature::File)?;
         if !self.is_ready() {
             return Err(WechatyError::NoPayload);
         }
@@ -266,6 +603,7 @@ where
             "message.reply_mini_program(id = {}, mini_program = {:?})",
             self.id_, mini_program
         );
+        self.check_reply_supported(PuppetFeature::MiniProgram)?;
         if !self.is_ready() {
             return Err(WechatyError::NoPayload);
         }
@@ -278,6 +616,7 @@ where
 
     pub async fn reply_url(&mut self, url: UrlLinkPayload) -> Result<Option<Message<T>>, WechatyError> {
         debug!("Message.reply_url(id = {}, url = {:?})", self.id_, url);
+        self.check_reply_supported(PuppetFeature::UrlLink)?;
         if !self.is_ready() {
             return Err(WechatyError::NoPayload);
         }
@@ -287,6 +626,18 @@ where
             self.from().unwrap().send_url(url).await
         }
     }
+
+    /// Refuse to reply with content the puppet cannot send, instead of surfacing an error from the gateway.
+    fn check_reply_supported(&self, feature: PuppetFeature) -> Result<(), WechatyError> {
+        if self.ctx_.supports(feature) {
+            Ok(())
+        } else {
+            Err(WechatyError::InvalidOperation(format!(
+                "Cannot reply with {:?}, which is not supported by the puppet",
+                feature
+            )))
+        }
+    }
 }
 
 impl<T> fmt::Debug for Message<T>
@@ -320,12 +671,458 @@ where
             None => String::new(),
         };
         let text = if self.is_ready() && self.message_type().unwrap() == MessageType::Text {
-            let text = self.text().unwrap().chars().collect::<Vec<_>>();
-            let len = text.len().min(70);
-            format!("Text: {} ", text[0..len].iter().collect::<String>())
+            format!("Text: {} ", text_preview(&self.text().unwrap(), TEXT_PREVIEW_LENGTH))
         } else {
             String::new()
         };
         write!(fmt, "{}", [from, to, room, message_type, text].join(""))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use wechaty_puppet::{fixtures, FileBox, MoneyStatus, PayloadType, Puppet, PuppetCapabilities, RoomMemberPayload};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::{MockClock, Wechaty};
+
+    fn message_payload(from_id: &str, room_id: &str) -> MessagePayload {
+        MessagePayload {
+            room_id: room_id.to_owned(),
+            to_id: "self".to_owned(),
+            ..fixtures::message("message", from_id, "hello")
+        }
+    }
+
+    #[actix_rt::test]
+    async fn replies_with_unsupported_content_are_refused() {
+        let capabilities = PuppetCapabilities {
+            supports_contact_self: true,
+            supports_tag: true,
+            supports_friendship: true,
+            supports_room_invitation: true,
+            supports_room_announce: true,
+            supports_file: true,
+            supports_mini_program: false,
+            supports_url_link: true,
+            supports_moment: true,
+        };
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default().with_capabilities(capabilities)));
+        let mut message = Message::new("message".to_owned(), ctx, Some(message_payload("alice", "")));
+        let mini_program: MiniProgramPayload = serde_json::from_str("{}").unwrap();
+        assert!(matches!(
+            message.reply_mini_program(mini_program).await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+
+        // Other content is still sent.
+        let file = FileBox::from_base64(String::new(), "a.pdf".to_owned());
+        assert!(message.reply_file(file).await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn can_expand_chat_history() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let mut history = message_payload("alice", "");
+        history.id = "chat-history".to_owned();
+        history.message_type = MessageType::ChatHistory;
+        let forwarded = |id: &str, from_id: &str, text: &str| MessagePayload {
+            id: id.to_owned(),
+            text: text.to_owned(),
+            ..message_payload(from_id, "")
+        };
+        mock.add_chat_history(
+            history.clone(),
+            vec![
+                forwarded("chat-history-1", "bob", "ding"),
+                forwarded("chat-history-2", "carol", "dong"),
+            ],
+        );
+
+        let message = Message::new(history.id.clone(), ctx.clone(), Some(history));
+        let message_list = message.chat_history().await.unwrap();
+        assert_eq!(
+            message_list
+                .iter()
+                .map(|message| (message.from().unwrap().id(), message.text().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("bob".to_owned(), "ding".to_owned()),
+                ("carol".to_owned(), "dong".to_owned())
+            ]
+        );
+
+        let text = Message::new("message".to_owned(), ctx, Some(message_payload("alice", "")));
+        assert!(matches!(
+            text.chat_history().await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn sender_is_contact_self_if_the_bot_sent_the_message() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        for (id, name) in [("self", "Bot"), ("alice", "Alice")] {
+            let payload = named_contact(ctx.clone(), id, name).payload().unwrap();
+            ctx.contacts().insert(id.to_owned(), payload);
+        }
+        let mut payload = message_payload("self", "");
+        payload.to_id = "alice".to_owned();
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(payload));
+        assert!(matches!(message.sender(), Some(Participant::ContactSelf(_))));
+        assert_eq!(message.sender().unwrap().contact_self().unwrap().id(), "self");
+        assert!(matches!(message.receiver(), Some(Participant::Contact(_))));
+        assert_eq!(message.from().unwrap().id(), "self");
+        // The participants keep the payloads loaded for `from` and `to`.
+        assert_eq!(message.sender().unwrap().identity(), "Bot");
+        assert_eq!(message.receiver().unwrap().identity(), "Alice");
+
+        let message = Message::new("message".to_owned(), ctx, Some(message_payload("alice", "")));
+        assert!(matches!(message.sender(), Some(Participant::Contact(_))));
+        assert!(matches!(message.receiver(), Some(Participant::ContactSelf(_))));
+    }
+
+    #[test]
+    fn can_preview_text() {
+        assert_eq!(text_preview("hello", 10), "hello");
+        assert_eq!(text_preview("  hello\n\n  world\t", 11), "hello world");
+        assert_eq!(text_preview("你好，\n世界！再见", 5), "你好， 世…");
+    }
+
+    #[actix_rt::test]
+    async fn display_previews_text_on_a_single_line() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let mut payload = message_payload("", "");
+        payload.to_id = String::new();
+        payload.text = format!("第一行\r\n  第二行\n\n{}", "字".repeat(80));
+        let message = Message::new("message".to_owned(), ctx, Some(payload));
+
+        let display = message.to_string();
+        assert!(!display.contains('\n') && !display.contains('\r'));
+        assert_eq!(display, format!("Type: Text Text: 第一行 第二行 {}… ", "字".repeat(62)));
+    }
+
+    #[actix_rt::test]
+    async fn record_resolves_conversation_of_sent_messages() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        let mut payload = message_payload("self", "");
+        payload.to_id = "alice".to_owned();
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(payload));
+
+        let record = message.to_record().unwrap();
+        assert_eq!(record.conversation_id, "alice");
+        assert_eq!(record.sender_id, "self");
+        assert_eq!(record.text, "hello");
+        assert!(Message::new("unknown".to_owned(), ctx, None).to_record().is_none());
+    }
+
+    fn named_contact(ctx: WechatyContext<PuppetMock>, id: &str, name: &str) -> Contact<PuppetMock> {
+        Contact::new(id.to_owned(), ctx, Some(fixtures::contact(id, name)))
+    }
+
+    #[actix_rt::test]
+    async fn can_check_mentions() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let alice = named_contact(ctx.clone(), "alice", "Alice");
+        let bob = named_contact(ctx.clone(), "bob", "Bob");
+        let carol = named_contact(ctx.clone(), "carol", "Carol");
+
+        let mut payload = message_payload("alice", "room");
+        payload.text = "@Alice\u{2005}@Bob\u{2005}lunch?".to_owned();
+        payload.mention_id_list = vec!["alice".to_owned(), "bob".to_owned()];
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(payload.clone()));
+        assert!(message.mentions(&alice));
+        assert!(message.mentions(&bob));
+        assert!(!message.mentions(&carol));
+        assert!(message.mentions_anyone());
+
+        // Without the mentioned ids, mentions are found in the text.
+        payload.mention_id_list = vec![];
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(payload.clone()));
+        assert!(message.mentions(&alice));
+        assert!(message.mentions(&bob));
+        assert!(!message.mentions(&carol));
+        assert!(message.mentions_anyone());
+
+        payload.text = "mail alice@example.com or @Caroline\u{2005}".to_owned();
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(payload.clone()));
+        assert!(!message.mentions(&carol));
+        assert!(message.mentions_anyone());
+
+        payload.text = "mail alice@example.com".to_owned();
+        let message = Message::new("message".to_owned(), ctx, Some(payload));
+        assert!(!message.mentions_anyone());
+    }
+
+    #[actix_rt::test]
+    async fn can_resolve_mention_names() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        for (id, name) in [("mention-alice", "Alice"), ("mention-bob", "Bob")] {
+            let payload = named_contact(ctx.clone(), id, name).payload().unwrap();
+            ctx.contacts().insert(id.to_owned(), payload);
+        }
+        ctx.puppet()
+            .room_member_payload_set(
+                "mention-room".to_owned(),
+                "mention-alice".to_owned(),
+                RoomMemberPayload {
+                    room_alias: "Ally".to_owned(),
+                    ..fixtures::room_member("mention-alice", "Alice")
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut payload = message_payload("mention-carol", "mention-room");
+        payload.id = "mention-message".to_owned();
+        payload.mention_id_list = vec!["mention-bob".to_owned(), "mention-alice".to_owned()];
+        let message = Message::new("mention-message".to_owned(), ctx.clone(), Some(payload));
+        assert_eq!(message.mention_names().await, vec!["Bob", "Ally"]);
+        assert!(ctx.mention_names().contains(&"mention-message".to_owned()));
+
+        ctx.invalidate(PayloadType::Message, "mention-message".to_owned())
+            .await
+            .unwrap();
+        assert!(!ctx.mention_names().contains(&"mention-message".to_owned()));
+
+        // Renaming a mentioned contact drops the cached names.
+        assert_eq!(message.mention_names().await, vec!["Bob", "Ally"]);
+        ctx.invalidate(PayloadType::Contact, "mention-bob".to_owned())
+            .await
+            .unwrap();
+        assert!(!ctx.mention_names().contains(&"mention-message".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn can_classify_messages() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        // (from id, room id, is direct message, is group message, is system)
+        let cases = [
+            ("contact", "", true, false, false),
+            ("contact", "room", false, true, false),
+            ("", "", false, false, true),
+            ("", "room", false, false, true),
+            ("weixin", "", false, false, true),
+            ("fmessage", "", false, false, true),
+        ];
+        for (from_id, room_id, is_direct_message, is_group_message, is_system) in cases.iter() {
+            let message = Message::new(
+                "message".to_owned(),
+                ctx.clone(),
+                Some(message_payload(from_id, room_id)),
+            );
+            assert_eq!(
+                message.is_direct_message(),
+                *is_direct_message,
+                "{} {}",
+                from_id,
+                room_id
+            );
+            assert_eq!(message.is_group_message(), *is_group_message, "{} {}", from_id, room_id);
+            assert_eq!(message.is_system(), *is_system, "{} {}", from_id, room_id);
+        }
+
+        let message = Message::new("unknown".to_owned(), ctx, None);
+        assert!(!message.is_direct_message());
+        assert!(!message.is_group_message());
+        assert!(!message.is_system());
+    }
+
+    #[actix_rt::test]
+    async fn can_get_url_link_from_url_message() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let mut payload = message_payload("alice", "");
+        payload.id = "url-message".to_owned();
+        payload.message_type = MessageType::Url;
+        let url_link = UrlLinkPayload::new("Rust".to_owned(), "https://www.rust-lang.org".to_owned())
+            .unwrap()
+            .with_description("A language empowering everyone".to_owned());
+        mock.add_url_link(payload.clone(), url_link);
+
+        let message = Message::new(payload.id.clone(), ctx, Some(payload));
+        let url_link = message.to_url_link().await.unwrap();
+        assert_eq!(url_link.title, "Rust");
+        assert_eq!(url_link.url, "https://www.rust-lang.org");
+        assert_eq!(url_link.description.as_deref(), Some("A language empowering everyone"));
+        assert_eq!(url_link.thumbnail_url, None);
+    }
+
+    #[actix_rt::test]
+    async fn cannot_get_url_link_from_text_message() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(message_payload("contact", "")));
+        assert!(matches!(
+            message.to_url_link().await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+        let message = Message::new("unknown".to_owned(), ctx, None);
+        assert!(matches!(message.to_url_link().await, Err(WechatyError::NoPayload)));
+    }
+
+    #[actix_rt::test]
+    async fn can_get_money_from_transfer_message() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let mut payload = message_payload("contact", "");
+        payload.message_type = MessageType::Transfer;
+        let message = Message::new("transfer-0.10".to_owned(), ctx.clone(), Some(payload));
+        let money = message.to_money().await.unwrap();
+        assert_eq!(money.amount(), Some("0.10".to_owned()));
+        assert_eq!(money.currency(), "CNY");
+        assert_eq!(money.status(), MoneyStatus::Pending);
+        assert_eq!(money.memo(), None);
+
+        let message = Message::new("message".to_owned(), ctx, Some(message_payload("contact", "")));
+        assert!(matches!(
+            message.to_money().await,
+            Err(WechatyError::InvalidOperation(_))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn can_quote_reply() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let message = Message::new("message".to_owned(), ctx.clone(), Some(message_payload("contact", "")));
+        let reply = message.quote_reply("hi".to_owned()).await.unwrap().unwrap();
+        assert_eq!(reply.id(), "quote-message");
+
+        let message = Message::new("unknown".to_owned(), ctx, None);
+        assert!(matches!(
+            message.quote_reply("hi".to_owned()).await,
+            Err(WechatyError::NoPayload)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn can_get_age_from_clock() {
+        let mut bot = Wechaty::new(Puppet::new(PuppetMock::default()));
+        let clock = Arc::new(MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)));
+        bot.with_clock(clock.clone());
+        let mut payload = message_payload("contact", "");
+        payload.timestamp = 900;
+        let message = Message::new("message".to_owned(), bot.ctx(), Some(payload));
+        assert_eq!(message.age(), 100);
+        assert!(message.is_fresh(120));
+
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(message.age(), 150);
+        assert!(!message.is_fresh(120));
+    }
+
+    #[actix_rt::test]
+    async fn can_get_recipient() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let message = Message::new(
+            "message".to_owned(),
+            ctx.clone(),
+            Some(message_payload("contact", "room")),
+        );
+        assert!(message.to().is_none());
+        let recipient = message.recipient().unwrap();
+        assert!(recipient.is_room());
+        assert_eq!(recipient.id(), "room");
+
+        let message = Message::new("message".to_owned(), ctx, Some(message_payload("contact", "")));
+        assert_eq!(message.to().unwrap().id(), "self");
+        let recipient = message.recipient().unwrap();
+        assert!(!recipient.is_room());
+        assert_eq!(recipient.id(), "self");
+    }
+
+    #[actix_rt::test]
+    async fn can_get_room_topic() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.rooms()
+            .insert("room".to_owned(), fixtures::room("room", "Rust", &[]));
+        let message = Message::new(
+            "message".to_owned(),
+            ctx.clone(),
+            Some(message_payload("contact", "room")),
+        );
+        assert_eq!(message.room_topic().await.unwrap(), Some("Rust".to_owned()));
+
+        // The mock room has no topic, but it is loaded from the puppet.
+        let message = Message::new(
+            "message".to_owned(),
+            ctx.clone(),
+            Some(message_payload("contact", "other")),
+        );
+        assert_eq!(message.room_topic().await.unwrap(), Some(String::new()));
+
+        let message = Message::new("message".to_owned(), ctx, Some(message_payload("contact", "")));
+        assert_eq!(message.room_topic().await.unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn filename_round_trips() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let contact = Contact::new("contact".to_owned(), ctx, None);
+
+        let file = FileBox::from_url("https://example.com/a".to_owned(), "a.pdf".to_owned());
+        let message = contact
+            .send_file_as(file, "report.pdf".to_owned())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.filename(), Some("report.pdf".to_owned()));
+
+        let file = FileBox::from_qr_code("qr code".to_owned());
+        let message = contact.send_file_as(file, String::new()).await.unwrap().unwrap();
+        assert_eq!(message.filename(), Some("qrcode.png".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn can_get_conversation() {
+        let mut ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        ctx.set_id("self".to_owned());
+        // (from id, to id, room id, conversation)
+        let cases = [
+            (
+                "contact",
+                "self",
+                "",
+                Some(ConversationId::Contact("contact".to_owned())),
+            ),
+            (
+                "self",
+                "contact",
+                "",
+                Some(ConversationId::Contact("contact".to_owned())),
+            ),
+            ("contact", "self", "room", Some(ConversationId::Room("room".to_owned()))),
+            ("contact", "@@room", "", Some(ConversationId::Room("@@room".to_owned()))),
+            (
+                "room@chatroom",
+                "self",
+                "",
+                Some(ConversationId::Room("room@chatroom".to_owned())),
+            ),
+            ("", "self", "", Some(ConversationId::Contact("self".to_owned()))),
+            ("", "", "", None),
+        ];
+        for (from_id, to_id, room_id, conversation) in cases.iter() {
+            let mut payload = message_payload(from_id, room_id);
+            payload.to_id = to_id.to_string();
+            let message = Message::new("message".to_owned(), ctx.clone(), Some(payload));
+            assert_eq!(
+                message.conversation(),
+                *conversation,
+                "{} {} {}",
+                from_id,
+                to_id,
+                room_id
+            );
+            assert_eq!(
+                message.conversation_id(),
+                conversation.as_ref().map(|conversation| conversation.id().to_owned())
+            );
+        }
+    }
+}
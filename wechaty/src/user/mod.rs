@@ -1,5 +1,6 @@
 pub(crate) mod contact;
 pub(crate) mod contact_self;
+pub(crate) mod conversation;
 pub(crate) mod entity;
 pub(crate) mod favorite;
 pub(crate) mod friendship;
@@ -9,6 +10,7 @@ pub(crate) mod message;
 pub(crate) mod mini_program;
 pub(crate) mod moment;
 pub(crate) mod money;
+pub(crate) mod participant;
 pub(crate) mod room;
 pub(crate) mod room_invitation;
 pub(crate) mod tag;
@@ -1,2 +1,34 @@
+use wechaty_puppet::{MoneyPayload, MoneyStatus};
+
+/// A transfer or red envelope, see `Message::to_money`.
 #[derive(Clone, Debug)]
-pub struct Money {}
+pub struct Money {
+    payload_: MoneyPayload,
+}
+
+impl Money {
+    pub(crate) fn new(payload: MoneyPayload) -> Self {
+        Self { payload_: payload }
+    }
+
+    /// Get the amount, which is not available for red envelopes.
+    pub fn amount(&self) -> Option<String> {
+        self.payload_.amount.clone()
+    }
+
+    pub fn currency(&self) -> String {
+        self.payload_.currency.clone()
+    }
+
+    pub fn status(&self) -> MoneyStatus {
+        self.payload_.status.clone()
+    }
+
+    pub fn memo(&self) -> Option<String> {
+        self.payload_.memo.clone()
+    }
+
+    pub fn payload(&self) -> MoneyPayload {
+        self.payload_.clone()
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use wechaty_puppet::{ContactPayload, PuppetImpl};
+
+use crate::{Contact, ContactSelf, IntoContact, Talkable, WechatyContext};
+
+/// The sender or the receiver of a message, which is a `ContactSelf` if it is the bot itself, so that self-only
+/// methods can be called on it.
+#[derive(Clone)]
+pub enum Participant<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    ContactSelf(ContactSelf<T>),
+    Contact(Contact<T>),
+}
+
+impl<T> Participant<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    /// Wrap a loaded contact, keeping its payload.
+    pub(crate) fn new(contact: Contact<T>) -> Self {
+        if contact.is_self() {
+            Participant::ContactSelf(ContactSelf::new(contact.id(), contact.ctx(), contact.payload()))
+        } else {
+            Participant::Contact(contact)
+        }
+    }
+
+    /// Get the bot itself, if it is the participant.
+    pub fn contact_self(self) -> Option<ContactSelf<T>> {
+        match self {
+            Participant::ContactSelf(contact_self) => Some(contact_self),
+            Participant::Contact(_) => None,
+        }
+    }
+}
+
+impl<T> Talkable<T> for Participant<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn id(&self) -> String {
+        match self {
+            Participant::ContactSelf(contact_self) => contact_self.id(),
+            Participant::Contact(contact) => contact.id(),
+        }
+    }
+
+    fn ctx(&self) -> WechatyContext<T> {
+        match self {
+            Participant::ContactSelf(contact_self) => contact_self.ctx(),
+            Participant::Contact(contact) => contact.ctx(),
+        }
+    }
+
+    fn identity(&self) -> String {
+        match self {
+            Participant::ContactSelf(contact_self) => contact_self.identity(),
+            Participant::Contact(contact) => contact.identity(),
+        }
+    }
+}
+
+impl<T> IntoContact<T> for Participant<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn payload(&self) -> Option<ContactPayload> {
+        match self {
+            Participant::ContactSelf(contact_self) => contact_self.payload(),
+            Participant::Contact(contact) => contact.payload(),
+        }
+    }
+
+    fn set_payload(&mut self, payload: Option<ContactPayload>) {
+        match self {
+            Participant::ContactSelf(contact_self) => contact_self.set_payload(payload),
+            Participant::Contact(contact) => contact.set_payload(payload),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Participant<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Participant::ContactSelf(contact_self) => write!(fmt, "Participant({:?})", contact_self),
+            Participant::Contact(contact) => write!(fmt, "Participant({:?})", contact),
+        }
+    }
+}
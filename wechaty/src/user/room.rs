@@ -1,13 +1,79 @@
+use std::collections::HashSet;
 use std::fmt;
 
 use async_trait::async_trait;
-use log::{debug, error, trace};
+use log::{debug, error, trace, warn};
 use wechaty_puppet::{PayloadType, PuppetImpl, RoomMemberQueryFilter, RoomPayload};
 
-use crate::{Contact, Entity, Talkable, WechatyContext, WechatyError};
+use crate::traits::message_load;
+use crate::{Contact, Entity, IntoContact, Message, Talkable, WechatyContext, WechatyError};
 
 pub type Room<T> = Entity<T, RoomPayload>;
 
+/// The separator WeChat puts after a mention.
+pub(crate) const MENTION_SEPARATOR: char = '\u{2005}';
+
+/// Substitute `{name}` and `{room}` placeholders in a template.
+///
+/// Use `{{` and `}}` for literal braces, unknown placeholders are kept as is.
+fn render_template(template: &str, name: &str, room: &str) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    placeholder.push(c);
+                }
+                match (placeholder.as_str(), closed) {
+                    ("name", true) => result.push_str(name),
+                    ("room", true) => result.push_str(room),
+                    (_, true) => result.push_str(&format!("{{{}}}", placeholder)),
+                    (_, false) => result.push_str(&format!("{{{}", placeholder)),
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Human-visible state of a room, see `Room::details`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomDetails {
+    pub topic: String,
+    pub announce: String,
+    pub qr_code: String,
+    pub member_count: usize,
+    /// Id of the owner, `None` if the gateway does not tell.
+    pub owner_id: Option<String>,
+}
+
+/// Compare two snapshots of room members, see `Room::members_snapshot`.
+///
+/// Returns the ids of the members that joined and the ids of the members that left, each sorted.
+pub fn room_member_diff(old: &HashSet<String>, new: &HashSet<String>) -> (Vec<String>, Vec<String>) {
+    let mut joined: Vec<String> = new.difference(old).cloned().collect();
+    let mut left: Vec<String> = old.difference(new).cloned().collect();
+    joined.sort();
+    left.sort();
+    (joined, left)
+}
+
 impl<T> Room<T>
 where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
@@ -25,21 +91,24 @@ where
         }
     }
 
-    pub(crate) async fn ready(&mut self, force_sync: bool) -> Result<(), WechatyError> {
+    /// Load the payload of the room, which is needed by most accessors if it is not cached.
+    ///
+    /// The room is loaded from the puppet again if `force_sync` is set.
+    pub async fn ready(&mut self, force_sync: bool) -> Result<(), WechatyError> {
         debug!("Room.ready(id = {})", self.id_);
         if !force_sync && self.is_ready() {
             Ok(())
         } else {
             let id = self.id();
-            let mut puppet = self.ctx().puppet();
+            let puppet = self.ctx().puppet();
             if force_sync {
-                if let Err(e) = puppet.dirty_payload(PayloadType::Room, id.clone()).await {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Room, id.clone()).await {
                     error!("Error occurred while dirtying room {}: {}", id, e);
-                    return Err(WechatyError::from(e));
+                    return Err(e);
                 }
-                if let Err(e) = puppet.dirty_payload(PayloadType::RoomMember, id.clone()).await {
+                if let Err(e) = self.ctx().invalidate(PayloadType::RoomMember, id.clone()).await {
                     error!("Error occurred while dirtying members of room {}: {}", id, e);
-                    return Err(WechatyError::from(e));
+                    return Err(e);
                 }
             }
             match puppet.room_payload(id.clone()).await {
@@ -62,6 +131,227 @@ where
         self.ready(true).await
     }
 
+    /// Reload the member list of the room only, which is cheaper than a forced `ready` when membership is all
+    /// that changed.
+    ///
+    /// The cached room payload, like the topic and the avatar, is kept, it is loaded first if the room is not ready.
+    pub async fn sync_members(&mut self) -> Result<(), WechatyError> {
+        debug!("Room.sync_members(id = {})", self.id_);
+        self.ready(false).await?;
+        let id = self.id();
+        if let Err(e) = self.ctx().invalidate(PayloadType::RoomMember, id.clone()).await {
+            error!("Error occurred while dirtying members of room {}: {}", id, e);
+            return Err(e);
+        }
+        match self.ctx().puppet().room_member_list(id.clone()).await {
+            Ok(member_id_list) => {
+                let mut payload = self.payload().unwrap();
+                payload.member_id_list = member_id_list.clone();
+                self.ctx().rooms().insert(id, payload.clone());
+                self.set_payload(Some(payload));
+                self.ctx().contact_load_batch(member_id_list).await;
+                Ok(())
+            }
+            Err(e) => {
+                error!("Error occurred while syncing members of room {}: {}", id, e);
+                Err(WechatyError::from(e))
+            }
+        }
+    }
+
+    /// Get the ids of the members in the payload, which is empty if the room is not ready.
+    ///
+    /// Take a snapshot before and after a forced sync to find out who joined and left, see `room_member_diff`.
+    pub fn members_snapshot(&self) -> HashSet<String> {
+        debug!("Room.members_snapshot(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => payload.member_id_list.iter().cloned().collect(),
+            None => HashSet::new(),
+        }
+    }
+
+    /// Get room's topic.
+    pub fn topic(&self) -> Option<String> {
+        debug!("Room.topic(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.topic.clone())
+    }
+
+    /// Set room's topic.
+    ///
+    /// The room payload is refreshed after the topic is set, a warning is logged if the refreshed topic
+    /// does not match the new one.
+    pub async fn set_topic(&mut self, new_topic: String) -> Result<(), WechatyError> {
+        debug!("Room.set_topic(id = {}, new_topic = {})", self.id_, new_topic);
+        let puppet = self.ctx().puppet();
+        let id = self.id();
+        // The gateway may echo the topic back before the call returns, so expect the echo first.
+        self.ctx().expect_room_echo(id.clone(), new_topic.clone());
+        match puppet.room_topic_set(id.clone(), new_topic.clone()).await {
+            Err(e) => {
+                error!("Failed to set topic for {}, reason: {}", self.identity(), e);
+                self.ctx().cancel_room_echo(&id, &new_topic);
+                Err(WechatyError::from(e))
+            }
+            Ok(_) => {
+                if let Err(e) = self.ctx().invalidate(PayloadType::Room, id.clone()).await {
+                    error!("Failed to dirty payload for {}, reason: {}", self.identity(), e);
+                }
+                match puppet.room_payload(id.clone()).await {
+                    Ok(payload) => {
+                        if payload.topic != new_topic {
+                            warn!("Topic of room {} is not correctly set", id);
+                        }
+                        self.ctx().rooms().insert(id, payload.clone());
+                        self.set_payload(Some(payload));
+                    }
+                    Err(e) => {
+                        error!("Failed to verify payload for {}, reason: {}", self.identity(), e);
+                    }
+                };
+                Ok(())
+            }
+        }
+    }
+
+    /// Get room's announcement.
+    pub async fn announce(&self) -> Result<String, WechatyError> {
+        debug!("Room.announce(id = {})", self.id_);
+        match self.ctx().puppet().room_announce(self.id()).await {
+            Ok(announce) => Ok(announce),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Get the topic, announcement, QR code, member count and owner of the room in one call.
+    ///
+    /// The cached payload is used for the topic, the members and the owner, the announcement and the QR code are
+    /// fetched concurrently.
+    pub async fn details(&mut self) -> Result<RoomDetails, WechatyError> {
+        debug!("Room.details(id = {})", self.id_);
+        let puppet = self.ctx().puppet();
+        let id = self.id();
+        let (ready, announce, qr_code) = futures::join!(
+            self.ready(false),
+            puppet.room_announce(id.clone()),
+            puppet.room_qr_code(id),
+        );
+        ready?;
+        let payload = self.payload().unwrap();
+        match (announce, qr_code) {
+            (Ok(announce), Ok(qr_code)) => Ok(RoomDetails {
+                topic: payload.topic,
+                announce,
+                qr_code,
+                member_count: payload.member_id_list.len(),
+                owner_id: Some(payload.owner_id).filter(|owner_id| !owner_id.is_empty()),
+            }),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to get details of {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+        }
+    }
+
+    /// Set room's announcement.
+    pub async fn set_announce(&self, text: String) -> Result<(), WechatyError> {
+        debug!("Room.set_announce(id = {}, text = {})", self.id_, text);
+        match self.ctx().puppet().room_announce_set(self.id(), text).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to set announcement for {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+        }
+    }
+
+    /// Mute the notifications of the room, which is not supported by all gateways.
+    pub async fn mute(&self) -> Result<(), WechatyError> {
+        debug!("Room.mute(id = {})", self.id_);
+        match self.ctx().puppet().room_mute(self.id()).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to mute {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+        }
+    }
+
+    /// Unmute the notifications of the room, which is not supported by all gateways.
+    pub async fn unmute(&self) -> Result<(), WechatyError> {
+        debug!("Room.unmute(id = {})", self.id_);
+        match self.ctx().puppet().room_unmute(self.id()).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to unmute {}, reason: {}", self.identity(), e);
+                Err(WechatyError::from(e))
+            }
+        }
+    }
+
+    /// Check whether the notifications of the room are muted, which is not supported by all gateways.
+    pub async fn is_muted(&self) -> Result<bool, WechatyError> {
+        debug!("Room.is_muted(id = {})", self.id_);
+        match self.ctx().puppet().room_is_muted(self.id()).await {
+            Ok(muted) => Ok(muted),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Compose the welcome message for a new member, return the text and the mention id list.
+    fn welcome_message(&self, template: &str, invitee: &Contact<T>) -> (String, Vec<String>) {
+        let name = match invitee.name() {
+            Some(name) if !name.is_empty() => name,
+            _ => invitee.identity(),
+        };
+        let text = format!(
+            "@{}{}{}",
+            name,
+            MENTION_SEPARATOR,
+            render_template(template, &name, &self.identity())
+        );
+        (text, vec![invitee.id()])
+    }
+
+    /// Welcome a new member in the room.
+    ///
+    /// `{name}` and `{room}` in the template are replaced by the member's name and the room's topic, and the
+    /// message mentions the member. Use `{{` and `}}` for literal braces.
+    pub async fn welcome(&self, template: &str, invitee: &Contact<T>) -> Result<Option<Message<T>>, WechatyError> {
+        debug!(
+            "Room.welcome(id = {}, template = {}, invitee = {})",
+            self.id_, template, invitee
+        );
+        let (text, mention_id_list) = self.welcome_message(template, invitee);
+        let ctx = self.ctx();
+        let message_id = match ctx.puppet().message_send_text(self.id(), text, mention_id_list).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                error!("Message has been sent to {} but cannot get message id", self.identity());
+                return Ok(None);
+            }
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        message_load(ctx, message_id, self.identity()).await
+    }
+
+    /// Get the contact who invited a member into the room, return `None` if the inviter is unknown.
+    pub async fn member_inviter(&self, contact_id: String) -> Result<Option<Contact<T>>, WechatyError> {
+        debug!("Room.member_inviter(id = {}, contact_id = {})", self.id_, contact_id);
+        let ctx = self.ctx();
+        let payload = match ctx.puppet().room_member_payload(self.id(), contact_id).await {
+            Ok(payload) => payload,
+            Err(e) => return Err(WechatyError::from(e)),
+        };
+        if payload.inviter_id.is_empty() {
+            Ok(None)
+        } else {
+            match ctx.contact_load(payload.inviter_id).await {
+                Ok(inviter) => Ok(Some(inviter)),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
     pub async fn member_find(&self, query: RoomMemberQueryFilter) -> Result<Vec<Contact<T>>, WechatyError> {
         debug!("Room.member_find(id = {}, query = {:?})", self.id_, query);
         let ctx = self.ctx();
@@ -141,3 +431,185 @@ where
         write!(fmt, "{}", self.identity())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::{fixtures, Puppet, RoomMemberPayload};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+
+    #[test]
+    fn can_render_template() {
+        assert_eq!(
+            render_template("Welcome {name} to {room}!", "Alice", "Rust"),
+            "Welcome Alice to Rust!"
+        );
+        assert_eq!(
+            render_template("{{name}} is {name}", "Alice", "Rust"),
+            "{name} is Alice"
+        );
+        assert_eq!(render_template("{unknown} {name", "Alice", "Rust"), "{unknown} {name");
+    }
+
+    fn snapshot(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn can_diff_member_snapshots() {
+        let before = snapshot(&["alice", "bob", "carol"]);
+        let after = snapshot(&["bob", "dave", "carol", "erin"]);
+        assert_eq!(
+            room_member_diff(&before, &after),
+            (vec!["dave".to_owned(), "erin".to_owned()], vec!["alice".to_owned()])
+        );
+        assert_eq!(room_member_diff(&after, &after), (vec![], vec![]));
+        assert_eq!(
+            room_member_diff(&HashSet::new(), &before),
+            (vec!["alice".to_owned(), "bob".to_owned(), "carol".to_owned()], vec![])
+        );
+    }
+
+    fn room(ctx: WechatyContext<PuppetMock>) -> Room<PuppetMock> {
+        Room::new(
+            "room".to_owned(),
+            ctx,
+            Some(fixtures::room("room", "Rust", &["alice", "bob"])),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn welcome_message_mentions_invitee() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let room = room(ctx.clone());
+        let invitee = Contact::new("alice".to_owned(), ctx, Some(fixtures::contact("alice", "Alice")));
+
+        let (text, mention_id_list) = room.welcome_message("Welcome {name} to {room}!", &invitee);
+        assert_eq!(text, "@Alice\u{2005}Welcome Alice to Rust!");
+        assert_eq!(mention_id_list, vec!["alice".to_owned()]);
+    }
+
+    #[actix_rt::test]
+    async fn members_snapshot_is_taken_from_payload() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        assert_eq!(room(ctx.clone()).members_snapshot(), snapshot(&["alice", "bob"]));
+        assert!(Room::new("unknown".to_owned(), ctx, None).members_snapshot().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn can_sync_members_without_refetching_room() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let id = "sync-members-room".to_owned();
+        mock.set_room_members(id.clone(), vec!["alice".to_owned(), "bob".to_owned()]);
+        let mut room = Room::new(id.clone(), ctx.clone(), None);
+        room.ready(false).await.unwrap();
+        assert_eq!(room.members_snapshot(), snapshot(&["alice", "bob"]));
+        assert_eq!(mock.call_count("room_raw_payload"), 1);
+
+        mock.set_room_members(
+            id.clone(),
+            vec!["alice".to_owned(), "bob".to_owned(), "carol".to_owned()],
+        );
+        room.sync_members().await.unwrap();
+        assert_eq!(room.members_snapshot(), snapshot(&["alice", "bob", "carol"]));
+        assert_eq!(mock.call_count("room_raw_payload"), 1);
+        assert!(ctx.puppet().is_payload_cached(PayloadType::Room, id.clone()));
+        assert_eq!(
+            Room::new(id, ctx, None).members_snapshot(),
+            snapshot(&["alice", "bob", "carol"])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn can_get_room_details() {
+        let mock = PuppetMock::default();
+        let ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        let id = "details-room".to_owned();
+        mock.set_room_members(
+            id.clone(),
+            vec!["alice".to_owned(), "bob".to_owned(), "carol".to_owned()],
+        );
+        let mut room = Room::new(id.clone(), ctx, None);
+        room.set_announce("No spam".to_owned()).await.unwrap();
+
+        assert_eq!(
+            room.details().await.unwrap(),
+            RoomDetails {
+                topic: String::new(),
+                announce: "No spam".to_owned(),
+                qr_code: "qr-details-room".to_owned(),
+                member_count: 3,
+                owner_id: None,
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn can_set_topic() {
+        let mock = PuppetMock::default();
+        let mut ctx = WechatyContext::new(Puppet::new(mock.clone()));
+        ctx.set_id("self".to_owned());
+        let id = "topic-room".to_owned();
+        mock.set_room_members(id.clone(), vec!["alice".to_owned(), "bob".to_owned()]);
+        let mut room = ctx.room_load(id.clone()).await.unwrap();
+        assert_eq!(room.topic(), Some(String::new()));
+
+        room.set_topic("Rust".to_owned()).await.unwrap();
+        assert_eq!(room.topic(), Some("Rust".to_owned()));
+        assert_eq!(ctx.puppet().room_topic(id.clone()).await.unwrap(), "Rust");
+        // The stale payload cached before the topic was set has been replaced.
+        assert_eq!(ctx.puppet().room_payload(id.clone()).await.unwrap().topic, "Rust");
+        assert_eq!(Room::new(id, ctx, None).topic(), Some("Rust".to_owned()));
+    }
+
+    #[actix_rt::test]
+    async fn can_mute_rooms() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let room = Room::new("muted-room".to_owned(), ctx, None);
+        assert!(!room.is_muted().await.unwrap());
+        room.mute().await.unwrap();
+        assert!(room.is_muted().await.unwrap());
+        room.unmute().await.unwrap();
+        assert!(!room.is_muted().await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn room_details_reuse_cached_payload() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let mut room = room(ctx);
+        let details = room.details().await.unwrap();
+        assert_eq!(details.topic, "Rust");
+        assert_eq!(details.member_count, 2);
+        assert_eq!(details.qr_code, "qr-room");
+    }
+
+    #[actix_rt::test]
+    async fn can_get_member_inviter() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let puppet = ctx.puppet();
+        puppet
+            .room_member_payload_set(
+                "room".to_owned(),
+                "alice".to_owned(),
+                RoomMemberPayload {
+                    inviter_id: "bob".to_owned(),
+                    ..fixtures::room_member("alice", "")
+                },
+            )
+            .await
+            .unwrap();
+        puppet
+            .room_member_payload_set("room".to_owned(), "bob".to_owned(), fixtures::room_member("bob", ""))
+            .await
+            .unwrap();
+        ctx.update_contact("bob".to_owned(), fixtures::contact("bob", "Bob"));
+        let room = room(ctx);
+
+        let inviter = room.member_inviter("alice".to_owned()).await.unwrap().unwrap();
+        assert_eq!(inviter.id(), "bob");
+        assert_eq!(inviter.name(), Some("Bob".to_owned()));
+        assert!(room.member_inviter("bob".to_owned()).await.unwrap().is_none());
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt;
 use log::{debug, error};
 use wechaty_puppet::{PuppetImpl, RoomInvitationPayload};
 
-use crate::{Entity, WechatyContext, WechatyError};
+use crate::{Contact, Entity, WechatyContext, WechatyError};
 
 pub type RoomInvitation<T> = Entity<T, RoomInvitationPayload>;
 
@@ -24,6 +24,53 @@ where
         }
     }
 
+    /// Get the contact who sent the invitation.
+    pub fn inviter(&self) -> Option<Contact<T>> {
+        debug!("RoomInvitation.inviter(id = {})", self.id_);
+        match &self.payload_ {
+            Some(payload) => {
+                if !payload.inviter_id.is_empty() {
+                    Some(Contact::new(payload.inviter_id.clone(), self.ctx_.clone(), None))
+                } else {
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+
+    /// Get the topic of the room the invitation is for.
+    pub fn topic(&self) -> Option<String> {
+        debug!("RoomInvitation.topic(id = {})", self.id_);
+        self.payload_.as_ref().map(|payload| payload.topic.clone())
+    }
+
+    /// Accept the invitation only if the predicate, given the inviter and the topic, returns true.
+    ///
+    /// Returns whether the invitation has been accepted.
+    pub async fn accept_if<F>(&mut self, predicate: F) -> Result<bool, WechatyError>
+    where
+        F: FnOnce(&Contact<T>, &str) -> bool,
+    {
+        debug!("RoomInvitation.accept_if(id = {})", self.id_);
+        self.ready().await?;
+        let payload = match self.payload() {
+            Some(payload) => payload,
+            None => return Err(WechatyError::NoPayload),
+        };
+        let inviter = match self.ctx_.contact_load(payload.inviter_id).await {
+            Ok(inviter) => inviter,
+            Err(e) => return Err(e),
+        };
+        if !predicate(&inviter, &payload.topic) {
+            return Ok(false);
+        }
+        match self.accept().await {
+            Ok(_) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn accept(&self) -> Result<(), WechatyError> {
         debug!("RoomInvitation.accept(id = {})", self.id_);
         match self.ctx().puppet().room_invitation_accept(self.id()).await {
@@ -32,7 +79,17 @@ where
         }
     }
 
-    pub(crate) async fn ready(&mut self) -> Result<(), WechatyError> {
+    /// Decline the invitation, which fails with `PuppetError::Unsupported` if the puppet cannot decline invitations.
+    pub async fn reject(&self) -> Result<(), WechatyError> {
+        debug!("RoomInvitation.reject(id = {})", self.id_);
+        match self.ctx().puppet().room_invitation_reject(self.id()).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+
+    /// Load the payload of the room invitation, which is needed by most accessors if it is not cached.
+    pub async fn ready(&mut self) -> Result<(), WechatyError> {
         debug!("RoomInvitation.ready(id = {})", self.id_);
         if self.is_ready() {
             Ok(())
@@ -76,3 +133,44 @@ where
         write!(fmt, "{}", self.id())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wechaty_puppet::{fixtures, Puppet};
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::IntoContact;
+
+    fn invitation(ctx: WechatyContext<PuppetMock>, id: &str, inviter_id: &str) -> RoomInvitation<PuppetMock> {
+        ctx.contacts()
+            .insert(inviter_id.to_owned(), fixtures::contact(inviter_id, inviter_id));
+        ctx.room_invitations().insert(
+            id.to_owned(),
+            RoomInvitationPayload {
+                topic: "Rust".to_owned(),
+                ..fixtures::room_invitation(id, inviter_id)
+            },
+        );
+        RoomInvitation::new(id.to_owned(), ctx, None)
+    }
+
+    #[actix_rt::test]
+    async fn can_accept_invitations_from_trusted_inviters() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let trusted =
+            |inviter: &Contact<PuppetMock>, topic: &str| inviter.name() == Some("alice".to_owned()) && topic == "Rust";
+
+        let mut from_alice = invitation(ctx.clone(), "alice-invitation", "alice");
+        assert!(from_alice.accept_if(trusted).await.unwrap());
+        let mut from_mallory = invitation(ctx, "mallory-invitation", "mallory");
+        assert!(!from_mallory.accept_if(trusted).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn can_reject_invitations() {
+        let ctx = WechatyContext::new(Puppet::new(PuppetMock::default()));
+        let from_mallory = invitation(ctx, "rejected-invitation", "mallory");
+        assert!(from_mallory.reject().await.is_ok());
+    }
+}
@@ -1,2 +1,50 @@
-#[derive(Clone, Debug)]
-pub struct Tag {}
+use std::fmt;
+
+use log::debug;
+use wechaty_puppet::PuppetImpl;
+
+use crate::{Contact, WechatyContext, WechatyError};
+
+#[derive(Clone)]
+pub struct Tag<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    id_: String,
+    ctx_: WechatyContext<T>,
+}
+
+impl<T> Tag<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    pub(crate) fn new(id: String, ctx: WechatyContext<T>) -> Self {
+        debug!("create tag {}", id);
+        Self { id_: id, ctx_: ctx }
+    }
+
+    pub fn id(&self) -> String {
+        self.id_.clone()
+    }
+
+    /// Add this tag to contacts, see `WechatyContext::tag_add_batch`.
+    pub async fn add_contacts(&self, contacts: Vec<Contact<T>>) -> Vec<(Contact<T>, Result<(), WechatyError>)> {
+        debug!("Tag.add_contacts(id = {})", self.id_);
+        self.ctx_.tag_add_batch(self.id(), contacts).await
+    }
+
+    /// Remove this tag from contacts, see `WechatyContext::tag_remove_batch`.
+    pub async fn remove_contacts(&self, contacts: Vec<Contact<T>>) -> Vec<(Contact<T>, Result<(), WechatyError>)> {
+        debug!("Tag.remove_contacts(id = {})", self.id_);
+        self.ctx_.tag_remove_batch(self.id(), contacts).await
+    }
+}
+
+impl<T> fmt::Debug for Tag<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "Tag({})", self.id_)
+    }
+}
@@ -1,11 +1,22 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
 use actix::{Actor, Addr, Recipient};
+use futures::Stream;
+use log::{debug, error};
 use tokio::signal;
-use wechaty_puppet::{Puppet, PuppetEvent, PuppetImpl};
+use wechaty_puppet::{ContactType, EventName, Puppet, PuppetEvent, PuppetImpl, Subscribe, UnSubscribe};
 
-use crate::{EventListener, EventListenerInner, WechatyContext};
+use crate::traits::event_listener::StopListener;
+use crate::{Clock, EventListener, EventListenerInner, Message, WechatyContext, WechatyError};
 
 type WechatyListener<T> = EventListenerInner<T>;
 
+/// Number of Wechaty instances created so far, which tells apart the listener names of bots sharing a puppet.
+static WECHATY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 pub struct Wechaty<T>
 where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
@@ -20,16 +31,205 @@ where
     T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
 {
     pub fn new(puppet: Puppet<T>) -> Self {
-        let listener = EventListenerInner::new("Wechaty".to_owned(), WechatyContext::new(puppet.clone()));
+        Self::with_name(puppet, "Wechaty".to_owned())
+    }
+
+    /// Create a Wechaty instance whose listener is named after the given name.
+    ///
+    /// A counter is appended to the name, so that listeners subscribing to the same puppet never share a name,
+    /// which would make dropping one bot unsubscribe the others.
+    pub(crate) fn with_name(puppet: Puppet<T>, name: String) -> Self {
+        let name = format!("{}#{}", name, WECHATY_COUNT.fetch_add(1, Ordering::SeqCst) + 1);
+        let listener = EventListenerInner::new(name, WechatyContext::new(puppet.clone()));
         let addr = listener.clone().start();
-        Self { puppet, listener, addr }
+        let wechaty = Self { puppet, listener, addr };
+        // Keep the caches fresh even when no dirty handler is registered.
+        wechaty.subscribe(EventName::Dirty);
+        wechaty
+    }
+
+    pub(crate) fn ctx(&self) -> WechatyContext<T> {
+        self.listener.ctx()
+    }
+
+    /// Record the previous name and alias of contacts when they change, see `IntoContact::previous_name`.
+    ///
+    /// Disabled by default to save memory.
+    pub fn track_contact_history(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_track_contact_history(enabled);
+        self
+    }
+
+    /// Hand entities to event handlers without syncing them first, which saves the round trips to the puppet for
+    /// handlers that only need ids.
+    ///
+    /// Entities that are not cached yet have no payload, so their accessors return `None` until the handler calls
+    /// `ready()` on them. The room and the sender of a message event are also `None` until the message is ready.
+    /// The bot itself is still synced on login and logout. Disabled by default.
+    pub fn lazy_entities(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_lazy_entities(enabled);
+        self
+    }
+
+    /// Skip the message handlers for messages sent by the bot itself, so that replying to every message does not
+    /// end up in an echo loop.
+    ///
+    /// Such messages still reach the message feeds. Disabled by default.
+    pub fn ignore_self_messages(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_ignore_self_messages(enabled);
+        self
+    }
+
+    /// Display contacts with `Contact::identity_verbose` instead of the plain identity, which tells apart contacts
+    /// sharing a name in logs. Disabled by default.
+    pub fn verbose_contact_identity(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_verbose_contact_identity(enabled);
+        self
+    }
+
+    /// Count the messages received in each conversation until they are marked as read, see
+    /// `WechatyContext::unread_count` and `WechatyContext::mark_read`.
+    ///
+    /// Messages sent by the bot itself are not counted. Disabled by default.
+    pub fn track_unread(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_track_unread(enabled);
+        if enabled {
+            self.subscribe(EventName::Message);
+        }
+        self
+    }
+
+    /// Re-fetch the members of the cached rooms whenever the puppet (re)connects, and emit room join and room leave
+    /// events for the members that changed while the bot was offline.
+    ///
+    /// Disabled by default, as it makes a call for each cached room.
+    pub fn reconcile_room_members(&mut self, enabled: bool) -> &mut Self {
+        self.ctx().set_reconcile_room_members(enabled);
+        if enabled {
+            self.subscribe(EventName::ConnectionChange);
+        }
+        self
+    }
+
+    /// Refuse to send messages to contacts of these types unless forced, see `Contact::send`.
+    ///
+    /// Only official accounts are restricted by default, as they usually cannot receive arbitrary messages.
+    pub fn restrict_sending_to(&mut self, contact_types: Vec<ContactType>) -> &mut Self {
+        self.ctx().set_restricted_contact_types(contact_types);
+        self
+    }
+
+    /// Use another clock for message ages, cache expiry, uptime and the avatar prefetch rate, e.g. a `MockClock` in
+    /// tests.
+    pub fn with_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.ctx().set_clock(clock);
+        self
+    }
+
+    /// Ignore the room topic event echoed back within this window after the bot changes the topic of a room,
+    /// which would otherwise re-trigger handlers that change the room again. Only an event carrying the topic the
+    /// bot set is ignored.
+    ///
+    /// Defaults to 3 seconds, a zero window disables the suppression.
+    pub fn room_echo_window(&mut self, window: Duration) -> &mut Self {
+        self.ctx().set_room_echo_window(window);
+        self
+    }
+
+    /// Reconnect if no event, including heartbeats, arrives from the gateway within this timeout, which overrides
+    /// `PuppetOptions::heartbeat_timeout`.
+    ///
+    /// Only puppets connected to a gateway can be watched, an error is logged for the others.
+    pub fn heartbeat_timeout(&mut self, timeout: Duration) -> &mut Self {
+        if let Err(e) = self.puppet.set_heartbeat_timeout(Some(timeout)) {
+            error!("Failed to set heartbeat timeout, reason: {}", e);
+        }
+        self
+    }
+
+    /// Fetch at most one avatar per interval when prefetching, see `WechatyContext::prefetch_avatars`.
+    ///
+    /// Defaults to 200 milliseconds.
+    pub fn avatar_prefetch_interval(&mut self, interval: Duration) -> &mut Self {
+        self.ctx().set_avatar_prefetch_interval(interval);
+        self
+    }
+
+    /// Persist the texts sent with `Talkable::send_text_reliable` to this file until they are sent, so that
+    /// `WechatyContext::flush_outbox` can retry them after a restart.
+    ///
+    /// Without a file, the outbox is only kept in memory.
+    pub fn outbox(&mut self, path: PathBuf) -> &mut Self {
+        self.ctx().set_outbox_path(path);
+        self
+    }
+
+    /// Subscribe to message events and consume them as a stream, see `WechatyContext::message_feed`.
+    pub fn message_feed(&self) -> impl Stream<Item = Message<T>> {
+        self.subscribe(EventName::Message);
+        self.ctx().message_feed()
+    }
+
+    /// Subscribe to an event without registering a handler.
+    fn subscribe(&self, event_name: EventName) {
+        if let Err(e) = self.puppet.get_subscribe_addr().do_send(Subscribe {
+            addr: self.get_addr(),
+            name: self.get_name(),
+            event_name,
+        }) {
+            error!(
+                "{} failed to subscribe to event {}: {}",
+                self.get_name(),
+                event_name.as_str(),
+                e
+            );
+        }
     }
 
     pub async fn start(&self) {
+        self.ctx().set_cancelled(false);
         signal::ctrl_c()
             .await
             .expect("Failed to establish the listener for graceful exit");
     }
+
+    /// Stop the bot, long-running operations such as `WechatyContext::warmup` give up promptly and the puppet
+    /// stops reconnecting to the gateway.
+    pub async fn stop(&self) -> Result<(), WechatyError> {
+        debug!("{} stop()", self.get_name());
+        self.ctx().set_cancelled(true);
+        match self.puppet.stop().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(WechatyError::from(e)),
+        }
+    }
+}
+
+/// Unsubscribe from all the events and stop the listener actor, so that a dropped bot does not leak.
+///
+/// This is best effort, as the messages are sent without waiting for them to be handled.
+impl<T> Drop for Wechaty<T>
+where
+    T: 'static + PuppetImpl + Clone + Unpin + Send + Sync,
+{
+    fn drop(&mut self) {
+        debug!("{} dropped", self.get_name());
+        let unsubscribe_addr = self.puppet.get_unsubscribe_addr();
+        for event_name in EventName::ALL {
+            if let Err(e) = unsubscribe_addr.do_send(UnSubscribe {
+                name: self.get_name(),
+                event_name,
+            }) {
+                debug!(
+                    "{} failed to unsubscribe from event {}: {}",
+                    self.get_name(),
+                    event_name.as_str(),
+                    e
+                );
+            }
+        }
+        self.addr.do_send(StopListener);
+    }
 }
 
 impl<T> EventListener<T> for Wechaty<T>
@@ -48,3 +248,82 @@ where
         self.addr.clone().recipient()
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use std::rc::Rc;
+
+    use wechaty_puppet_mock::PuppetMock;
+
+    use super::*;
+    use crate::MessagePayload;
+
+    #[actix_rt::test]
+    async fn dropping_bot_unsubscribes_it() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let mut bot = Wechaty::new(puppet.clone());
+        bot.on_message(|_payload: MessagePayload<PuppetMock>, _ctx| async {});
+        bot.on_login(|_payload, _ctx| async {});
+        // Subscriptions are handled by the puppet actor in the order they are sent, so no need to wait.
+        assert_eq!(puppet.subscriber_names(EventName::Message).await, vec![bot.get_name()]);
+        assert_eq!(puppet.subscriber_names(EventName::Login).await, vec![bot.get_name()]);
+
+        drop(bot);
+        for event_name in EventName::ALL {
+            assert!(puppet.subscriber_names(event_name).await.is_empty());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn dropping_bot_keeps_other_bots_on_the_same_puppet() {
+        let puppet = Puppet::new(PuppetMock::default());
+        let mut dropped_bot = Wechaty::new(puppet.clone());
+        let mut bot = Wechaty::new(puppet.clone());
+        assert_ne!(dropped_bot.get_name(), bot.get_name());
+        dropped_bot.on_message(|_payload: MessagePayload<PuppetMock>, _ctx| async {});
+        bot.on_message(|_payload: MessagePayload<PuppetMock>, _ctx| async {});
+
+        // Subscriptions are handled by the puppet actor in the order they are sent, so no need to wait.
+        drop(dropped_bot);
+        assert_eq!(puppet.subscriber_names(EventName::Message).await, vec![bot.get_name()]);
+        assert_eq!(puppet.subscriber_names(EventName::Dirty).await, vec![bot.get_name()]);
+    }
+
+    #[actix_rt::test]
+    async fn stopping_cancels_warmup() {
+        let mock = PuppetMock::default().with_latency(Duration::from_millis(20));
+        mock.set_room_members(
+            "stop-warmup-room".to_owned(),
+            (0..200).map(|i| format!("stop-warmup-{}", i)).collect(),
+        );
+        let puppet = Puppet::new(mock.clone());
+        let batch_size = puppet.batch_size();
+        let bot = Rc::new(Wechaty::new(puppet));
+        let mut ctx = bot.ctx();
+        ctx.set_id("self".to_owned());
+
+        // Stop the bot from another task while the first batch is being loaded.
+        let stopping_bot = bot.clone();
+        actix_rt::spawn(async move {
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+            stopping_bot.stop().await.unwrap();
+        });
+        assert!(matches!(ctx.warmup().await, Err(WechatyError::Cancelled)));
+        // No load is started after the stop, so only the first batch has been loaded.
+        assert_eq!(mock.call_count("contact_raw_payload"), batch_size);
+        let loaded = (0..200)
+            .filter(|i| ctx.contacts().contains_key(&format!("stop-warmup-{}", i)))
+            .count();
+        assert!(loaded <= batch_size);
+
+        // A stopped bot does not even list the contacts.
+        assert!(matches!(ctx.warmup().await, Err(WechatyError::Cancelled)));
+        assert_eq!(mock.call_count("contact_list"), 1);
+        assert_eq!(mock.call_count("contact_raw_payload"), batch_size);
+
+        // Stopping only cancels the warmup, the finders still load everything.
+        assert_eq!(ctx.contact_find_all(None).await.unwrap().len(), 200);
+        assert!(ctx.contacts().contains_key("stop-warmup-199"));
+    }
+}